@@ -0,0 +1,41 @@
+//! Compares evaluating a pre-compiled expression against scanning, parsing
+//! and evaluating it from source on every call. Run with `cargo bench`.
+use lux::interpreter::Interpreter;
+use lux::value::Value;
+use std::time::Instant;
+
+const SOURCE: &str = "price * (1 + rate) - discount";
+const ITERATIONS: u32 = 100_000;
+
+fn bindings(i: u32) -> [(&'static str, Value); 3] {
+    [
+        ("price", Value::Number(i as f64)),
+        ("rate", Value::Number(0.2)),
+        ("discount", Value::Number(1.5)),
+    ]
+}
+
+fn main() {
+    let mut interpreter = Interpreter::new();
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        let compiled = lux::compile_expression(SOURCE).unwrap();
+        compiled.eval(&mut interpreter, &bindings(i)).unwrap();
+    }
+    let full = start.elapsed();
+
+    let compiled = lux::compile_expression(SOURCE).unwrap();
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        compiled.eval(&mut interpreter, &bindings(i)).unwrap();
+    }
+    let eval_only = start.elapsed();
+
+    println!(
+        "full pipeline: {:?}/call, eval only: {:?}/call ({:.1}x faster)",
+        full / ITERATIONS,
+        eval_only / ITERATIONS,
+        full.as_secs_f64() / eval_only.as_secs_f64()
+    );
+}
@@ -0,0 +1,40 @@
+//! Times a tight loop summing into a local variable, which reads and writes
+//! locals by slot, against the same loop over globals, which are looked up
+//! by name. Run with `cargo bench`.
+use lux::interpreter::Interpreter;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn time(interpreter: &mut Interpreter, source: &str) -> std::time::Duration {
+    let start = Instant::now();
+    interpreter.run_isolated(source).unwrap();
+    start.elapsed()
+}
+
+fn main() {
+    let mut interpreter = Interpreter::new();
+
+    let locals = format!(
+        "fun sum() {{
+            var total = 0;
+            for (var i = 0; i < {ITERATIONS}; i = i + 1) total = total + i;
+            return total;
+        }}
+        sum();"
+    );
+    let globals = format!(
+        "var total = 0;
+        for (var i = 0; i < {ITERATIONS}; i = i + 1) total = total + i;
+        total;"
+    );
+    let locals = time(&mut interpreter, &locals);
+    let globals = time(&mut interpreter, &globals);
+
+    println!(
+        "locals: {:?}/iteration, globals: {:?}/iteration ({:.1}x faster)",
+        locals / ITERATIONS,
+        globals / ITERATIONS,
+        globals.as_secs_f64() / locals.as_secs_f64()
+    );
+}
@@ -0,0 +1,31 @@
+//! Times a tight loop reading a long string variable, which copies the
+//! string on every read unless strings are shared. Run with `cargo bench`.
+use lux::interpreter::Interpreter;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 1_000_000;
+const LENGTH: usize = 10_000;
+
+fn main() {
+    let mut interpreter = Interpreter::new();
+    let source = format!(
+        "var text = \"{}\";
+        fun read() {{
+            var last;
+            for (var i = 0; i < {ITERATIONS}; i = i + 1) last = text;
+            return last;
+        }}
+        read();",
+        "x".repeat(LENGTH)
+    );
+
+    let start = Instant::now();
+    interpreter.run_isolated(&source).unwrap();
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:?}/read of a {}-character string",
+        elapsed / ITERATIONS,
+        LENGTH
+    );
+}
@@ -0,0 +1,17 @@
+//! Times a loop incrementing a global ten million times, which spends most
+//! of its time looking variables up by name. Run with `cargo bench`.
+use lux::interpreter::Interpreter;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 10_000_000;
+
+fn main() {
+    let mut interpreter = Interpreter::new();
+    let source = format!("var i = 0; while (i < {ITERATIONS}) i = i + 1; i;");
+
+    let start = Instant::now();
+    interpreter.run_isolated(&source).unwrap();
+    let elapsed = start.elapsed();
+
+    println!("{:?} total, {:?}/iteration", elapsed, elapsed / ITERATIONS);
+}
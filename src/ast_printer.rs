@@ -1,15 +1,17 @@
 use crate::expressions::{
-    Accept, BinaryExpr, Expr, ExprVisitor, GroupingExpr, LiteralExpr, LiteralValue, UnaryExpr,
+    Accept, AssignExpr, BinaryExpr, CallExpr, ConditionalExpr, ErrorExpr, Expr, ExprVisitor,
+    GetExpr, GroupingExpr, IfExpr, IncrementExpr, IndexExpr, IndexSetExpr, ListExpr, LiteralExpr,
+    LiteralValue, LogicalExpr, MapExpr, SetExpr, SuperExpr, ThisExpr, TupleExpr, UnaryExpr,
     VarExpr,
 };
 
 pub struct AstPrinter;
 impl AstPrinter {
     #[allow(dead_code)]
-    pub fn print(&self, expr: &Expr) -> String {
+    pub fn print(&mut self, expr: &Expr) -> String {
         expr.accept(self)
     }
-    fn parenthesize(&self, name: &str, exprs: &[&Expr]) -> String {
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
         let mut result = String::new();
         result.push('(');
         result.push_str(name);
@@ -22,26 +24,102 @@ impl AstPrinter {
     }
 }
 impl ExprVisitor<String> for AstPrinter {
-    fn visit_unary_expr(&self, expr: &UnaryExpr) -> String {
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> String {
         self.parenthesize(&expr.operator.lexeme, &[&expr.right])
     }
-    fn visit_binary_expr(&self, expr: &BinaryExpr) -> String {
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> String {
         self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
     }
-    fn visit_literal_expr(&self, expr: &LiteralExpr) -> String {
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> String {
         match &expr.value {
+            LiteralValue::Int(v) => format!("{}", v),
             LiteralValue::Number(v) => format!("{}", v),
             LiteralValue::String(v) => v.to_owned(),
             LiteralValue::Bool(v) => format!("{}", v),
             LiteralValue::Nil => String::from("nil"),
         }
     }
-    fn visit_grouping_expr(&self, expr: &GroupingExpr) -> String {
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> String {
         self.parenthesize("group", &[&expr.expr])
     }
-    fn visit_var_expr(&self, expr: &VarExpr) -> String {
+    fn visit_var_expr(&mut self, expr: &VarExpr) -> String {
         self.parenthesize(&format!("var {}", expr.name), &[])
     }
+    fn visit_list_expr(&mut self, expr: &ListExpr) -> String {
+        let elements: Vec<&Expr> = expr.elements.iter().collect();
+        self.parenthesize("list", &elements)
+    }
+    fn visit_tuple_expr(&mut self, expr: &TupleExpr) -> String {
+        let elements: Vec<&Expr> = expr.elements.iter().collect();
+        self.parenthesize("tuple", &elements)
+    }
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> String {
+        let entries: Vec<&Expr> = expr
+            .entries
+            .iter()
+            .flat_map(|(key, value)| [key, value])
+            .collect();
+        self.parenthesize("map", &entries)
+    }
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+    }
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) -> String {
+        self.parenthesize(&format!("= {}", expr.name.lexeme), &[&expr.value])
+    }
+    fn visit_increment_expr(&mut self, expr: &IncrementExpr) -> String {
+        if expr.prefix {
+            format!("({}{})", expr.operator.lexeme, expr.name.lexeme)
+        } else {
+            format!("({}{})", expr.name.lexeme, expr.operator.lexeme)
+        }
+    }
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> String {
+        self.parenthesize("index", &[&expr.object, &expr.index])
+    }
+    fn visit_index_set_expr(&mut self, expr: &IndexSetExpr) -> String {
+        let operator = expr.operator.as_ref().map_or("", |token| &token.lexeme);
+        self.parenthesize(
+            &format!("index{}=", operator),
+            &[&expr.object, &expr.index, &expr.value],
+        )
+    }
+    fn visit_if_expr(&mut self, expr: &IfExpr) -> String {
+        self.parenthesize(
+            "if",
+            &[&expr.condition, &expr.then_branch, &expr.else_branch],
+        )
+    }
+    fn visit_conditional_expr(&mut self, expr: &ConditionalExpr) -> String {
+        self.parenthesize(
+            "?:",
+            &[&expr.condition, &expr.then_branch, &expr.else_branch],
+        )
+    }
+    fn visit_get_expr(&mut self, expr: &GetExpr) -> String {
+        self.parenthesize(&format!(". {}", expr.name.lexeme), &[&expr.object])
+    }
+    fn visit_set_expr(&mut self, expr: &SetExpr) -> String {
+        let operator = expr.operator.as_ref().map_or("", |token| &token.lexeme);
+        self.parenthesize(
+            &format!(".{}= {}", operator, expr.name.lexeme),
+            &[&expr.object, &expr.value],
+        )
+    }
+    fn visit_this_expr(&mut self, _expr: &ThisExpr) -> String {
+        "this".to_string()
+    }
+    fn visit_super_expr(&mut self, expr: &SuperExpr) -> String {
+        format!("(super {})", expr.method.lexeme)
+    }
+    fn visit_call_expr(&mut self, expr: &CallExpr) -> String {
+        let mut exprs = vec![&expr.callee];
+        exprs.extend(expr.arguments.iter());
+        self.parenthesize("call", &exprs)
+    }
+    fn visit_error_expr(&mut self, _expr: &ErrorExpr) -> String {
+        "(error)".to_string()
+    }
 }
 
 #[cfg(test)]
@@ -57,6 +135,9 @@ mod tests {
                     token_type: TokenType::Minus,
                     lexeme: "-".to_string(),
                     line: 1,
+                    column: 1,
+                    span: 0..1,
+                    symbol: None,
                 },
                 right: Expr::Literal(Box::new(LiteralExpr {
                     value: LiteralValue::Number(123.0),
@@ -66,6 +147,9 @@ mod tests {
                 token_type: TokenType::Star,
                 lexeme: "*".to_string(),
                 line: 1,
+                column: 6,
+                span: 5..6,
+                symbol: None,
             },
             right: Expr::Grouping(Box::new(GroupingExpr {
                 expr: Expr::Literal(Box::new(LiteralExpr {
@@ -73,8 +157,8 @@ mod tests {
                 })),
             })),
         }));
-        let visitor = AstPrinter {};
-        let printed = expression.accept(&visitor);
+        let mut visitor = AstPrinter {};
+        let printed = expression.accept(&mut visitor);
         assert_eq!(printed, "(* (- 123) (group abc))")
     }
 }
@@ -0,0 +1,110 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::function::{Callable, Function};
+use crate::value::Value;
+
+/// A class declared in a script. Calling it creates an instance.
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+    /// Assigns the defaults of the fields declared in the class body, on
+    /// each new instance before `init` runs.
+    pub field_initializer: Option<Rc<Function>>,
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Rc<Function>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+            field_initializer: None,
+        }
+    }
+
+    /// Look up a method on this class, then up the superclass chain.
+    pub fn find_method(&self, name: &str) -> Option<&Rc<Function>> {
+        match self.methods.get(name) {
+            Some(method) => Some(method),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    /// The number of arguments a call to the class takes, which are passed
+    /// on to `init`.
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    pub fn is_variadic(&self) -> bool {
+        self.find_method("init")
+            .is_some_and(|init| init.is_variadic())
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// An object created by calling a class, holding its own fields.
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: RefCell<HashMap<String, Value>>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Self {
+            class,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.fields.borrow().get(name).cloned()
+    }
+
+    /// The names of the instance's fields, sorted.
+    pub fn field_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.fields.borrow().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// The method `name` of the instance's class, bound to this instance.
+    pub fn method(self: &Rc<Self>, name: &str) -> Option<Function> {
+        let method = self.class.find_method(name)?;
+        Some(method.bind(Rc::clone(self)))
+    }
+
+    pub fn set(&self, name: &str, value: Value) {
+        self.fields.borrow_mut().insert(name.to_string(), value);
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+// Fields can refer back to the instance, so deriving this could recurse
+// forever.
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instance")
+            .field("class", &self.class.name)
+            .finish_non_exhaustive()
+    }
+}
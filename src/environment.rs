@@ -1,16 +1,319 @@
-use crate::{expressions::LiteralValue, token::Token};
-use std::collections::HashMap;
+use crate::{
+    symbol::{Symbol, SymbolMap},
+    token::Token,
+    value::Value,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
 
-#[derive(Default, Debug)]
+/// A scope of variable bindings. Scopes are shared rather than owned: the
+/// interpreter holds the current one while it runs, and every function
+/// declared inside a scope keeps it alive for as long as the function
+/// exists, so variables outlive the block that declared them.
+///
+/// Names are interned symbols. Global scopes hold their bindings in a map
+/// keyed by symbol. Local scopes hold them in the order they are defined,
+/// which is the order the resolver numbers them in, so a resolved variable
+/// is found by its distance and slot without looking at its name at all.
+#[derive(Clone, Default, Debug)]
 pub struct Environment {
-    values: HashMap<String, LiteralValue>,
+    bindings: Bindings,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+    /// Whether assigning to a name bound only in an enclosing scope binds it
+    /// here instead, leaving the outer binding as it was.
+    copy_on_write: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Bindings {
+    Named(SymbolMap<Value>),
+    /// The names are kept for code that was not resolved, such as a
+    /// compiled expression, which looks variables up by name.
+    Slots(Vec<(Symbol, Value)>),
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::Named(SymbolMap::default())
+    }
 }
 
 impl Environment {
-    pub fn define(&mut self, name: String, value: LiteralValue) {
-        self.values.insert(name, value);
+    /// The outermost scope, with nothing enclosing it.
+    pub fn new_global() -> Self {
+        Self::default()
+    }
+    /// A global scope whose unknown names are looked up in `enclosing`, as
+    /// the globals of a script are in the scope of the natives.
+    pub fn new_global_within(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            bindings: Bindings::default(),
+            enclosing: Some(enclosing),
+            copy_on_write: false,
+        }
+    }
+    /// A global scope like `new_global_within`, except that assignments to
+    /// the names of `enclosing` shadow them in the new scope rather than
+    /// changing them.
+    pub fn new_copy_on_write_within(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            copy_on_write: true,
+            ..Self::new_global_within(enclosing)
+        }
+    }
+    /// A local scope nested in `enclosing`. Names not found in it are looked
+    /// up and assigned in `enclosing`, but `define` always binds in the new
+    /// scope, which may shadow a binding outside it.
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            bindings: Bindings::Slots(Vec::new()),
+            enclosing: Some(enclosing),
+            copy_on_write: false,
+        }
+    }
+    /// The scope `distance` steps out from `environment`, where the resolver
+    /// found the declaration of a local variable.
+    pub fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(environment);
+        for _ in 0..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolved scope exists at runtime");
+            environment = enclosing;
+        }
+        environment
+    }
+    /// The local in `slot` of the scope `distance` steps out from
+    /// `environment`.
+    pub fn get_at(environment: &Rc<RefCell<Environment>>, distance: usize, slot: usize) -> Value {
+        Self::ancestor(environment, distance)
+            .borrow()
+            .slot(slot)
+            .clone()
+    }
+    pub fn assign_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        slot: usize,
+        value: Value,
+    ) {
+        *Self::ancestor(environment, distance)
+            .borrow_mut()
+            .slot_mut(slot) = value;
+    }
+    fn slot(&self, slot: usize) -> &Value {
+        match &self.bindings {
+            Bindings::Slots(slots) => &slots.get(slot).expect("resolved slot exists at runtime").1,
+            Bindings::Named(_) => unreachable!("locals are resolved to local scopes"),
+        }
+    }
+    fn slot_mut(&mut self, slot: usize) -> &mut Value {
+        match &mut self.bindings {
+            Bindings::Slots(slots) => {
+                &mut slots
+                    .get_mut(slot)
+                    .expect("resolved slot exists at runtime")
+                    .1
+            }
+            Bindings::Named(_) => unreachable!("locals are resolved to local scopes"),
+        }
+    }
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        match &mut self.bindings {
+            Bindings::Named(values) => {
+                values.insert(name, value);
+            }
+            Bindings::Slots(slots) => slots.push((name, value)),
+        }
+    }
+    pub fn get(&self, name: &Token) -> Result<Value, &'static str> {
+        self.lookup(name.symbol()).ok_or("undefined variable")
+    }
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), &'static str> {
+        match self.own_mut(name.symbol()) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => match &self.enclosing {
+                Some(enclosing) if self.copy_on_write => {
+                    enclosing.borrow().get(name)?;
+                    self.define(name.symbol(), value);
+                    Ok(())
+                }
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => Err("undefined variable"),
+            },
+        }
+    }
+    /// The value of `name` in this scope alone, ignoring enclosing scopes.
+    pub fn get_own(&self, name: Symbol) -> Option<Value> {
+        self.own(name).cloned()
     }
-    pub fn get(&self, name: &Token) -> Result<&LiteralValue, &'static str> {
-        self.values.get(&name.lexeme).ok_or("undefined variabel")
+    pub fn bindings(&self) -> Box<dyn Iterator<Item = (Symbol, &Value)> + '_> {
+        match &self.bindings {
+            Bindings::Named(values) => Box::new(values.iter().map(|(name, value)| (*name, value))),
+            Bindings::Slots(slots) => Box::new(slots.iter().map(|(name, value)| (*name, value))),
+        }
+    }
+    pub fn lookup(&self, name: Symbol) -> Option<Value> {
+        match self.own(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().lookup(name),
+        }
+    }
+    fn own(&self, name: Symbol) -> Option<&Value> {
+        match &self.bindings {
+            Bindings::Named(values) => values.get(&name),
+            // The latest definition of a name wins, as in a map.
+            Bindings::Slots(slots) => slots
+                .iter()
+                .rev()
+                .find(|(slot_name, _)| *slot_name == name)
+                .map(|(_, value)| value),
+        }
+    }
+    fn own_mut(&mut self, name: Symbol) -> Option<&mut Value> {
+        match &mut self.bindings {
+            Bindings::Named(values) => values.get_mut(&name),
+            Bindings::Slots(slots) => slots
+                .iter_mut()
+                .rev()
+                .find(|(slot_name, _)| *slot_name == name)
+                .map(|(_, value)| value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenType;
+
+    fn name(lexeme: &str) -> Token {
+        Token {
+            token_type: TokenType::Identifier,
+            lexeme: lexeme.to_string(),
+            line: 1,
+            column: 1,
+            span: 0..lexeme.len(),
+            symbol: Some(Symbol::intern(lexeme)),
+        }
+    }
+
+    /// A global scope with a block and a scope inside that, innermost last.
+    fn scopes() -> [Rc<RefCell<Environment>>; 3] {
+        let global = Rc::new(RefCell::new(Environment::new_global()));
+        let block = Rc::new(RefCell::new(Environment::with_enclosing(Rc::clone(
+            &global,
+        ))));
+        let inner = Rc::new(RefCell::new(Environment::with_enclosing(Rc::clone(&block))));
+        [global, block, inner]
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let [global, block, inner] = scopes();
+        global
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Int(1));
+        block
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Int(2));
+        assert_eq!(inner.borrow().get(&name("a")), Ok(Value::Int(2)));
+        assert_eq!(global.borrow().get(&name("a")), Ok(Value::Int(1)));
+        assert_eq!(inner.borrow().get_own(Symbol::intern("a")), None);
+        // Redefining in the same scope replaces the binding.
+        inner
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Int(3));
+        inner
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Int(4));
+        assert_eq!(inner.borrow().get(&name("a")), Ok(Value::Int(4)));
+        assert_eq!(block.borrow().get(&name("a")), Ok(Value::Int(2)));
+    }
+
+    #[test]
+    fn test_assign_outer_scopes() {
+        let [global, block, inner] = scopes();
+        global
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Int(1));
+        block
+            .borrow_mut()
+            .define(Symbol::intern("b"), Value::Int(1));
+        inner
+            .borrow_mut()
+            .assign(&name("a"), Value::Int(2))
+            .unwrap();
+        inner
+            .borrow_mut()
+            .assign(&name("b"), Value::Int(3))
+            .unwrap();
+        assert_eq!(
+            global.borrow().get_own(Symbol::intern("a")),
+            Some(Value::Int(2))
+        );
+        assert_eq!(
+            block.borrow().get_own(Symbol::intern("b")),
+            Some(Value::Int(3))
+        );
+        assert_eq!(inner.borrow().bindings().count(), 0);
+        // Only the nearest binding is assigned.
+        block
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Int(5));
+        inner
+            .borrow_mut()
+            .assign(&name("a"), Value::Int(6))
+            .unwrap();
+        assert_eq!(
+            block.borrow().get_own(Symbol::intern("a")),
+            Some(Value::Int(6))
+        );
+        assert_eq!(
+            global.borrow().get_own(Symbol::intern("a")),
+            Some(Value::Int(2))
+        );
+    }
+
+    #[test]
+    fn test_undefined_names() {
+        let [global, block, inner] = scopes();
+        inner.borrow_mut().define(Symbol::intern("c"), Value::Nil);
+        for scope in [&global, &block] {
+            assert_eq!(scope.borrow().get(&name("c")), Err("undefined variable"));
+            assert_eq!(
+                scope.borrow_mut().assign(&name("c"), Value::Nil),
+                Err("undefined variable")
+            );
+        }
+        assert_eq!(inner.borrow().get(&name("d")), Err("undefined variable"));
+        assert_eq!(
+            inner.borrow_mut().assign(&name("d"), Value::Nil),
+            Err("undefined variable")
+        );
+        assert_eq!(global.borrow().lookup(Symbol::intern("d")), None);
+    }
+
+    #[test]
+    fn test_ancestor() {
+        let [global, block, inner] = scopes();
+        global
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Int(1));
+        assert!(Rc::ptr_eq(&Environment::ancestor(&inner, 0), &inner));
+        assert!(Rc::ptr_eq(&Environment::ancestor(&inner, 1), &block));
+        let outermost = Environment::ancestor(&inner, 2);
+        assert_eq!(
+            outermost.borrow().get_own(Symbol::intern("a")),
+            Some(Value::Int(1))
+        );
     }
 }
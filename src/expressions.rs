@@ -1,22 +1,52 @@
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::token::Token;
 
+/// Identifies a variable reference so that the interpreter can find what
+/// the resolver worked out about it. Ids are never reused, even across
+/// separate parses, and later parses get greater ids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    pub fn fresh() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
 pub trait Accept<R> {
-    fn accept(&self, visitor: &impl ExprVisitor<R>) -> R;
+    fn accept(&self, visitor: &mut impl ExprVisitor<R>) -> R;
 }
 
 pub trait ExprVisitor<R> {
-    fn visit_binary_expr(&self, expr: &BinaryExpr) -> R;
-    fn visit_unary_expr(&self, expr: &UnaryExpr) -> R;
-    fn visit_literal_expr(&self, expr: &LiteralExpr) -> R;
-    fn visit_grouping_expr(&self, expr: &GroupingExpr) -> R;
-    fn visit_var_expr(&self, expr: &VarExpr) -> R;
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> R;
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> R;
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> R;
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> R;
+    fn visit_var_expr(&mut self, expr: &VarExpr) -> R;
+    fn visit_list_expr(&mut self, expr: &ListExpr) -> R;
+    fn visit_tuple_expr(&mut self, expr: &TupleExpr) -> R;
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> R;
+    fn visit_if_expr(&mut self, expr: &IfExpr) -> R;
+    fn visit_conditional_expr(&mut self, expr: &ConditionalExpr) -> R;
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> R;
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> R;
+    fn visit_index_set_expr(&mut self, expr: &IndexSetExpr) -> R;
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) -> R;
+    fn visit_increment_expr(&mut self, expr: &IncrementExpr) -> R;
+    fn visit_call_expr(&mut self, expr: &CallExpr) -> R;
+    fn visit_get_expr(&mut self, expr: &GetExpr) -> R;
+    fn visit_set_expr(&mut self, expr: &SetExpr) -> R;
+    fn visit_this_expr(&mut self, expr: &ThisExpr) -> R;
+    fn visit_super_expr(&mut self, expr: &SuperExpr) -> R;
+    fn visit_error_expr(&mut self, expr: &ErrorExpr) -> R;
 }
 
 #[macro_export]
 macro_rules! ast_node {
-    ( $node_name:ident,  $(($field_name:ident, $field_type:ident)),* ) => {
+    ( $node_name:ident,  $(($field_name:ident, $field_type:ty)),* ) => {
         #[derive(Clone, Debug)]
         pub struct $node_name {
             $(
@@ -26,7 +56,7 @@ macro_rules! ast_node {
 
         paste::paste! {
         impl<'a, R> Accept<R> for $node_name {
-           fn accept(&self, visitor: &impl ExprVisitor<R>) -> R {
+           fn accept(&self, visitor: &mut impl ExprVisitor<R>) -> R {
                visitor.[<visit_ $node_name:snake>](self)
            }
         }
@@ -37,6 +67,7 @@ macro_rules! ast_node {
 #[derive(Clone, PartialEq, Debug)]
 pub enum LiteralValue {
     String(String),
+    Int(i64),
     Number(f64),
     Bool(bool),
     Nil,
@@ -46,6 +77,7 @@ impl fmt::Display for LiteralValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let message = match self {
             Self::Bool(value) => format!("Bool({})", value),
+            Self::Int(value) => format!("Int({})", value),
             Self::Number(value) => format!("Number({})", value),
             Self::String(value) => format!("String({})", value),
             Self::Nil => "Nil".to_string(),
@@ -58,7 +90,65 @@ ast_node!(BinaryExpr, (left, Expr), (operator, Token), (right, Expr));
 ast_node!(UnaryExpr, (operator, Token), (right, Expr));
 ast_node!(LiteralExpr, (value, LiteralValue));
 ast_node!(GroupingExpr, (expr, Expr));
-ast_node!(VarExpr, (name, Token));
+ast_node!(VarExpr, (id, ExprId), (name, Token));
+ast_node!(LogicalExpr, (left, Expr), (operator, Token), (right, Expr));
+ast_node!(AssignExpr, (id, ExprId), (name, Token), (value, Expr));
+// `i++` or `--i`, where `operator` is `++` or `--`. The prefix forms give the
+// new value and the postfix forms the old one.
+ast_node!(
+    IncrementExpr,
+    (id, ExprId),
+    (name, Token),
+    (operator, Token),
+    (prefix, bool)
+);
+ast_node!(IndexExpr, (object, Expr), (bracket, Token), (index, Expr));
+// `operator` is set for compound assignments, as for `SetExpr`.
+ast_node!(
+    IndexSetExpr,
+    (object, Expr),
+    (bracket, Token),
+    (index, Expr),
+    (operator, Option<Token>),
+    (value, Expr)
+);
+ast_node!(ListExpr, (elements, Vec<Expr>));
+ast_node!(TupleExpr, (elements, Vec<Expr>));
+ast_node!(MapExpr, (brace, Token), (entries, Vec<(Expr, Expr)>));
+ast_node!(
+    ConditionalExpr,
+    (condition, Expr),
+    (then_branch, Expr),
+    (else_branch, Expr)
+);
+ast_node!(GetExpr, (object, Expr), (name, Token));
+// `operator` is set for compound assignments like `a.b += c`, which read the
+// property and apply the operator before storing, evaluating `a` only once.
+ast_node!(
+    SetExpr,
+    (object, Expr),
+    (name, Token),
+    (operator, Option<Token>),
+    (value, Expr)
+);
+ast_node!(ThisExpr, (id, ExprId), (keyword, Token));
+ast_node!(SuperExpr, (id, ExprId), (keyword, Token), (method, Token));
+ast_node!(
+    CallExpr,
+    (callee, Expr),
+    (paren, Token),
+    (arguments, Vec<Expr>)
+);
+ast_node!(
+    IfExpr,
+    (condition, Expr),
+    (then_branch, Expr),
+    (else_branch, Expr)
+);
+// Stands in for an expression that failed to parse, at the token where it
+// went wrong, so the parser can carry on and report later mistakes in the
+// same statement. A tree only contains one when parsing failed.
+ast_node!(ErrorExpr, (token, Token));
 
 // Box is necessary because expression created inside a function
 // needs to be owned
@@ -69,16 +159,78 @@ pub enum Expr {
     Literal(Box<LiteralExpr>),
     Grouping(Box<GroupingExpr>),
     Variable(Box<VarExpr>),
+    List(Box<ListExpr>),
+    Tuple(Box<TupleExpr>),
+    Map(Box<MapExpr>),
+    If(Box<IfExpr>),
+    Conditional(Box<ConditionalExpr>),
+    Logical(Box<LogicalExpr>),
+    Index(Box<IndexExpr>),
+    IndexSet(Box<IndexSetExpr>),
+    Assign(Box<AssignExpr>),
+    Increment(Box<IncrementExpr>),
+    Call(Box<CallExpr>),
+    Get(Box<GetExpr>),
+    Set(Box<SetExpr>),
+    This(Box<ThisExpr>),
+    Super(Box<SuperExpr>),
+    Error(Box<ErrorExpr>),
+}
+
+impl Expr {
+    /// The token that runtime errors raised by this expression itself are
+    /// reported at, if it has one.
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            Self::Binary(expr) => Some(&expr.operator),
+            Self::Unary(expr) => Some(&expr.operator),
+            Self::Logical(expr) => Some(&expr.operator),
+            Self::Variable(expr) => Some(&expr.name),
+            Self::Assign(expr) => Some(&expr.name),
+            Self::Increment(expr) => Some(&expr.operator),
+            Self::Map(expr) => Some(&expr.brace),
+            Self::Index(expr) => Some(&expr.bracket),
+            Self::IndexSet(expr) => Some(&expr.bracket),
+            Self::Call(expr) => Some(&expr.paren),
+            Self::Get(expr) => Some(&expr.name),
+            Self::Set(expr) => Some(&expr.name),
+            Self::This(expr) => Some(&expr.keyword),
+            Self::Super(expr) => Some(&expr.method),
+            Self::Error(expr) => Some(&expr.token),
+            Self::Literal(_)
+            | Self::Grouping(_)
+            | Self::List(_)
+            | Self::Tuple(_)
+            | Self::If(_)
+            | Self::Conditional(_) => None,
+        }
+    }
 }
 
 impl<R> Accept<R> for Expr {
-    fn accept(&self, visitor: &impl ExprVisitor<R>) -> R {
+    fn accept(&self, visitor: &mut impl ExprVisitor<R>) -> R {
         match self {
             Self::Binary(expr) => expr.accept(visitor),
             Self::Unary(expr) => expr.accept(visitor),
             Self::Literal(expr) => expr.accept(visitor),
             Self::Grouping(expr) => expr.accept(visitor),
             Self::Variable(expr) => expr.accept(visitor),
+            Self::List(expr) => expr.accept(visitor),
+            Self::Tuple(expr) => expr.accept(visitor),
+            Self::Map(expr) => expr.accept(visitor),
+            Self::If(expr) => expr.accept(visitor),
+            Self::Conditional(expr) => expr.accept(visitor),
+            Self::Logical(expr) => expr.accept(visitor),
+            Self::Index(expr) => expr.accept(visitor),
+            Self::IndexSet(expr) => expr.accept(visitor),
+            Self::Assign(expr) => expr.accept(visitor),
+            Self::Increment(expr) => expr.accept(visitor),
+            Self::Call(expr) => expr.accept(visitor),
+            Self::Get(expr) => expr.accept(visitor),
+            Self::Set(expr) => expr.accept(visitor),
+            Self::This(expr) => expr.accept(visitor),
+            Self::Super(expr) => expr.accept(visitor),
+            Self::Error(expr) => expr.accept(visitor),
         }
     }
 }
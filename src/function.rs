@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::class::Instance;
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::statements::FunctionStmt;
+use crate::symbol::Symbol;
+use crate::value::Value;
+
+/// Anything a script can call. The interpreter checks the number of
+/// arguments against `arity` before calling `call`.
+pub trait Callable: fmt::Debug + fmt::Display {
+    fn name(&self) -> &str;
+    fn arity(&self) -> usize;
+    /// Whether calls may pass more arguments than `arity`, which is then
+    /// the minimum.
+    fn is_variadic(&self) -> bool {
+        false
+    }
+    /// This as a function declared in a script, if it is one.
+    fn as_function(&self) -> Option<&Function> {
+        None
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError>;
+}
+
+/// A function declared in a script, as stored in a variable once its
+/// declaration has run. It keeps the scope it was declared in, so the body
+/// sees the variables around the declaration rather than those of the
+/// caller.
+pub struct Function {
+    pub declaration: FunctionStmt,
+    pub closure: Rc<RefCell<Environment>>,
+    /// The top-level scope of the file the function was declared in, where
+    /// its body looks up globals even when called from another module.
+    pub globals: Rc<RefCell<Environment>>,
+    /// Whether this is a class's `init` method, which always returns the
+    /// instance it is bound to.
+    pub is_initializer: bool,
+}
+
+impl Function {
+    pub fn new(
+        declaration: FunctionStmt,
+        closure: Rc<RefCell<Environment>>,
+        globals: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            globals,
+            is_initializer,
+        }
+    }
+
+    /// This method with `this` bound to `instance`, as returned when the
+    /// method is looked up on the instance.
+    pub fn bind(&self, instance: Rc<Instance>) -> Function {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define(Symbol::THIS, Value::Instance(instance));
+        Function::new(
+            self.declaration.clone(),
+            Rc::new(RefCell::new(environment)),
+            Rc::clone(&self.globals),
+            self.is_initializer,
+        )
+    }
+}
+
+impl Callable for Function {
+    fn name(&self) -> &str {
+        &self.declaration.name.lexeme
+    }
+
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn is_variadic(&self) -> bool {
+        self.declaration.rest.is_some()
+    }
+
+    fn as_function(&self) -> Option<&Function> {
+        Some(self)
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        interpreter.call_function(self, arguments)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+// The closure usually contains the function itself, so deriving this would
+// recurse forever.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The signature of a host function exposed to scripts.
+pub type NativeFn = fn(&mut Interpreter, Vec<Value>) -> Result<Value, RuntimeError>;
+
+/// A function implemented in Rust, such as the `clock` builtin.
+#[derive(Debug)]
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    variadic: bool,
+    function: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: usize, function: NativeFn) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            variadic: false,
+            function,
+        }
+    }
+
+    /// A native that takes `min_arity` or more arguments.
+    pub fn variadic(name: &str, min_arity: usize, function: NativeFn) -> Self {
+        Self {
+            variadic: true,
+            ..Self::new(name, min_arity, function)
+        }
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn is_variadic(&self) -> bool {
+        self.variadic
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        (self.function)(interpreter, arguments)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
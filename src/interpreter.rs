@@ -1,162 +1,3605 @@
+use crate::class::{Class, Instance};
 use crate::environment::Environment;
 use crate::expressions::{
-    Accept, BinaryExpr, Expr, ExprVisitor, GroupingExpr, LiteralExpr, LiteralValue, UnaryExpr,
-    VarExpr,
+    Accept, AssignExpr, BinaryExpr, CallExpr, ConditionalExpr, ErrorExpr, Expr, ExprId,
+    ExprVisitor, GetExpr, GroupingExpr, IfExpr, IncrementExpr, IndexExpr, IndexSetExpr, ListExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SuperExpr, ThisExpr, TupleExpr, UnaryExpr, VarExpr,
 };
+use crate::function::{Callable, Function, NativeFn, NativeFunction};
+use crate::module::Module;
+use crate::natives::{self, Rng};
+use crate::parser::Parser;
+use crate::reporter::BufferedReporter;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
 use crate::statements::Accept as StmtAccept;
-use crate::statements::{ExprStmt, PrintStmt, Stmt, StmtVisitor, VarStmt};
-use crate::token::TokenType;
+use crate::statements::{
+    BlockStmt, BreakStmt, ClassStmt, ContinueStmt, DestructureStmt, DoWhileStmt, EprintStmt,
+    ExprStmt, ForInStmt, FunctionStmt, IfStmt, ImportStmt, PrintStmt, ReturnStmt, Stmt,
+    StmtVisitor, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use crate::symbol::Symbol;
+use crate::token::{Token, TokenType};
+use crate::value::{deep_eq, HashableValue, Value};
+use crate::LuxError;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
+pub enum RuntimeError {
+    Message(&'static str),
+    /// A message that names the runtime value at fault.
+    Detailed(String),
+    /// A value raised by `throw` that no `catch` handled, as `print` shows
+    /// it. The value itself stays with the interpreter, which keeps errors
+    /// `Send`.
+    Thrown(String),
+    /// Execution was stopped through an `InterruptHandle`.
+    Interrupted,
+    /// `error`, raised by the code at the token, as added by `at`.
+    At(Box<Token>, Box<RuntimeError>),
+    /// `error`, with the calls it unwound out of, innermost first, as added
+    /// by `in_frame`. This always wraps any location.
+    Traced(Vec<Frame>, Box<RuntimeError>),
+}
+
+/// A call that an error unwound out of, as shown in its stack trace.
+#[derive(Debug, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    /// The line the call was made on, or `None` for a native function.
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "in {} (line {})", self.name, line),
+            None => write!(f, "in {}", self.name),
+        }
+    }
+}
+
+impl RuntimeError {
+    /// This error located at `token`, unless it has a location already,
+    /// which is then the closer one to where it was raised. Interrupts
+    /// happen wherever the script had got to, so they are never located.
+    pub fn at(self, token: &Token) -> Self {
+        match self {
+            Self::At(..) | Self::Interrupted => self,
+            Self::Traced(frames, error) => Self::Traced(frames, Box::new(error.at(token))),
+            error => Self::At(Box::new(token.clone()), Box::new(error)),
+        }
+    }
+
+    /// This error with `frame` added to its trace as it unwinds out of
+    /// that call. Interrupts aren't traced, for the same reason that they
+    /// aren't located.
+    fn in_frame(self, frame: Frame) -> Self {
+        match self {
+            Self::Interrupted => self,
+            Self::Traced(mut frames, error) => {
+                frames.push(frame);
+                Self::Traced(frames, error)
+            }
+            error => Self::Traced(vec![frame], Box::new(error)),
+        }
+    }
+
+    /// The token of the code that raised this error, if known.
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            Self::At(token, _) => Some(token),
+            Self::Traced(_, error) => error.token(),
+            _ => None,
+        }
+    }
+
+    /// The calls that were running when this error was raised, innermost
+    /// first.
+    pub fn trace(&self) -> &[Frame] {
+        match self {
+            Self::Traced(frames, _) => frames,
+            _ => &[],
+        }
+    }
+
+    /// The line of the code that raised this error, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.token().map(|token| token.line)
+    }
+
+    /// This error without its location or trace.
+    pub fn into_unlocated(self) -> RuntimeError {
+        match self {
+            Self::At(_, error) => *error,
+            Self::Traced(_, error) => error.into_unlocated(),
+            error => error,
+        }
+    }
+
+    /// This error with `context` appended to its message, keeping its
+    /// location. Thrown values and interrupts are passed on unchanged.
+    fn with_context(self, context: &str) -> Self {
+        match self {
+            Self::Message(_) | Self::Detailed(_) => {
+                Self::Detailed(format!("{} ({})", self, context))
+            }
+            Self::At(token, error) => Self::At(token, Box::new(error.with_context(context))),
+            Self::Traced(frames, error) => {
+                Self::Traced(frames, Box::new(error.with_context(context)))
+            }
+            error => error,
+        }
+    }
+}
+
+impl From<&'static str> for RuntimeError {
+    fn from(message: &'static str) -> Self {
+        Self::Message(message)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Message(message) => write!(f, "{}", message),
+            Self::Detailed(message) => write!(f, "{}", message),
+            Self::Thrown(value) => write!(f, "uncaught exception: {}", value),
+            Self::Interrupted => write!(f, "execution interrupted"),
+            Self::At(_, error) | Self::Traced(_, error) => write!(f, "{}", error),
+        }
+    }
+}
+
+const OVERFLOW_ERROR: &str = "integer overflow";
+const STAR_ERROR: &str = "* can only act on a pair of numbers or a string and a number";
+
+/// How deeply calls may nest unless `set_max_call_depth` says otherwise.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Why a statement stopped executing before reaching its end. Loops catch
+/// `Break` and `Continue`, function calls catch `Return`; errors propagate
+/// to the caller of `interpret`.
+#[derive(Debug, PartialEq)]
+pub enum Unwind {
+    Break,
+    Continue,
+    Return(Value),
+    /// A `return` whose value is a call to a script function, which the
+    /// function returning makes in place of itself rather than nesting a
+    /// new call inside it. Holds the callee and the arguments.
+    TailCall(Value, Vec<Value>),
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Self::Error(error)
+    }
+}
+
+impl Unwind {
+    /// The error to report when unwinding reaches the top level. The parser
+    /// only accepts `break` and `continue` inside a loop and `return` inside
+    /// a function, so that can only come from a
+    /// hand-built syntax tree.
+    fn into_error(self) -> RuntimeError {
+        match self {
+            Self::Error(error) => error,
+            Self::Break => RuntimeError::Message("'break' outside loop"),
+            Self::Continue => RuntimeError::Message("'continue' outside loop"),
+            Self::Return(_) | Self::TailCall(..) => {
+                RuntimeError::Message("'return' outside function")
+            }
+        }
+    }
+}
+
+/// Stops a running interpreter from another thread. The interpreter notices
+/// at the next statement boundary or loop iteration and returns
+/// `RuntimeError::Interrupted`; the request is consumed so the interpreter
+/// can be used again afterwards.
+#[derive(Clone, Debug)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+}
 
 pub struct Interpreter {
-    environment: Environment,
+    /// Native functions. The globals scope is nested inside this one, so a
+    /// script variable named like a native shadows it rather than replacing
+    /// it, and `reset` brings the native back.
+    natives: Rc<RefCell<Environment>>,
+    /// The top-level scope of scripts, where unresolved names are looked up.
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    /// What the resolver found for each variable reference: how many scopes
+    /// out from the current one its declaration lives and its slot there,
+    /// or `None` for a global. References missing from the table, e.g. in a
+    /// compiled expression, search outward through the scopes by name
+    /// instead.
+    locals: HashMap<ExprId, Option<(usize, usize)>>,
+    interrupted: Arc<AtomicBool>,
+    /// The class of the objects a `catch` receives for errors raised by the
+    /// interpreter itself, which carry the error text in `message`.
+    error_class: Rc<Class>,
+    /// The value of the `throw` currently unwinding, for `catch` to take.
+    thrown: Option<Value>,
+    /// Imported modules by canonical path. A module maps to `None` while its
+    /// top level is still running, so importing it again is a cycle.
+    modules: HashMap<PathBuf, Option<Rc<Module>>>,
+    /// The file whose top level is running, which imports are relative to.
+    current_file: Option<PathBuf>,
+    /// The generator behind `random` and `randomInt`.
+    rng: Rng,
+    /// Refuse to concatenate a string with a value of another type instead
+    /// of stringifying that value.
+    strict: bool,
+    /// Let dividing a float by zero give infinity or NaN, as in IEEE 754,
+    /// rather than failing.
+    ieee_division: bool,
+    /// How many calls are running, and how many may run at once before a
+    /// call fails, which stops unbounded recursion before it overflows the
+    /// Rust stack.
+    call_depth: usize,
+    max_call_depth: usize,
+    /// How many `try` statements are running in the current call. A
+    /// `return` inside one is not a tail call, since the `catch` and
+    /// `finally` blocks must see what the call does.
+    try_depth: usize,
+    /// Where `readLine` reads from, or `None` for stdin. Stdin is locked
+    /// only for the duration of each read, so that interpreters on the same
+    /// thread don't wait on each other.
+    stdin: Option<Box<dyn BufRead>>,
+    stdout: Box<dyn Write>,
+    stderr: Box<dyn Write>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl Interpreter {
     pub fn new() -> Self {
-        Self {
-            environment: Environment::default(),
+        Self::with_output(io::stdout(), io::stderr())
+    }
+
+    /// Create an interpreter that writes `print` output to `stdout` and
+    /// `eprint` output to `stderr`, e.g. to capture them in tests.
+    pub fn with_output(stdout: impl Write + 'static, stderr: impl Write + 'static) -> Self {
+        let natives = Rc::new(RefCell::new(Environment::new_global()));
+        let globals = Rc::new(RefCell::new(Environment::new_global_within(Rc::clone(
+            &natives,
+        ))));
+        let mut interpreter = Self {
+            natives,
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            error_class: Rc::new(Class::new("Error".to_string(), None, HashMap::new())),
+            thrown: None,
+            modules: HashMap::new(),
+            current_file: None,
+            rng: Rng::from_time(),
+            strict: false,
+            ieee_division: false,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            try_depth: 0,
+            stdin: None,
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+        };
+        natives::define_all(&mut interpreter);
+        interpreter
+    }
+
+    /// Expose a host function to scripts under `name`.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = NativeFunction::new(name, arity, function);
+        self.natives
+            .borrow_mut()
+            .define(Symbol::intern(name), Value::Callable(Rc::new(native)));
+    }
+
+    /// Like `define_native`, but calls may pass any number of arguments from
+    /// `min_arity` up.
+    pub fn define_variadic_native(&mut self, name: &str, min_arity: usize, function: NativeFn) {
+        let native = NativeFunction::variadic(name, min_arity, function);
+        self.natives
+            .borrow_mut()
+            .define(Symbol::intern(name), Value::Callable(Rc::new(native)));
+    }
+
+    /// In strict mode `+` only adds two numbers or joins two strings, so
+    /// `"score: " + 42` is an error rather than `"score: 42"`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Make dividing by zero give infinity or NaN, as IEEE 754 floats do,
+    /// instead of being a runtime error. Ints have neither, so dividing an
+    /// int by the int 0 is an error either way.
+    pub fn set_ieee_division(&mut self, ieee_division: bool) {
+        self.ieee_division = ieee_division;
+    }
+
+    /// Limit how deeply calls may nest; a call beyond the limit is a runtime
+    /// error. Each call takes several frames of the Rust stack, so raising
+    /// the limit far above the default may need a bigger stack.
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// Record that the program about to run was read from `path`, so its
+    /// imports resolve relative to it and a module importing it back is
+    /// reported as a circular import.
+    pub fn set_script_path(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.modules.insert(path.clone(), None);
+        self.current_file = Some(path);
+    }
+
+    pub(crate) fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// Replace the source scripts read input from, which defaults to stdin.
+    pub fn set_input(&mut self, stdin: impl BufRead + 'static) {
+        self.stdin = Some(Box::new(stdin));
+    }
+
+    /// Read the next line of input with its line ending stripped, or `None`
+    /// at end of input.
+    pub fn read_line(&mut self) -> Result<Option<String>, RuntimeError> {
+        let mut buf = Vec::new();
+        let bytes = match &mut self.stdin {
+            Some(stdin) => stdin.read_until(b'\n', &mut buf),
+            None => io::stdin().lock().read_until(b'\n', &mut buf),
+        }
+        .map_err(|_| "failed to read from stdin")?;
+        if bytes == 0 {
+            return Ok(None);
+        }
+        if buf.ends_with(b"\n") {
+            buf.pop();
+            if buf.ends_with(b"\r") {
+                buf.pop();
+            }
         }
+        String::from_utf8(buf)
+            .map(Some)
+            .map_err(|_| "input is not valid UTF-8".into())
     }
 
-    pub fn interpret(&mut self, statements: &Vec<Stmt>) -> Result<(), &'static str> {
-        for stmt in statements {
-            self.execute(stmt)?;
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            interrupted: Arc::clone(&self.interrupted),
         }
-        Ok(())
     }
 
-    pub fn execute(&mut self, stmt: &Stmt) -> Result<(), &'static str> {
-        stmt.accept(self)
+    fn check_interrupt(&self) -> Result<(), RuntimeError> {
+        if self.interrupted.swap(false, Ordering::Relaxed) {
+            Err(RuntimeError::Interrupted)
+        } else {
+            Ok(())
+        }
     }
 
-    pub fn evaluate(&self, expr: &Expr) -> Result<LiteralValue, &'static str> {
-        expr.accept(self)
+    /// Bind a host value as a global so scripts can read it.
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals
+            .borrow_mut()
+            .define(Symbol::intern(name), value);
     }
-    fn is_truthy(expr: &LiteralValue) -> bool {
-        match &expr {
-            LiteralValue::Nil => false,
-            LiteralValue::Bool(value) => *value,
-            _ => true,
+
+    /// Read back a global, e.g. a result a script left behind.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().lookup(Symbol::intern(name))
+    }
+
+    /// Forget everything scripts have defined, returning the globals to the
+    /// state of a freshly constructed interpreter. Input, output and the
+    /// interrupt handle are kept; what the resolver found is not, so code
+    /// must be resolved again after a reset.
+    pub fn reset(&mut self) {
+        let globals = Environment::new_global_within(Rc::clone(&self.natives));
+        self.globals = Rc::new(RefCell::new(globals));
+        self.environment = Rc::clone(&self.globals);
+        self.locals.clear();
+    }
+
+    /// Run `source` in a throwaway scope inside the globals: definitions it
+    /// makes are discarded once it finishes, whether or not it succeeds.
+    /// Assigning to a variable that was already global shadows it in that
+    /// scope, so the global keeps its value. Functions declared before the
+    /// run still assign to the globals they were declared among.
+    ///
+    /// Returns the value of the final statement when it is an expression
+    /// statement, and nil otherwise.
+    pub fn run_isolated(&mut self, source: &str) -> Result<Value, LuxError> {
+        let first = ExprId::fresh();
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new(source, &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let Ok(statements) = Parser::new(tokens, &mut reporter).parse() else {
+            return Err(LuxError::Compile(reporter.messages));
+        };
+        let ids = first..ExprId::fresh();
+        Resolver::new(self, &mut reporter).resolve(&statements);
+        let result = if reporter.messages.is_empty() {
+            let scope = Environment::new_copy_on_write_within(Rc::clone(&self.globals));
+            let scope = Rc::new(RefCell::new(scope));
+            let globals = std::mem::replace(&mut self.globals, Rc::clone(&scope));
+            let environment = std::mem::replace(&mut self.environment, scope);
+            let result = self.execute_for_value(&statements);
+            self.globals = globals;
+            self.environment = environment;
+            result.map_err(LuxError::from)
+        } else {
+            Err(LuxError::Compile(reporter.messages))
+        };
+        // Drop what the resolver found for the snippet so repeated runs don't
+        // grow the table. A function the snippet hands back still works: its
+        // references, no longer in the table, look their names up instead.
+        self.locals.retain(|id, _| !ids.contains(id));
+        result
+    }
+
+    fn execute_for_value(&mut self, statements: &[Stmt]) -> Result<Value, RuntimeError> {
+        let Some((last, init)) = statements.split_last() else {
+            return Ok(Value::Nil);
+        };
+        for stmt in init {
+            self.execute(stmt).map_err(Unwind::into_error)?;
+        }
+        match last {
+            Stmt::Expr(stmt) => {
+                self.check_interrupt()?;
+                self.evaluate(&stmt.expr)
+            }
+            _ => {
+                self.execute(last).map_err(Unwind::into_error)?;
+                Ok(Value::Nil)
+            }
         }
     }
-}
 
-impl StmtVisitor<Result<(), &'static str>> for Interpreter {
-    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) -> Result<(), &'static str> {
-        self.evaluate(&stmt.expr)?;
-        Ok(())
+    /// Record where the declaration of the variable reference `id` lives:
+    /// in the given slot of the scope the given number of scopes out from
+    /// where it is used, or among the globals when `slot` is `None`.
+    pub fn resolve(&mut self, id: ExprId, slot: Option<(usize, usize)>) {
+        self.locals.insert(id, slot);
     }
-    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> Result<(), &'static str> {
-        let value = self.evaluate(&stmt.expr)?;
-        println!("{}", value);
+
+    /// The value of the variable `name` referenced by `id`.
+    fn look_up_variable(&self, id: ExprId, name: &Token) -> Result<Value, RuntimeError> {
+        let value = match self.locals.get(&id) {
+            Some(Some((distance, slot))) => {
+                return Ok(Environment::get_at(&self.environment, *distance, *slot))
+            }
+            Some(None) => self.globals.borrow().get(name)?,
+            None => self.environment.borrow().get(name)?,
+        };
+        Ok(value)
+    }
+
+    /// Store `value` in the variable `name` referenced by `id`.
+    fn assign_variable(
+        &mut self,
+        id: ExprId,
+        name: &Token,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        match self.locals.get(&id) {
+            Some(Some((distance, slot))) => {
+                Environment::assign_at(&self.environment, *distance, *slot, value)
+            }
+            Some(None) => self.globals.borrow_mut().assign(name, value)?,
+            None => self.environment.borrow_mut().assign(name, value)?,
+        }
         Ok(())
     }
-    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> Result<(), &'static str> {
-        let value = match &stmt.initializer {
-            Some(expr) => self.evaluate(expr)?,
-            None => LiteralValue::Nil,
+
+    /// Run `f` with `environment` as the current scope, going back to the
+    /// previous scope afterwards whether or not `f` succeeds.
+    fn with_scope<T>(&mut self, environment: Environment, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = f(self);
+        self.environment = previous;
+        result
+    }
+
+    /// Run the body of the for-in loop `stmt` once for `item`, returning
+    /// whether the loop goes on.
+    fn for_in_pass(&mut self, stmt: &ForInStmt, item: Value) -> Result<bool, Unwind> {
+        // Each pass gets its own variable, which closures can capture.
+        let mut scope = Environment::with_enclosing(Rc::clone(&self.environment));
+        scope.define(stmt.variable.symbol(), item);
+        match self.with_scope(scope, |interpreter| interpreter.execute(&stmt.body)) {
+            Err(Unwind::Break) => return Ok(false),
+            Err(Unwind::Continue) | Ok(()) => {}
+            Err(error) => return Err(error),
+        }
+        self.check_interrupt()?;
+        Ok(true)
+    }
+
+    /// Run `statements` in a new scope nested inside the current one.
+    pub fn execute_block(&mut self, statements: &[Stmt]) -> Result<(), Unwind> {
+        let scope = Environment::with_enclosing(Rc::clone(&self.environment));
+        self.with_scope(scope, |interpreter| {
+            statements
+                .iter()
+                .try_for_each(|stmt| interpreter.execute(stmt))
+        })
+    }
+
+    /// Evaluate `expr` in a transient scope holding `bindings`, which is
+    /// discarded afterwards.
+    pub fn evaluate_with(
+        &mut self,
+        expr: &Expr,
+        bindings: &[(&str, Value)],
+    ) -> Result<Value, RuntimeError> {
+        let mut scope = Environment::new_global_within(Rc::clone(&self.environment));
+        for (name, value) in bindings {
+            scope.define(Symbol::intern(name), value.clone());
+        }
+        self.with_scope(scope, |interpreter| interpreter.evaluate(expr))
+    }
+
+    /// Run the body of `function` with its parameters bound to `arguments`,
+    /// in a scope nested inside the one the function was declared in.
+    pub(crate) fn call_function(
+        &mut self,
+        function: &Function,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(RuntimeError::Detailed(format!(
+                "maximum call depth ({}) exceeded",
+                self.max_call_depth
+            )));
+        }
+        self.call_depth += 1;
+        let try_depth = std::mem::take(&mut self.try_depth);
+        let mut result = self.run_frame(function, arguments);
+        // Tail calls run one after another here, at the depth of the call
+        // that started them, so tail recursion doesn't grow the stack.
+        while let Err(Unwind::TailCall(callee, arguments)) = result {
+            let Value::Callable(callable) = &callee else {
+                unreachable!("only functions are tail called");
+            };
+            let function = callable
+                .as_function()
+                .expect("only functions are tail called");
+            result = self.run_frame(function, arguments);
+        }
+        self.try_depth = try_depth;
+        self.call_depth -= 1;
+        result.map_err(Unwind::into_error)
+    }
+
+    /// Run the body of `function` once, giving what it returns, or the call
+    /// it ends with when that is a tail call.
+    fn run_frame(&mut self, function: &Function, arguments: Vec<Value>) -> Result<Value, Unwind> {
+        let mut scope = Environment::with_enclosing(Rc::clone(&function.closure));
+        let mut arguments = arguments.into_iter();
+        for (param, argument) in function.declaration.params.iter().zip(&mut arguments) {
+            scope.define(param.symbol(), argument);
+        }
+        if let Some(rest) = &function.declaration.rest {
+            scope.define(rest.symbol(), Value::list(arguments.collect()));
+        }
+        let globals = std::mem::replace(&mut self.globals, Rc::clone(&function.globals));
+        let result = self.with_scope(scope, |interpreter| {
+            function
+                .declaration
+                .body
+                .iter()
+                .try_for_each(|stmt| interpreter.execute(stmt))
+        });
+        self.globals = globals;
+        let value = match result {
+            Ok(()) => Value::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(unwind) => return Err(unwind),
         };
-        self.environment.define(stmt.name.lexeme.clone(), value);
-        Ok(())
+        if function.is_initializer {
+            let this = function.closure.borrow().lookup(Symbol::THIS);
+            return Ok(this.expect("initializer is bound to an instance"));
+        }
+        Ok(value)
     }
-}
 
-impl ExprVisitor<Result<LiteralValue, &'static str>> for Interpreter {
-    fn visit_unary_expr(&self, expr: &UnaryExpr) -> Result<LiteralValue, &'static str> {
-        let right = self.evaluate(&expr.right)?;
-        match &expr.operator.token_type {
-            TokenType::Minus => {
-                if let LiteralValue::Number(n) = right {
-                    Ok(LiteralValue::Number(-n))
-                } else {
-                    Err("negation can only act on a number")
+    /// Run the blocks of a `try` statement.
+    fn execute_try(&mut self, stmt: &TryStmt) -> Result<(), Unwind> {
+        let mut result = self.execute_block(&stmt.body);
+        if let Some((name, body)) = &stmt.catch {
+            // Only errors are caught: break, continue and return pass through,
+            // and so does an interrupt, which must stop the script.
+            result = match result {
+                Err(Unwind::Error(error)) if error != RuntimeError::Interrupted => {
+                    let mut scope = Environment::with_enclosing(Rc::clone(&self.environment));
+                    scope.define(name.symbol(), self.caught_value(error));
+                    self.with_scope(scope, |interpreter| {
+                        body.iter().try_for_each(|stmt| interpreter.execute(stmt))
+                    })
                 }
-            }
-            TokenType::Bang => Ok(LiteralValue::Bool(!Self::is_truthy(&right))),
-            _ => Err("unary operation can only have operator '-' or '!'"),
+                result => result,
+            };
+        }
+        // A finally block always runs. If it unwinds itself, that replaces
+        // the outcome of the try and catch blocks.
+        if let Some(finally) = &stmt.finally {
+            self.execute_block(finally)?;
         }
+        result
     }
-    fn visit_binary_expr(&self, expr: &BinaryExpr) -> Result<LiteralValue, &'static str> {
-        let left = self.evaluate(&expr.left)?;
-        let right = self.evaluate(&expr.right)?;
-        match &expr.operator.token_type {
-            TokenType::Minus => {
-                if let (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) = (left, right)
-                {
-                    Ok(LiteralValue::Number(v_left - v_right))
-                } else {
-                    Err("substraction can only act on a pair of numbers")
-                }
+
+    /// Load the module at `path`, relative to the file being run, running
+    /// its top level the first time it is imported.
+    fn import(&mut self, path: &str) -> Result<Rc<Module>, RuntimeError> {
+        let base = match &self.current_file {
+            Some(file) => file.parent().unwrap_or(Path::new("")).join(path),
+            None => PathBuf::from(path),
+        };
+        let canonical = base.canonicalize().map_err(|error| {
+            RuntimeError::Detailed(format!("can't import '{}': {}", path, error))
+        })?;
+        match self.modules.get(&canonical) {
+            Some(Some(module)) => return Ok(Rc::clone(module)),
+            Some(None) => {
+                return Err(RuntimeError::Detailed(format!(
+                    "circular import of '{}'",
+                    path
+                )))
             }
-            TokenType::Slash => {
-                if let (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) = (left, right)
-                {
-                    Ok(LiteralValue::Number(v_left / v_right))
-                } else {
-                    Err("negation can only act on a pair of numbers")
-                }
+            None => {}
+        }
+        let source = std::fs::read_to_string(&canonical).map_err(|error| {
+            RuntimeError::Detailed(format!("can't import '{}': {}", path, error))
+        })?;
+        let mut reporter = BufferedReporter {
+            file: Some(path.to_string()),
+            ..Default::default()
+        };
+        let mut scanner = Scanner::new(&source, &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let Ok(statements) = Parser::new(tokens, &mut reporter).parse() else {
+            return Err(RuntimeError::Detailed(reporter.messages.join("\n")));
+        };
+        Resolver::new(self, &mut reporter).resolve(&statements);
+        if !reporter.messages.is_empty() {
+            return Err(RuntimeError::Detailed(reporter.messages.join("\n")));
+        }
+
+        self.modules.insert(canonical.clone(), None);
+        let globals = Rc::new(RefCell::new(Environment::new_global_within(Rc::clone(
+            &self.natives,
+        ))));
+        let previous_globals = std::mem::replace(&mut self.globals, Rc::clone(&globals));
+        let previous_environment = std::mem::replace(&mut self.environment, Rc::clone(&globals));
+        let previous_file = self.current_file.replace(canonical.clone());
+        let result = self.interpret(&statements);
+        self.globals = previous_globals;
+        self.environment = previous_environment;
+        self.current_file = previous_file;
+        if let Err(error) = result {
+            // A failed import can be retried rather than looking circular.
+            self.modules.remove(&canonical);
+            return Err(error.with_context(&format!("in {}", path)));
+        }
+        let module = Rc::new(Module::new(path.to_string(), globals));
+        self.modules.insert(canonical, Some(Rc::clone(&module)));
+        Ok(module)
+    }
+
+    /// Give a new instance the field defaults of `class`, those of its
+    /// superclasses first so that a subclass can override them.
+    fn initialize_fields(
+        &mut self,
+        class: &Class,
+        instance: &Rc<Instance>,
+    ) -> Result<(), RuntimeError> {
+        if let Some(superclass) = &class.superclass {
+            self.initialize_fields(superclass, instance)?;
+        }
+        if let Some(initializer) = &class.field_initializer {
+            self.call_function(&initializer.bind(Rc::clone(instance)), Vec::new())?;
+        }
+        Ok(())
+    }
+
+    /// The callee and arguments of the call `expr`, evaluated in order.
+    fn evaluate_call(&mut self, expr: &CallExpr) -> Result<(Value, Vec<Value>), RuntimeError> {
+        let callee = self.evaluate(&expr.callee)?;
+        let arguments = expr
+            .arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((callee, arguments))
+    }
+
+    /// Call `callee`, which should be a function or a class, from the call
+    /// at `paren`. An error raised by the callee gets the call added to its
+    /// trace.
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::Callable(callable) => {
+                Self::check_arity(arguments.len(), callable.arity(), callable.is_variadic())?;
+                // Natives have no lines of their own to point into.
+                let line = callable.as_function().map(|_| paren.line);
+                callable.call(self, arguments).map_err(|error| {
+                    error.in_frame(Frame {
+                        name: callable.name().to_string(),
+                        line,
+                    })
+                })
             }
-            TokenType::Star => {
-                if let (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) = (left, right)
-                {
-                    Ok(LiteralValue::Number(v_left * v_right))
-                } else {
-                    Err("negation can only act on a pair of numbers")
+            Value::Class(class) => {
+                Self::check_arity(arguments.len(), class.arity(), class.is_variadic())?;
+                let instance = Rc::new(Instance::new(Rc::clone(&class)));
+                let traced = |error: RuntimeError| {
+                    error.in_frame(Frame {
+                        name: class.name.clone(),
+                        line: Some(paren.line),
+                    })
+                };
+                self.initialize_fields(&class, &instance).map_err(traced)?;
+                if let Some(init) = class.find_method("init") {
+                    self.call_function(&init.bind(Rc::clone(&instance)), arguments)
+                        .map_err(traced)?;
                 }
+                Ok(Value::Instance(instance))
             }
-            TokenType::Less => {
-                if let (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) = (left, right)
+            _ => Err("can only call functions and classes".into()),
+        }
+    }
+
+    /// Return the result of the call `expr`, handing a call to a script
+    /// function back to `call_function` to make in place of the current one.
+    fn tail_call(&mut self, expr: &CallExpr) -> Result<(), Unwind> {
+        let (callee, arguments) = self.evaluate_call(expr)?;
+        let function = match &callee {
+            Value::Callable(callable) => callable.as_function(),
+            _ => None,
+        };
+        if let Some(function) = function {
+            Self::check_arity(arguments.len(), function.arity(), function.is_variadic())
+                .map_err(|error| error.at(&expr.paren))?;
+            return Err(Unwind::TailCall(callee, arguments));
+        }
+        let value = self
+            .call_value(callee, arguments, &expr.paren)
+            .map_err(|error| error.at(&expr.paren))?;
+        Err(Unwind::Return(value))
+    }
+
+    fn check_arity(count: usize, arity: usize, variadic: bool) -> Result<(), RuntimeError> {
+        if count == arity || (variadic && count > arity) {
+            Ok(())
+        } else {
+            Err("wrong number of arguments".into())
+        }
+    }
+
+    /// What looking up a bound method evaluates to: the result of calling
+    /// it for a getter, and the method itself otherwise.
+    fn method_value(&mut self, method: Function) -> Result<Value, RuntimeError> {
+        if method.declaration.is_getter {
+            self.call_function(&method, Vec::new())
+        } else {
+            Ok(Value::Callable(Rc::new(method)))
+        }
+    }
+
+    /// The text `print` shows for `value`. An instance whose class has a
+    /// `toString` method shows what that method returns; values nested in
+    /// lists and maps keep their built-in form.
+    pub fn stringify(&mut self, value: &Value) -> Result<String, RuntimeError> {
+        let Value::Instance(instance) = value else {
+            return Ok(value.to_string());
+        };
+        let Some(method) = instance.method("toString") else {
+            return Ok(value.to_string());
+        };
+        let class = &instance.class.name;
+        Self::check_arity(0, method.arity(), method.is_variadic()).map_err(|_| {
+            RuntimeError::Detailed(format!("{}.toString() must take no arguments", class))
+        })?;
+        match self.call_function(&method, Vec::new()) {
+            Ok(Value::String(string)) => Ok(string.to_string()),
+            Ok(other) => Err(RuntimeError::Detailed(format!(
+                "{}.toString() must return a string, got {}",
+                class,
+                other.type_name()
+            ))),
+            Err(error) => Err(error.with_context(&format!("in {}.toString()", class))),
+        }
+    }
+
+    /// Apply a binary operator to operands that have already been evaluated.
+    fn binary_operation(
+        &mut self,
+        operator: &Token,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        match &operator.token_type {
+            TokenType::Minus => Self::arithmetic(
+                &left,
+                &right,
+                i64::checked_sub,
+                |l, r| l - r,
+                "subtraction can only act on a pair of numbers",
+            ),
+            // Dividing two ints truncates towards zero, like the other int
+            // operators it stays an int.
+            TokenType::Slash => match (&left, &right) {
+                (Value::Int(_), Value::Int(0)) => Err(RuntimeError::Message("division by zero")),
+                (Value::Int(_) | Value::Number(_), zero)
+                    if !self.ieee_division && zero.as_f64() == Some(0.0) =>
                 {
-                    Ok(LiteralValue::Bool(v_left < v_right))
-                } else {
-                    Err("< can only act on a pair of numbers")
+                    Err(RuntimeError::Message("division by zero"))
                 }
-            }
-            TokenType::Greater => {
-                if let (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) = (left, right)
-                {
-                    Ok(LiteralValue::Bool(v_left > v_right))
-                } else {
-                    Err("> can only act on a pair of numbers")
+                _ => Self::arithmetic(
+                    &left,
+                    &right,
+                    i64::checked_div,
+                    |l, r| l / r,
+                    "division can only act on a pair of numbers",
+                ),
+            },
+            TokenType::Star => match (left, right) {
+                (Value::String(string), count) | (count, Value::String(string)) => {
+                    match count.as_f64() {
+                        Some(count) => Self::repeat_string(&string, count),
+                        None => Err(RuntimeError::Message(STAR_ERROR)),
+                    }
                 }
-            }
-            TokenType::LessEqual => {
-                if let (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) = (left, right)
-                {
-                    Ok(LiteralValue::Bool(v_left <= v_right))
-                } else {
-                    Err("<= can only act on a pair of numbers")
+                (left, right) => {
+                    Self::arithmetic(&left, &right, i64::checked_mul, |l, r| l * r, STAR_ERROR)
                 }
-            }
-            TokenType::GreaterEqual => {
-                if let (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) = (left, right)
-                {
-                    Ok(LiteralValue::Bool(v_left >= v_right))
-                } else {
-                    Err(">= can only act on a pair of numbers")
+            },
+            // An int raised to a negative power is a float.
+            TokenType::StarStar => match (&left, &right) {
+                (Value::Int(base), Value::Int(exponent)) if *exponent >= 0 => {
+                    u32::try_from(*exponent)
+                        .ok()
+                        .and_then(|exponent| base.checked_pow(exponent))
+                        .map(Value::Int)
+                        .ok_or(RuntimeError::Message(OVERFLOW_ERROR))
                 }
-            }
-            TokenType::BangEqual => Ok(LiteralValue::Bool(!(left == right))),
-            TokenType::EqualEqual => Ok(LiteralValue::Bool(left == right)),
+                _ => match (left.as_f64(), right.as_f64()) {
+                    (Some(base), Some(exponent)) => Ok(Value::Number(base.powf(exponent))),
+                    _ => Err(RuntimeError::Message("** can only act on a pair of numbers")),
+                },
+            },
+            TokenType::Less
+            | TokenType::Greater
+            | TokenType::LessEqual
+            | TokenType::GreaterEqual => Self::compare(operator, &left, &right),
+            TokenType::BangEqual => Ok(Value::Bool(!deep_eq(&left, &right))),
+            TokenType::EqualEqual => Ok(Value::Bool(deep_eq(&left, &right))),
             TokenType::Plus => match (left, right) {
-                (LiteralValue::Number(v_left), LiteralValue::Number(v_right)) => {
-                    Ok(LiteralValue::Number(v_left + v_right))
+                (Value::String(v_left), Value::String(v_right)) => {
+                    Ok(Value::String(format!("{}{}", v_left, v_right).into()))
+                }
+                // The other operand is formatted the way `print` shows it.
+                (Value::String(v_left), v_right) if !self.strict => {
+                    Ok(Value::String(
+                        format!("{}{}", v_left, self.stringify(&v_right)?).into(),
+                    ))
                 }
-                (LiteralValue::String(v_left), LiteralValue::String(v_right)) => {
-                    Ok(LiteralValue::String(format!("{}{}", v_left, v_right)))
+                (v_left, Value::String(v_right)) if !self.strict => {
+                    Ok(Value::String(
+                        format!("{}{}", self.stringify(&v_left)?, v_right).into(),
+                    ))
                 }
-                _ => Err("addition can only act on a pair of numbers or strings"),
+                (left, right) => Self::arithmetic(
+                    &left,
+                    &right,
+                    i64::checked_add,
+                    |l, r| l + r,
+                    "addition can only act on a pair of numbers or strings",
+                ),
             },
-            _ => Err("binary operation can only have operator  '-', '+', '*', '/', '<', '>', '<=', '>=','==', '!='"),
+            _ => Err(RuntimeError::Message("binary operation can only have operator  '-', '+', '*', '/', '<', '>', '<=', '>=','==', '!='")),
         }
     }
-    fn visit_literal_expr(&self, expr: &LiteralExpr) -> Result<LiteralValue, &'static str> {
-        Ok(expr.value.clone())
+    /// Two ints give an int, failing on overflow. If either operand is a
+    /// float both are treated as floats.
+    fn arithmetic(
+        left: &Value,
+        right: &Value,
+        int_operation: fn(i64, i64) -> Option<i64>,
+        float_operation: fn(f64, f64) -> f64,
+        message: &'static str,
+    ) -> Result<Value, RuntimeError> {
+        if let (Value::Int(v_left), Value::Int(v_right)) = (left, right) {
+            return int_operation(*v_left, *v_right)
+                .map(Value::Int)
+                .ok_or(RuntimeError::Message(OVERFLOW_ERROR));
+        }
+        match (left.as_f64(), right.as_f64()) {
+            (Some(v_left), Some(v_right)) => Ok(Value::Number(float_operation(v_left, v_right))),
+            _ => Err(RuntimeError::Message(message)),
+        }
     }
-    fn visit_grouping_expr(&self, expr: &GroupingExpr) -> Result<LiteralValue, &'static str> {
-        self.evaluate(&expr.expr)
+
+    /// Order numbers by value and strings by code point, so `"Z" < "a"` and
+    /// `"ab" < "abc"`. Comparisons involving NaN are false, as in IEEE 754.
+    fn compare(operator: &Token, left: &Value, right: &Value) -> Result<Value, RuntimeError> {
+        use std::cmp::Ordering;
+        let ordering = match (left, right) {
+            (Value::Int(v_left), Value::Int(v_right)) => Some(v_left.cmp(v_right)),
+            (Value::Int(_) | Value::Number(_), Value::Int(_) | Value::Number(_)) => {
+                left.as_f64().partial_cmp(&right.as_f64())
+            }
+            // Byte order of UTF-8 is code point order.
+            (Value::String(v_left), Value::String(v_right)) => Some(v_left.cmp(v_right)),
+            _ => {
+                return Err(RuntimeError::Detailed(format!(
+                    "{} can only compare two numbers or two strings, got {} and {}",
+                    operator.lexeme,
+                    left.type_name(),
+                    right.type_name()
+                )))
+            }
+        };
+        let result = match operator.token_type {
+            TokenType::Less => ordering == Some(Ordering::Less),
+            TokenType::Greater => ordering == Some(Ordering::Greater),
+            TokenType::LessEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        };
+        Ok(Value::Bool(result))
     }
-    fn visit_var_expr(&self, expr: &VarExpr) -> Result<LiteralValue, &'static str> {
-        let value = self.environment.get(&expr.name)?.clone();
-        Ok(value)
+
+    /// `string * count`, refusing counts that are not whole numbers as well
+    /// as results too large to allocate sensibly.
+    fn repeat_string(string: &str, count: f64) -> Result<Value, RuntimeError> {
+        const MAX_LEN: usize = 100 * 1024 * 1024;
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(RuntimeError::Detailed(format!(
+                "string repeat count must be a non-negative integer, got {}",
+                count
+            )));
+        }
+        if string.len() as f64 * count > MAX_LEN as f64 {
+            return Err(RuntimeError::Detailed(format!(
+                "string repeated {} times would be too long",
+                count
+            )));
+        }
+        Ok(Value::String(string.repeat(count as usize).into()))
+    }
+
+    fn index_value(object: &Value, index: Value) -> Result<Value, RuntimeError> {
+        match object {
+            Value::List(values) => {
+                let position = Self::list_position(index)?;
+                values
+                    .borrow()
+                    .get(position)
+                    .cloned()
+                    .ok_or("list index out of range".into())
+            }
+            Value::Tuple(values) => {
+                let position = Self::list_position(index)?;
+                values
+                    .get(position)
+                    .cloned()
+                    .ok_or("tuple index out of range".into())
+            }
+            // Looking up a missing key yields nil rather than an error.
+            Value::Map(entries) => {
+                let key = HashableValue::new(index)?;
+                Ok(entries.borrow().get(&key).cloned().unwrap_or(Value::Nil))
+            }
+            _ => Err("only lists, tuples and maps can be indexed".into()),
+        }
+    }
+
+    fn list_position(index: Value) -> Result<usize, RuntimeError> {
+        let position = match index {
+            Value::Int(position) => position as f64,
+            Value::Number(position) => position,
+            _ => return Err("list index must be a number".into()),
+        };
+        if position.fract() != 0.0 || position < 0.0 {
+            return Err("list index must be a non-negative integer".into());
+        }
+        Ok(position as usize)
+    }
+
+    /// Read a property of `instance`: a field, or else a method of its class.
+    fn get_property(&mut self, instance: &Rc<Instance>, name: &str) -> Result<Value, RuntimeError> {
+        // Fields shadow methods of the same name.
+        if let Some(value) = instance.field(name) {
+            return Ok(value);
+        }
+        let method = instance.method(name).ok_or("undefined property")?;
+        self.method_value(method)
+    }
+
+    /// Call `object.name()` as though the call were written at `token`.
+    fn call_method(
+        &mut self,
+        object: &Value,
+        name: &str,
+        token: &Token,
+    ) -> Result<Value, RuntimeError> {
+        let Value::Instance(instance) = object else {
+            return Err(RuntimeError::Detailed(format!(
+                "can't call {}() on {}",
+                name,
+                object.type_name()
+            ))
+            .at(token));
+        };
+        let method = self
+            .get_property(instance, name)
+            .map_err(|error| error.at(token))?;
+        self.call_value(method, Vec::new(), token)
+            .map_err(|error| error.at(token))
+    }
+
+    /// What a `catch` binds for `error`: the thrown value itself, or an
+    /// `Error` instance describing an error raised by the interpreter.
+    fn caught_value(&mut self, error: RuntimeError) -> Value {
+        match error.into_unlocated() {
+            RuntimeError::Thrown(_) => self.thrown.take().unwrap_or(Value::Nil),
+            error => {
+                let instance = Instance::new(Rc::clone(&self.error_class));
+                instance.set("message", Value::String(error.to_string().into()));
+                Value::Instance(Rc::new(instance))
+            }
+        }
+    }
+
+    pub fn interpret(&mut self, statements: &Vec<Stmt>) -> Result<(), RuntimeError> {
+        for stmt in statements {
+            self.execute(stmt).map_err(Unwind::into_error)?;
+        }
+        Ok(())
+    }
+
+    pub fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        self.check_interrupt()?;
+        stmt.accept(self)
+    }
+
+    /// Evaluate `expr`, locating any error it raises at its token.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        expr.accept(self).map_err(|error| match expr.token() {
+            Some(token) => error.at(token),
+            None => error,
+        })
+    }
+    fn is_truthy(expr: &Value) -> bool {
+        match &expr {
+            Value::Nil => false,
+            Value::Bool(value) => *value,
+            _ => true,
+        }
+    }
+}
+
+impl StmtVisitor<Result<(), Unwind>> for Interpreter {
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) -> Result<(), Unwind> {
+        self.evaluate(&stmt.expr)?;
+        Ok(())
+    }
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> Result<(), Unwind> {
+        let values = stmt
+            .expressions
+            .iter()
+            .map(|expr| {
+                let value = self.evaluate(expr)?;
+                self.stringify(&value)
+            })
+            .collect::<Result<Vec<_>, RuntimeError>>()?;
+        writeln!(self.stdout, "{}", values.join(" "))
+            .map_err(|_| RuntimeError::Message("failed to write to stdout"))?;
+        Ok(())
+    }
+    fn visit_eprint_stmt(&mut self, stmt: &EprintStmt) -> Result<(), Unwind> {
+        let value = self.evaluate(&stmt.expr)?;
+        let value = self.stringify(&value)?;
+        writeln!(self.stderr, "{}", value)
+            .map_err(|_| RuntimeError::Message("failed to write to stderr"))?;
+        Ok(())
+    }
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> Result<(), Unwind> {
+        for (name, initializer) in &stmt.declarators {
+            let value = match initializer {
+                Some(expr) => self.evaluate(expr)?,
+                None => Value::Nil,
+            };
+            self.environment.borrow_mut().define(name.symbol(), value);
+        }
+        Ok(())
+    }
+    fn visit_import_stmt(&mut self, stmt: &ImportStmt) -> Result<(), Unwind> {
+        let module = self
+            .import(&stmt.path)
+            .map_err(|error| error.at(&stmt.keyword))?;
+        let mut environment = self.environment.borrow_mut();
+        match &stmt.alias {
+            Some(alias) => environment.define(alias.symbol(), Value::Module(module)),
+            None => {
+                for (name, value) in module.exports() {
+                    environment.define(name, value);
+                }
+            }
+        }
+        Ok(())
+    }
+    fn visit_destructure_stmt(&mut self, stmt: &DestructureStmt) -> Result<(), Unwind> {
+        let values = match self.evaluate(&stmt.initializer)? {
+            Value::Tuple(values) => values.to_vec(),
+            Value::List(values) => values.borrow().clone(),
+            _ => {
+                let error = RuntimeError::Message("can only destructure tuples and lists");
+                return Err(error.at(&stmt.names[0]).into());
+            }
+        };
+        if values.len() != stmt.names.len() {
+            let error = RuntimeError::Detailed(format!(
+                "can't destructure {} values into {} variables",
+                values.len(),
+                stmt.names.len()
+            ));
+            return Err(error.at(&stmt.names[0]).into());
+        }
+        for (name, value) in stmt.names.iter().zip(values) {
+            self.environment.borrow_mut().define(name.symbol(), value);
+        }
+        Ok(())
+    }
+    fn visit_break_stmt(&mut self, _stmt: &BreakStmt) -> Result<(), Unwind> {
+        Err(Unwind::Break)
+    }
+    fn visit_continue_stmt(&mut self, _stmt: &ContinueStmt) -> Result<(), Unwind> {
+        Err(Unwind::Continue)
+    }
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) -> Result<(), Unwind> {
+        let function = Function::new(
+            stmt.clone(),
+            Rc::clone(&self.environment),
+            Rc::clone(&self.globals),
+            false,
+        );
+        self.environment
+            .borrow_mut()
+            .define(stmt.name.symbol(), Value::Callable(Rc::new(function)));
+        Ok(())
+    }
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> Result<(), Unwind> {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self
+                .visit_var_expr(superclass)
+                .map_err(|error| error.at(&superclass.name))?
+            {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let error = RuntimeError::Message("Superclass must be a class.");
+                    return Err(error.at(&superclass.name).into());
+                }
+            },
+            None => None,
+        };
+        // Methods of a subclass close over a scope binding `super`, which
+        // stays the superclass of the declaring class whatever the receiver.
+        let closure = match &superclass {
+            Some(superclass) => {
+                let mut scope = Environment::with_enclosing(Rc::clone(&self.environment));
+                scope.define(Symbol::SUPER, Value::Class(Rc::clone(superclass)));
+                Rc::new(RefCell::new(scope))
+            }
+            None => Rc::clone(&self.environment),
+        };
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let function = Function::new(
+                    method.clone(),
+                    Rc::clone(&closure),
+                    Rc::clone(&self.globals),
+                    method.name.lexeme == "init",
+                );
+                (method.name.lexeme.clone(), Rc::new(function))
+            })
+            .collect();
+        let mut class = Class::new(stmt.name.lexeme.clone(), superclass, methods);
+        class.field_initializer = stmt.field_initializer.as_ref().map(|initializer| {
+            Rc::new(Function::new(
+                initializer.clone(),
+                Rc::clone(&closure),
+                Rc::clone(&self.globals),
+                false,
+            ))
+        });
+        self.environment
+            .borrow_mut()
+            .define(stmt.name.symbol(), Value::Class(Rc::new(class)));
+        Ok(())
+    }
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> Result<(), Unwind> {
+        let value = match &stmt.value {
+            Some(Expr::Call(call)) if self.try_depth == 0 => return self.tail_call(call),
+            Some(expr) => self.evaluate(expr)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> Result<(), Unwind> {
+        self.execute_block(&stmt.statements)
+    }
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> Result<(), Unwind> {
+        if Self::is_truthy(&self.evaluate(&stmt.condition)?) {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> Result<(), Unwind> {
+        while Self::is_truthy(&self.evaluate(&stmt.condition)?) {
+            match self.execute(&stmt.body) {
+                Err(Unwind::Break) => break,
+                Err(Unwind::Continue) | Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+            if let Some(increment) = &stmt.increment {
+                self.evaluate(increment)?;
+            }
+            self.check_interrupt()?;
+        }
+        Ok(())
+    }
+    fn visit_do_while_stmt(&mut self, stmt: &DoWhileStmt) -> Result<(), Unwind> {
+        loop {
+            match self.execute(&stmt.body) {
+                Err(Unwind::Break) => break,
+                Err(Unwind::Continue) | Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+            if !Self::is_truthy(&self.evaluate(&stmt.condition)?) {
+                break;
+            }
+            self.check_interrupt()?;
+        }
+        Ok(())
+    }
+    fn visit_for_in_stmt(&mut self, stmt: &ForInStmt) -> Result<(), Unwind> {
+        // Iterate over a snapshot, so changing a list or map inside the loop
+        // doesn't affect which items the loop visits.
+        let items = match self.evaluate(&stmt.iterable)? {
+            Value::List(values) => values.borrow().clone(),
+            Value::Tuple(values) => values.to_vec(),
+            Value::Map(entries) => {
+                let mut keys: Vec<HashableValue> = entries.borrow().keys().cloned().collect();
+                keys.sort();
+                keys.into_iter().map(|key| key.value().clone()).collect()
+            }
+            Value::String(string) => string
+                .chars()
+                .map(|c| Value::String(c.to_string().into()))
+                .collect(),
+            // An instance whose class has an `iterate()` method hands back an
+            // iterator, whose `next()` steps to the next item or returns nil
+            // at the end, and whose `value()` is the item it stepped to.
+            // There is no fuel limit on how long an iterator may run; as
+            // with any other loop, an interrupt is what stops one that never
+            // ends.
+            Value::Instance(instance)
+                if instance.field("iterate").is_some() || instance.method("iterate").is_some() =>
+            {
+                let object = Value::Instance(instance);
+                let iterator = self.call_method(&object, "iterate", &stmt.variable)?;
+                while !matches!(
+                    self.call_method(&iterator, "next", &stmt.variable)?,
+                    Value::Nil
+                ) {
+                    let item = self.call_method(&iterator, "value", &stmt.variable)?;
+                    if !self.for_in_pass(stmt, item)? {
+                        break;
+                    }
+                }
+                return Ok(());
+            }
+            _ => {
+                let error = RuntimeError::Message(
+                    "can only loop over lists, tuples, maps, strings and iterable instances",
+                );
+                return Err(error.at(&stmt.variable).into());
+            }
+        };
+        for item in items {
+            if !self.for_in_pass(stmt, item)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+    fn visit_throw_stmt(&mut self, stmt: &ThrowStmt) -> Result<(), Unwind> {
+        let value = self.evaluate(&stmt.value)?;
+        let error = RuntimeError::Thrown(value.to_string()).at(&stmt.keyword);
+        self.thrown = Some(value);
+        Err(error.into())
+    }
+    fn visit_switch_stmt(&mut self, stmt: &SwitchStmt) -> Result<(), Unwind> {
+        let subject = self.evaluate(&stmt.subject)?;
+        // Case values are evaluated in order, stopping at the first match.
+        for (value, body) in &stmt.cases {
+            if deep_eq(&subject, &self.evaluate(value)?) {
+                return self.execute_block(body);
+            }
+        }
+        match &stmt.default {
+            Some(default) => self.execute_block(default),
+            None => Ok(()),
+        }
+    }
+    fn visit_try_stmt(&mut self, stmt: &TryStmt) -> Result<(), Unwind> {
+        self.try_depth += 1;
+        let result = self.execute_try(stmt);
+        self.try_depth -= 1;
+        result
+    }
+}
+
+impl ExprVisitor<Result<Value, RuntimeError>> for Interpreter {
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> Result<Value, RuntimeError> {
+        let right = self.evaluate(&expr.right)?;
+        match &expr.operator.token_type {
+            TokenType::Minus => match right {
+                Value::Int(n) => n.checked_neg().map(Value::Int).ok_or(OVERFLOW_ERROR.into()),
+                Value::Number(n) => Ok(Value::Number(-n)),
+                _ => Err(RuntimeError::Message("negation can only act on a number")),
+            },
+            TokenType::Bang | TokenType::Not => Ok(Value::Bool(!Self::is_truthy(&right))),
+            _ => Err(RuntimeError::Message(
+                "unary operation can only have operator '-' or '!'",
+            )),
+        }
+    }
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+        self.binary_operation(&expr.operator, left, right)
+    }
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<Value, RuntimeError> {
+        Ok(expr.value.clone().into())
+    }
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> Result<Value, RuntimeError> {
+        self.evaluate(&expr.expr)
+    }
+    fn visit_var_expr(&mut self, expr: &VarExpr) -> Result<Value, RuntimeError> {
+        self.look_up_variable(expr.id, &expr.name)
+    }
+    fn visit_get_expr(&mut self, expr: &GetExpr) -> Result<Value, RuntimeError> {
+        match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => self.get_property(&instance, &expr.name.lexeme),
+            Value::Module(module) => module.get(expr.name.symbol()).ok_or_else(|| {
+                RuntimeError::Detailed(format!(
+                    "module '{}' has no export '{}'",
+                    module.name, expr.name.lexeme
+                ))
+            }),
+            _ => Err("only instances and modules have properties".into()),
+        }
+    }
+    fn visit_set_expr(&mut self, expr: &SetExpr) -> Result<Value, RuntimeError> {
+        let Value::Instance(instance) = self.evaluate(&expr.object)? else {
+            return Err("only instances have fields".into());
+        };
+        let mut value = self.evaluate(&expr.value)?;
+        if let Some(operator) = &expr.operator {
+            let current = self.get_property(&instance, &expr.name.lexeme)?;
+            value = self.binary_operation(operator, current, value)?;
+        }
+        instance.set(&expr.name.lexeme, value.clone());
+        Ok(value)
+    }
+    fn visit_this_expr(&mut self, expr: &ThisExpr) -> Result<Value, RuntimeError> {
+        self.look_up_variable(expr.id, &expr.keyword)
+    }
+    fn visit_super_expr(&mut self, expr: &SuperExpr) -> Result<Value, RuntimeError> {
+        let Value::Class(superclass) = self.look_up_variable(expr.id, &expr.keyword)? else {
+            unreachable!("'super' is always bound to a class");
+        };
+        let Some(Value::Instance(this)) = self.environment.borrow().lookup(Symbol::THIS) else {
+            unreachable!("'super' is only used inside methods");
+        };
+        let method = superclass
+            .find_method(&expr.method.lexeme)
+            .ok_or("undefined property")?;
+        self.method_value(method.bind(this))
+    }
+    fn visit_error_expr(&mut self, _expr: &ErrorExpr) -> Result<Value, RuntimeError> {
+        Err(RuntimeError::Message("can't run code that failed to parse"))
+    }
+    fn visit_list_expr(&mut self, expr: &ListExpr) -> Result<Value, RuntimeError> {
+        let elements = expr
+            .elements
+            .iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<_, _>>()?;
+        Ok(Value::list(elements))
+    }
+    fn visit_tuple_expr(&mut self, expr: &TupleExpr) -> Result<Value, RuntimeError> {
+        let elements = expr
+            .elements
+            .iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<_, _>>()?;
+        Ok(Value::tuple(elements))
+    }
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> Result<Value, RuntimeError> {
+        let mut entries = HashMap::new();
+        // A repeated key keeps the last value given for it.
+        for (key, value) in &expr.entries {
+            let key = HashableValue::new(self.evaluate(key)?)?;
+            let value = self.evaluate(value)?;
+            entries.insert(key, value);
+        }
+        Ok(Value::map(entries))
+    }
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(&expr.left)?;
+        match &expr.operator.token_type {
+            TokenType::Or if Self::is_truthy(&left) => Ok(left),
+            TokenType::And if !Self::is_truthy(&left) => Ok(left),
+            TokenType::Or | TokenType::And => self.evaluate(&expr.right),
+            // Only nil is missing; false and 0 are kept.
+            TokenType::QuestionQuestion if !matches!(left, Value::Nil) => Ok(left),
+            TokenType::QuestionQuestion => self.evaluate(&expr.right),
+            // xor needs both operands, so it never short-circuits.
+            TokenType::Xor => {
+                let right = self.evaluate(&expr.right)?;
+                Ok(Value::Bool(
+                    Self::is_truthy(&left) != Self::is_truthy(&right),
+                ))
+            }
+            _ => Err(RuntimeError::Message(
+                "logical operation can only have operator 'and', 'or', 'xor', '??'",
+            )),
+        }
+    }
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) -> Result<Value, RuntimeError> {
+        let value = self.evaluate(&expr.value)?;
+        self.assign_variable(expr.id, &expr.name, value.clone())?;
+        Ok(value)
+    }
+    fn visit_increment_expr(&mut self, expr: &IncrementExpr) -> Result<Value, RuntimeError> {
+        let old = self.look_up_variable(expr.id, &expr.name)?;
+        let (step, message) = match expr.operator.token_type {
+            TokenType::PlusPlus => (1, "increment can only act on a number"),
+            _ => (-1, "decrement can only act on a number"),
+        };
+        let new = match old {
+            Value::Int(n) => n.checked_add(step).map(Value::Int).ok_or(OVERFLOW_ERROR)?,
+            Value::Number(n) => Value::Number(n + step as f64),
+            _ => return Err(RuntimeError::Message(message)),
+        };
+        self.assign_variable(expr.id, &expr.name, new.clone())?;
+        Ok(if expr.prefix { new } else { old })
+    }
+    fn visit_call_expr(&mut self, expr: &CallExpr) -> Result<Value, RuntimeError> {
+        let (callee, arguments) = self.evaluate_call(expr)?;
+        self.call_value(callee, arguments, &expr.paren)
+    }
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> Result<Value, RuntimeError> {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        Self::index_value(&object, index)
+    }
+    fn visit_index_set_expr(&mut self, expr: &IndexSetExpr) -> Result<Value, RuntimeError> {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        let mut value = self.evaluate(&expr.value)?;
+        if let Some(operator) = &expr.operator {
+            let current = Self::index_value(&object, index.clone())?;
+            value = self.binary_operation(operator, current, value)?;
+        }
+        match object {
+            // Lists don't grow by assigning past the end.
+            Value::List(values) => {
+                let position = Self::list_position(index)?;
+                let mut values = values.borrow_mut();
+                let slot = values.get_mut(position).ok_or("list index out of range")?;
+                *slot = value.clone();
+            }
+            Value::Map(entries) => {
+                let key = HashableValue::new(index)?;
+                entries.borrow_mut().insert(key, value.clone());
+            }
+            Value::Tuple(_) => return Err("tuples are immutable".into()),
+            _ => return Err("only lists and maps can be indexed".into()),
+        }
+        Ok(value)
+    }
+    fn visit_if_expr(&mut self, expr: &IfExpr) -> Result<Value, RuntimeError> {
+        if Self::is_truthy(&self.evaluate(&expr.condition)?) {
+            self.evaluate(&expr.then_branch)
+        } else {
+            self.evaluate(&expr.else_branch)
+        }
+    }
+    fn visit_conditional_expr(&mut self, expr: &ConditionalExpr) -> Result<Value, RuntimeError> {
+        if Self::is_truthy(&self.evaluate(&expr.condition)?) {
+            self.evaluate(&expr.then_branch)
+        } else {
+            self.evaluate(&expr.else_branch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::reporter::StdoutReporter;
+    use crate::scanner::Scanner;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut reporter = StdoutReporter::default();
+        let mut scanner = Scanner::new(source, &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        Parser::new(tokens, &mut reporter)
+            .parse()
+            .expect("test source should parse")
+    }
+
+    /// `error` without where it was raised, for tests of what went wrong.
+    fn unlocated(error: LuxError) -> LuxError {
+        match error {
+            LuxError::Runtime(error) => LuxError::Runtime(error.into_unlocated()),
+            error => error,
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    /// Run `source` and return what it wrote to stdout and stderr.
+    fn run(source: &str) -> (String, String) {
+        let (stdout, stderr) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut interpreter = Interpreter::with_output(stdout.clone(), stderr.clone());
+        let statements = parse(source);
+        Resolver::new(&mut interpreter, &mut StdoutReporter::default()).resolve(&statements);
+        interpreter.interpret(&statements).unwrap();
+        (stdout.contents(), stderr.contents())
+    }
+
+    #[test]
+    fn test_refuses_error_placeholder() {
+        let token = Token {
+            token_type: TokenType::Comma,
+            lexeme: ",".to_string(),
+            line: 1,
+            column: 1,
+            span: 0..1,
+            symbol: None,
+        };
+        let placeholder = Expr::Error(Box::new(ErrorExpr { token }));
+        assert!(Interpreter::new().evaluate(&placeholder).is_err());
+    }
+
+    #[test]
+    fn test_stack_traces() {
+        let source = "fun c(x) {
+                  return 1 / x;
+                }
+                fun b(x) {
+                  var y = c(x);
+                  return y;
+                }
+                fun a() {
+                  return b(0) + 1;
+                }
+                a();";
+        // The line of `source`, counting from 1, that holds `code`.
+        let line_of = |code: &str| source.lines().position(|line| line.contains(code)).unwrap() + 1;
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.run_isolated(source).unwrap_err();
+        let LuxError::Runtime(runtime_error) = &error else {
+            panic!("expected a runtime error, got {:?}", error);
+        };
+        let frame = |name: &str, line| Frame {
+            name: name.to_string(),
+            line,
+        };
+        assert_eq!(
+            (
+                line_of("1 / x"),
+                line_of("c(x);"),
+                line_of("b(0)"),
+                line_of("a();")
+            ),
+            (2, 5, 9, 11)
+        );
+        assert_eq!(
+            runtime_error.trace(),
+            [
+                frame("c", Some(line_of("c(x);"))),
+                frame("b", Some(line_of("b(0)"))),
+                frame("a", Some(line_of("a();")))
+            ]
+        );
+        assert_eq!(runtime_error.line(), Some(line_of("1 / x")));
+        assert_eq!(
+            error.to_string(),
+            "[line 2] runtime error: division by zero\n  in c (line 5) / in b (line 9) / in a (line 11)"
+        );
+
+        // Natives have no line, and constructing an instance is a call of
+        // its class.
+        let error = interpreter
+            .run_isolated(
+                "class Box { init(item) { this.size = len(item); } }
+                fun pack() { return Box(1); }
+                pack();",
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1] runtime error: len() expects a string, list, tuple or map, got int\n  \
+             in len / in Box (line 2) / in pack (line 3)"
+        );
+
+        // A caught error leaves no trace behind, in its message or on a
+        // later error.
+        let (stdout, _) = run("fun fail() { return 1 / 0; }
+            fun safe() { try { fail(); } catch (e) { print e.message; } }
+            safe();");
+        assert_eq!(stdout, "division by zero\n");
+        let error = interpreter
+            .run_isolated(
+                "fun fail() { return 1 / 0; }
+                fun retry() {
+                  try { fail(); } catch (e) {}
+                  return nil + 1;
+                }
+                retry();",
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 4] runtime error: addition can only act on a pair of numbers or strings\n  \
+             in retry (line 6)"
+        );
+
+        // Errors raised at the top level have no trace.
+        let error = interpreter.run_isolated("1 / 0;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1] runtime error: division by zero"
+        );
+    }
+
+    #[test]
+    fn test_runtime_error_lines() {
+        let cases = [
+            ("-\"a\";", "negation can only act on a number"),
+            (
+                "1 - \"a\";",
+                "subtraction can only act on a pair of numbers",
+            ),
+            (
+                "nil + 1;",
+                "addition can only act on a pair of numbers or strings",
+            ),
+            ("1 / \"a\";", "division can only act on a pair of numbers"),
+            ("nil * 1;", STAR_ERROR),
+            ("1 / 0;", "division by zero"),
+            ("9223372036854775807 + 1;", "integer overflow"),
+            ("\"a\" ** 2;", "** can only act on a pair of numbers"),
+            ("missing;", "undefined variable"),
+            ("missing = 1;", "undefined variable"),
+            (
+                "x += nil;",
+                "addition can only act on a pair of numbers or strings",
+            ),
+            ("var s = \"a\"; s++;", "increment can only act on a number"),
+            ("--A;", "decrement can only act on a number"),
+            ("x();", "can only call functions and classes"),
+            ("clock(1);", "wrong number of arguments"),
+            (
+                "len(1);",
+                "len() expects a string, list, tuple or map, got int",
+            ),
+            ("x.y;", "only instances and modules have properties"),
+            ("x.y = 1;", "only instances have fields"),
+            ("class A {} A().y;", "undefined property"),
+            ("x[0];", "only lists, tuples and maps can be indexed"),
+            ("[][0];", "list index out of range"),
+            ("var t = (1, 2); t[0] = 1;", "tuples are immutable"),
+            (
+                "({[]: 1});",
+                "map keys must be strings, numbers, bools or nil",
+            ),
+            (
+                "for (var i in 1) {}",
+                "can only loop over lists, tuples, maps, strings and iterable instances",
+            ),
+            (
+                "var (a, b) = (1,);",
+                "can't destructure 1 values into 2 variables",
+            ),
+            ("class B < x {}", "Superclass must be a class."),
+            (
+                "class C < A { f() { return super.g; } } C().f();",
+                "undefined property",
+            ),
+            ("throw \"boom\";", "uncaught exception: boom"),
+            ("import \"missing.lux\";", "can't import 'missing.lux'"),
+        ];
+        for (code, message) in cases {
+            let source = format!("var x = 1;\nclass A {{}}\n{}\nprint 1;", code);
+            let mut interpreter = Interpreter::new();
+            let statements = parse(&source);
+            Resolver::new(&mut interpreter, &mut StdoutReporter::default()).resolve(&statements);
+            let error = interpreter.interpret(&statements).unwrap_err();
+            assert_eq!(error.line(), Some(3), "{}", code);
+            assert!(
+                error.to_string().starts_with(message),
+                "{}: {}",
+                code,
+                error
+            );
+        }
+
+        // An error is reported where it was raised, not where the function
+        // raising it was called from.
+        let mut interpreter = Interpreter::new();
+        let error = interpreter
+            .run_isolated("fun f(n) {\n  return n + nil;\n}\nf(1);")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2] runtime error: addition can only act on a pair of numbers or strings\n  \
+             in f (line 4)"
+        );
+        // Catching an error still gives its plain message.
+        let (stdout, _) = run("try {\n  1 / 0;\n} catch (e) {\n  print e.message;\n}");
+        assert_eq!(stdout, "division by zero\n");
+    }
+
+    #[test]
+    fn test_class_fields() {
+        let (stdout, _) = run("class Bag {
+                var items = {};
+                var label = \"bag\", count;
+                var size = len(this.items);
+                init(label) { if (label != nil) this.label = label; }
+            }
+            var a = Bag(nil);
+            var b = Bag(\"named\");
+            a.items[\"x\"] = 1;
+            print a.items, b.items;
+            print a.label, b.label, a.count, a.size;
+            class Point { var x = 0; var y = 0; init(x, y) { this.x = x; this.y = y; } }
+            var p = Point(1, 2);
+            print p.x, p.y;
+            class Base { var kind = \"base\"; var shared = 1; }
+            class Derived < Base { var kind = \"derived\"; }
+            var d = Derived();
+            print d.kind, d.shared;");
+        assert_eq!(stdout, "{\"x\": 1} {}\nbag named nil 0\n1 2\nderived 1\n");
+    }
+
+    #[test]
+    fn test_to_string_hook() {
+        let (stdout, stderr) = run("class Point {
+                init(x, y) { this.x = x; this.y = y; }
+                toString() { return \"(\" + this.x + \", \" + this.y + \")\"; }
+            }
+            class Plain {}
+            var p = Point(1, 2);
+            print p;
+            eprint p;
+            print \"p = \" + p, p + \"!\";
+            print str(p), Plain();
+            print [p];");
+        assert_eq!(
+            stdout,
+            "(1, 2)\np = (1, 2) (1, 2)!\n(1, 2) Plain instance\n[Point instance]\n"
+        );
+        assert_eq!(stderr, "(1, 2)\n");
+
+        let mut interpreter = Interpreter::new();
+        let error = |message: &str| {
+            Err(LuxError::Runtime(RuntimeError::Detailed(
+                message.to_string(),
+            )))
+        };
+        assert_eq!(
+            interpreter.run_isolated("class A { toString() { return 1; } } print A();"),
+            error("A.toString() must return a string, got int")
+        );
+        assert_eq!(
+            interpreter
+                .run_isolated("class B { toString() { return -nil; } } print B();")
+                .map_err(unlocated),
+            error("negation can only act on a number (in B.toString())")
+        );
+        assert_eq!(
+            interpreter.run_isolated("class C { toString(x) { return x; } } print C();"),
+            error("C.toString() must take no arguments")
+        );
+    }
+
+    #[test]
+    fn test_print_several_values() {
+        let (stdout, _) = run("var x = 2;
+            print \"x is\", x, [x];
+            print;
+            print x = 3, x;");
+        assert_eq!(stdout, "x is 2 [2]\n\n3 3\n");
+    }
+
+    #[test]
+    fn test_print_and_eprint_sinks() {
+        let (stdout, stderr) = run("print \"data\"; eprint \"progress\"; print [1, \"a\"];");
+        assert_eq!(stdout, "data\n[1, \"a\"]\n");
+        assert_eq!(stderr, "progress\n");
+    }
+
+    #[test]
+    fn test_read_line() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input("one\r\ntwo\nthree".as_bytes());
+        assert_eq!(interpreter.read_line(), Ok(Some("one".to_string())));
+        assert_eq!(interpreter.read_line(), Ok(Some("two".to_string())));
+        assert_eq!(interpreter.read_line(), Ok(Some("three".to_string())));
+        assert_eq!(interpreter.read_line(), Ok(None));
+    }
+
+    #[test]
+    fn test_read_line_invalid_utf8() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input(&b"\xff\xfe\n"[..]);
+        assert_eq!(
+            interpreter.read_line(),
+            Err(RuntimeError::Message("input is not valid UTF-8"))
+        );
+    }
+
+    #[test]
+    fn test_guessing_game() {
+        let secret = Interpreter::new()
+            .run_isolated("seed(3); randomInt(1, 10);")
+            .unwrap();
+        let stdout = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_output(stdout.clone(), io::sink());
+        interpreter.set_input("1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n".as_bytes());
+        let statements = parse(
+            "seed(3);
+            var secret = randomInt(1, 10);
+            var guesses = 0;
+            var guess = readLine();
+            while (guess != nil) {
+                guesses = guesses + 1;
+                if (guess == \"\" + secret) {
+                    print \"Got it in \" + guesses + \" guesses\";
+                    break;
+                }
+                guess = readLine();
+            }",
+        );
+        Resolver::new(&mut interpreter, &mut StdoutReporter::default()).resolve(&statements);
+        interpreter.interpret(&statements).unwrap();
+        assert_eq!(stdout.contents(), format!("Got it in {} guesses\n", secret));
+
+        interpreter.set_input("last\n".as_bytes());
+        assert_eq!(
+            interpreter.run_isolated("[readLine(), readLine()];"),
+            Ok(Value::list(vec![Value::String("last".into()), Value::Nil]))
+        );
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let (stdout, _) = run("var score = 75;
+            print if (score > 90) \"A\" else if (score > 70) \"B\" else \"C\";
+            print if (nil) -\"not evaluated\" else \"only else\";");
+        assert_eq!(stdout, "B\nonly else\n");
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let (stdout, _) = run("print nil or \"default\";
+            print 0 and \"truthy\";
+            print 1 xor nil;
+            print \"\" xor 0;
+            print false or true xor true;
+            print not nil and not false;
+            print true or -\"short-circuited\";");
+        assert_eq!(stdout, "default\ntruthy\ntrue\nfalse\nfalse\ntrue\ntrue\n");
+    }
+
+    #[test]
+    fn test_xor_evaluates_both_operands() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .interpret(&parse("true xor -\"x\";"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message("negation can only act on a number"))
+        );
+    }
+
+    #[test]
+    fn test_map_number_keys() {
+        let (stdout, stderr) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut interpreter = Interpreter::with_output(stdout.clone(), stderr);
+        let entries = HashMap::from([
+            (
+                HashableValue::new(Value::Number(1.0)).unwrap(),
+                Value::String("number".into()),
+            ),
+            ("1".into(), Value::String("string".into())),
+        ]);
+        interpreter.define_global("m", Value::map(entries));
+        interpreter
+            .interpret(&parse(
+                "print m[1]; print m[1.0]; print m[\"1\"]; print m[2];",
+            ))
+            .unwrap();
+        assert_eq!(stdout.contents(), "number\nnumber\nstring\nnil\n");
+        assert_eq!(
+            interpreter
+                .interpret(&parse("m[[1]];"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message(
+                "map keys must be strings, numbers, bools or nil"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_list_index() {
+        let (stdout, _) = run("var xs = [\"a\", [\"b\"]]; print xs[0]; print xs[1][0];");
+        assert_eq!(stdout, "a\nb\n");
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .interpret(&parse("[1][1];"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message("list index out of range"))
+        );
+        assert_eq!(
+            interpreter
+                .interpret(&parse("[1][0.5];"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message(
+                "list index must be a non-negative integer"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_run_isolated() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_global("base", Value::Number(10.0));
+        assert_eq!(
+            interpreter.run_isolated("var x = 1; [base, x];"),
+            Ok(Value::list(vec![Value::Number(10.0), Value::Int(1)]))
+        );
+        assert_eq!(
+            interpreter.run_isolated("x;").map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Message(
+                "undefined variable"
+            )))
+        );
+        assert!(matches!(
+            interpreter.run_isolated("var x = 2; var base = 0; x;"),
+            Ok(Value::Int(2))
+        ));
+        assert_eq!(
+            interpreter.run_isolated("print x;").map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Message(
+                "undefined variable"
+            )))
+        );
+        assert_eq!(interpreter.get_global("x"), None);
+        assert_eq!(interpreter.get_global("base"), Some(Value::Number(10.0)));
+        assert!(matches!(
+            interpreter.run_isolated("base = 5; { base = base + 1; } base;"),
+            Ok(Value::Int(6))
+        ));
+        assert!(matches!(
+            interpreter.get_global("base"),
+            Some(Value::Number(10.0))
+        ));
+        assert_eq!(
+            interpreter.run_isolated("missing = 1;").map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Message(
+                "undefined variable"
+            )))
+        );
+        assert_eq!(interpreter.run_isolated("var y = 1;"), Ok(Value::Nil));
+        assert!(matches!(
+            interpreter.run_isolated("var = 1;"),
+            Err(LuxError::Compile(_))
+        ));
+
+        // Runs leave nothing behind in the resolver's table, and a function
+        // a run returns can still be called later.
+        let resolved = interpreter.locals.len();
+        let add = interpreter
+            .run_isolated("fun add(n) { var m = n; { return m + base; } } add;")
+            .unwrap();
+        interpreter
+            .run_isolated("{ var a = 1; a = a + base; }")
+            .unwrap();
+        interpreter.run_isolated("{ var a = a; }").unwrap_err();
+        assert_eq!(interpreter.locals.len(), resolved);
+        interpreter.define_global("add", add);
+        assert!(matches!(
+            interpreter.run_isolated("add(1);"),
+            Ok(Value::Number(n)) if n == 11.0
+        ));
+        assert_eq!(interpreter.locals.len(), resolved);
+    }
+
+    #[test]
+    fn test_resolver_error_stops_execution() {
+        let mut interpreter = Interpreter::new();
+        let log = Value::list(vec![Value::Int(0)]);
+        interpreter.define_global("log", log.clone());
+        assert_eq!(
+            interpreter.run_isolated("log[0] = 1;\n{ var a = a; }"),
+            Err(LuxError::Compile(vec![
+                "[line 2, col 11] Error at 'a': Can't read local variable in its own initializer."
+                    .to_string()
+            ]))
+        );
+        assert_eq!(log, Value::list(vec![Value::Int(0)]));
+        assert!(matches!(
+            interpreter.run_isolated("var a = 1; var a = a + 1; a;"),
+            Ok(Value::Int(2))
+        ));
+        assert_eq!(
+            interpreter.run_isolated("log[0] = 1;\nreturn;"),
+            Err(LuxError::Compile(vec![
+                "[line 2, col 1] Error at 'return': Can't return from top-level code.".to_string()
+            ]))
+        );
+        assert_eq!(log, Value::list(vec![Value::Int(0)]));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut interpreter = Interpreter::new();
+        let statements = parse("var user = 1; { var local = user; }");
+        Resolver::new(&mut interpreter, &mut StdoutReporter::default()).resolve(&statements);
+        interpreter.interpret(&statements).unwrap();
+        assert!(!interpreter.locals.is_empty());
+        interpreter.reset();
+        assert_eq!(interpreter.get_global("user"), None);
+        assert!(interpreter.locals.is_empty());
+        assert!(matches!(
+            interpreter.get_global("clock"),
+            Some(Value::Callable(_))
+        ));
+        assert!(matches!(
+            interpreter.run_isolated("clock();"),
+            Ok(Value::Number(_))
+        ));
+    }
+
+    #[test]
+    fn test_assignment() {
+        let (stdout, _) = run("var a = 1; var b; print a = 3; a = b = [a]; print a; print b;");
+        assert_eq!(stdout, "3\n[3]\n[3]\n");
+    }
+
+    #[test]
+    fn test_assignment_to_undefined_variable() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .interpret(&parse("a = 1;"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message("undefined variable"))
+        );
+    }
+
+    #[test]
+    fn test_block_scopes() {
+        let (stdout, _) = run("var a = \"outer\"; var b = 1;
+            {
+                var a = \"inner\";
+                b = 2;
+                { var c = a; print c; }
+                print a;
+            }
+            print a;
+            print b;");
+        assert_eq!(stdout, "inner\ninner\nouter\n2\n");
+    }
+
+    #[test]
+    fn test_block_variables_do_not_leak() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .interpret(&parse("{ var a = 1; } print a;"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message("undefined variable"))
+        );
+        assert_eq!(
+            interpreter
+                .interpret(&parse("{ var b = 1; -\"error\"; }"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message("negation can only act on a number"))
+        );
+        assert_eq!(interpreter.get_global("b"), None);
+    }
+
+    #[test]
+    fn test_if_statement() {
+        let (stdout, _) = run(
+            "if (0) print \"zero is truthy\"; else print \"unreachable\";
+            if (nil) print \"unreachable\"; else print \"nil is falsey\";
+            if (false) { print \"unreachable\"; }
+            if (true) if (false) print \"unreachable\"; else print \"nearest if\";
+            if (false) -\"not evaluated\"; else print \"else only\";
+            if (true); print \"after empty body\";
+            if (false); else print \"else after empty body\";",
+        );
+        assert_eq!(
+            stdout,
+            "zero is truthy\nnil is falsey\nnearest if\nelse only\nafter empty body\nelse after empty body\n"
+        );
+    }
+
+    #[test]
+    fn test_break() {
+        let (stdout, _) = run("var i = 0;
+            while (true) {
+                if (i == 3) { break; }
+                var j = 0;
+                while (true) { j = j + 1; if (j > i) break; }
+                print j;
+                i = i + 1;
+            }
+            print i;");
+        assert_eq!(stdout, "1\n2\n3\n3\n");
+    }
+
+    #[test]
+    fn test_break_skips_for_increment() {
+        let (stdout, _) = run("var last;
+            for (var i = 0; i < 10; i = i + 1) { last = i; if (i == 2) break; }
+            print last;
+            var n = 0;
+            for (; n < 5; n = n + 1) if (n == 4) break;
+            print n;");
+        assert_eq!(stdout, "2\n4\n");
+    }
+
+    #[test]
+    fn test_continue() {
+        let (stdout, _) = run("var even = false;
+            for (var i = 0; i < 6; i = i + 1) {
+                even = !even;
+                if (!even) continue;
+                print i;
+            }
+            var n = 0;
+            while (n < 3) { n = n + 1; if (n == 2) { continue; } print n; }");
+        assert_eq!(stdout, "0\n2\n4\n1\n3\n");
+    }
+
+    #[test]
+    fn test_do_while() {
+        let (stdout, _) = run("do { print \"once\"; } while (false);
+            var i = 0;
+            do {
+                i = i + 1;
+                if (i == 2) continue;
+                if (i == 4) break;
+                print i;
+            } while (i < 10);
+            print i;
+            var n = 5;
+            do n = n - 1; while (n > 0);
+            print n;");
+        assert_eq!(stdout, "once\n1\n3\n4\n0\n");
+    }
+
+    #[test]
+    fn test_return_from_loop() {
+        let (stdout, _) = run("fun find(items, wanted) {
+                for (var i = 0; i < 10; i = i + 1) {
+                    { if (items[i] == wanted) return i; }
+                }
+                return -1;
+            }
+            print find([4, 5, 6], 5);
+            print find([4, 5, 6], 4);");
+        assert_eq!(stdout, "1\n0\n");
+    }
+
+    #[test]
+    fn test_return_value() {
+        let (stdout, _) = run("fun square(n) { return n * n; }
+            fun nothing() { return; }
+            fun noReturn() { 1; }
+            print square(3) + square(4);
+            print nothing();
+            print noReturn();
+            print square;");
+        assert_eq!(stdout, "25\nnil\nnil\n<fn square>\n");
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("fun f(a) {} f();")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("wrong number of arguments".into()))
+        );
+        assert_eq!(
+            interpreter.run_isolated("1();").map_err(unlocated),
+            Err(LuxError::Runtime(
+                "can only call functions and classes".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        // Test threads get a smaller stack than the main thread, which is
+        // too small for a thousand nested calls in a debug build.
+        let test = std::thread::Builder::new()
+            .stack_size(16 * 1024 * 1024)
+            .spawn(|| {
+                let mut interpreter = Interpreter::new();
+                let error = interpreter
+                    .run_isolated("fun f() {\n  f();\n}\nf();")
+                    .unwrap_err();
+                assert_eq!(
+                    error.to_string(),
+                    "[line 2] runtime error: maximum call depth (1000) exceeded\n  \
+                     in f (line 2), 1000 times / in f (line 4)"
+                );
+
+                // A thousand calls deep is allowed, and the depth unwinds after an
+                // error so later calls start from the top again.
+                let countdown = "fun down(n) { if (n == 0) return 0; return down(n - 1) + 1; }";
+                let value = interpreter
+                    .run_isolated(&format!("{} down(999);", countdown))
+                    .unwrap();
+                assert_eq!(value, Value::Int(999));
+                assert!(interpreter
+                    .run_isolated(&format!("{} down(1000);", countdown))
+                    .is_err());
+
+                interpreter.set_max_call_depth(10);
+                assert!(interpreter
+                    .run_isolated(&format!("{} down(9);", countdown))
+                    .is_ok());
+                let error = interpreter
+                    .run_isolated(&format!("{} down(10);", countdown))
+                    .unwrap_err();
+                assert_eq!(
+                    error.to_string(),
+                    "[line 1] runtime error: maximum call depth (10) exceeded\n  \
+                     in down (line 1), 11 times"
+                );
+            })
+            .unwrap();
+        test.join().unwrap();
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        // Tail calls don't count towards the call depth, however many there
+        // are, including between functions and through methods.
+        let (stdout, _) = run("fun count(n, total) {
+                if (n == 0) return total;
+                return count(n - 1, total + 1);
+            }
+            print count(1000000, 0);
+            fun even(n) { if (n == 0) return true; return odd(n - 1); }
+            fun odd(n) { if (n == 0) return false; return even(n - 1); }
+            print even(5001);
+            class Loop { run(n) { if (n == 0) return \"done\"; return this.run(n - 1); } }
+            print Loop().run(5000);");
+        assert_eq!(stdout, "1000000\nfalse\ndone\n");
+
+        // Anything else done with the result means the call is not in tail
+        // position, and neither is a return inside a try.
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_call_depth(100);
+        for source in [
+            "fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); } f(5000);",
+            "fun f(n) { if (n == 0) return 0; try { return f(n - 1); } finally {} } f(5000);",
+        ] {
+            let error = interpreter.run_isolated(source).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "[line 1] runtime error: maximum call depth (100) exceeded\n  \
+                 in f (line 1), 101 times",
+                "{}",
+                source
+            );
+        }
+
+        // A try still sees errors from a call it returns, even when the try
+        // is inside a function called in tail position.
+        let (stdout, _) = run("fun fail() { return 1 / 0; }
+            fun guarded() {
+                try { return fail(); } catch (e) { return \"caught \" + e.message; }
+            }
+            fun outer() { return guarded(); }
+            print outer();
+            fun wrong() { return fail(1); }
+            try { wrong(); } catch (e) { print e.message; }");
+        assert_eq!(
+            stdout,
+            "caught division by zero\nwrong number of arguments\n"
+        );
+    }
+
+    #[test]
+    fn test_closure_counter() {
+        let (stdout, _) = run("fun makeCounter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            var first = makeCounter();
+            var second = makeCounter();
+            print first();
+            print first();
+            print second();
+            print first();");
+        assert_eq!(stdout, "1\n2\n1\n3\n");
+    }
+
+    #[test]
+    fn test_closure_sees_later_assignment() {
+        let (stdout, _) = run("fun make() {
+                var x = \"before\";
+                fun show() { print x; }
+                x = \"after\";
+                return show;
+            }
+            make()();
+            var a = \"declared\";
+            fun read() { return a; }
+            fun call() { var a = \"caller\"; return read(); }
+            print call();");
+        assert_eq!(stdout, "after\ndeclared\n");
+    }
+
+    #[test]
+    fn test_local_slots() {
+        // Each name gets its own slot, even when it shadows one outside.
+        let (stdout, _) = run("var a = \"global\";
+            {
+                var a = \"outer\";
+                var b = \"b\";
+                {
+                    var c = \"c\";
+                    var a = \"inner\";
+                    print a + b + c;
+                    a = \"inner2\";
+                    b = \"b2\";
+                    print a;
+                }
+                print a + b;
+            }
+            print a;");
+        assert_eq!(stdout, "innerbc\ninner2\nouterb2\nglobal\n");
+
+        // Closures keep the slots of the scopes they capture, including
+        // locals declared after the closure.
+        let (stdout, _) = run("fun make() {
+                var x = 1;
+                fun get() { return x; }
+                var y = 10;
+                fun bump() { x = x + y; }
+                return [get, bump];
+            }
+            var fs = make();
+            fs[1]();
+            fs[1]();
+            print fs[0]();
+            var adders = [nil, nil, nil];
+            for (var i in [0, 1, 2]) {
+                var twice = i * 2;
+                fun add(n) { return n + i + twice; }
+                adders[i] = add;
+            }
+            print adders[0](0) + adders[2](100);");
+        assert_eq!(stdout, "21\n106\n");
+    }
+
+    #[test]
+    fn test_variadic_functions() {
+        let (stdout, _) = run("fun sum(...xs) {
+                var total = 0;
+                for (var x in xs) total += x;
+                return total;
+            }
+            print sum();
+            print sum(4);
+            print sum(1, 2, 3, 4);
+            fun tag(name, ...rest) { print [name, rest]; }
+            tag(\"a\");
+            tag(\"b\", 1, 2);
+            class Bag { init(...items) { this.items = items; } }
+            print Bag(1, 2).items;");
+        assert_eq!(stdout, "0\n4\n10\n[\"a\", []]\n[\"b\", [1, 2]]\n[1, 2]\n");
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("fun f(a, ...rest) {} f();")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("wrong number of arguments".into()))
+        );
+    }
+
+    #[test]
+    fn test_type() {
+        let (stdout, _) = run("class Point {}
+            fun f() {}
+            try { nil + 1; } catch (e) { print type(e); }
+            for (var value in [1, 1.5, \"s\", true, nil, [], {}, f, clock, Point, Point()]) {
+                print type(value);
+            }");
+        assert_eq!(
+            stdout,
+            "Error\nint\nfloat\nstring\nbool\nnil\nlist\nmap\nfunction\nfunction\nclass\nPoint\n"
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.run_isolated("type();").map_err(unlocated),
+            Err(LuxError::Runtime("wrong number of arguments".into()))
+        );
+    }
+
+    #[test]
+    fn test_string_natives() {
+        let (stdout, _) = run("print len(\"héllo\");
+            print len([1, 2]) + len((1,)) + len({\"a\": 1});
+            print substring(\"héllo\", 1, 3);
+            print substring(\"abc\", 0, 3.0);
+            print substring(\"abc\", 2, 2) == \"\";
+            print upper(\"straße\");
+            print lower(\"ÀB\");");
+        assert_eq!(stdout, "5\n4\nél\nabc\ntrue\nSTRASSE\nàb\n");
+
+        let mut interpreter = Interpreter::new();
+        let error = |message: &str| {
+            Err(LuxError::Runtime(RuntimeError::Detailed(
+                message.to_string(),
+            )))
+        };
+        assert_eq!(
+            interpreter
+                .run_isolated("substring(\"abc\", 2, 4);")
+                .map_err(unlocated),
+            error("substring range 2..4 is out of bounds for a string of 3 characters")
+        );
+        assert_eq!(
+            interpreter
+                .run_isolated("substring(\"abc\", 2, 1);")
+                .map_err(unlocated),
+            error("substring range 2..1 is out of bounds for a string of 3 characters")
+        );
+        assert_eq!(
+            interpreter
+                .run_isolated("substring(\"abc\", 0.5, 1);")
+                .map_err(unlocated),
+            error("substring() expects an integer as argument 2, got 0.5")
+        );
+        assert_eq!(
+            interpreter.run_isolated("upper(1);").map_err(unlocated),
+            error("upper() expects a string as argument 1, got int")
+        );
+        assert_eq!(
+            interpreter.run_isolated("len(nil);").map_err(unlocated),
+            error("len() expects a string, list, tuple or map, got nil")
+        );
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut interpreter = Interpreter::new();
+        let Ok(Value::Number(seconds)) = interpreter.run_isolated("clock();") else {
+            panic!("clock should return a number");
+        };
+        assert!(seconds > 1.5e9);
+        assert_eq!(
+            interpreter.run_isolated("var start = clock(); clock() - start >= 0;"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            interpreter.run_isolated("clock;").unwrap().to_string(),
+            "<native fn clock>"
+        );
+    }
+
+    #[test]
+    fn test_native_shadowing() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("twice", 1, |_, arguments| match arguments[0].as_f64() {
+            Some(n) => Ok(Value::Number(n * 2.0)),
+            None => Err("twice expects a number".into()),
+        });
+        assert_eq!(
+            interpreter.run_isolated("twice(21);"),
+            Ok(Value::Number(42.0))
+        );
+        assert_eq!(
+            interpreter.run_isolated("twice();").map_err(unlocated),
+            Err(LuxError::Runtime("wrong number of arguments".into()))
+        );
+        interpreter.interpret(&parse("var clock = 1;")).unwrap();
+        assert!(matches!(
+            interpreter.get_global("clock"),
+            Some(Value::Int(1))
+        ));
+        interpreter.reset();
+        let Some(Value::Callable(clock)) = interpreter.get_global("clock") else {
+            panic!("reset should bring back the clock native");
+        };
+        assert_eq!(clock.name(), "clock");
+    }
+
+    #[test]
+    fn test_resolved_closure_ignores_later_shadow() {
+        let (stdout, _) = run("var a = \"global\";
+            {
+                fun show() { print a; }
+                show();
+                var a = \"block\";
+                show();
+                print a;
+            }");
+        assert_eq!(stdout, "global\nglobal\nblock\n");
+    }
+
+    #[test]
+    fn test_class_instantiation() {
+        let (stdout, _) = run("class Bagel { eat() { print \"crunch\"; } }
+            print Bagel;
+            var bagel = Bagel();
+            print bagel;
+            print bagel == bagel;
+            print bagel == Bagel();");
+        assert_eq!(stdout, "Bagel\nBagel instance\ntrue\nfalse\n");
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("class A {} A(1);")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("wrong number of arguments".into()))
+        );
+    }
+
+    #[test]
+    fn test_methods_and_this() {
+        let (stdout, _) = run("class Counter {
+                add(n) { this.total = this.total + n; return this; }
+                show() { print this.total; }
+            }
+            var counter = Counter();
+            counter.total = 1;
+            counter.add(2).add(3);
+            counter.show();
+            print counter.total;");
+        assert_eq!(stdout, "6\n6\n");
+    }
+
+    #[test]
+    fn test_bound_method() {
+        let (stdout, _) = run("class Person {
+                greet() { print \"hi, \" + this.name; }
+            }
+            var jane = Person();
+            jane.name = \"Jane\";
+            var bill = Person();
+            bill.name = \"Bill\";
+            var greet = jane.greet;
+            bill.greet = greet;
+            bill.greet();
+            print greet;");
+        assert_eq!(stdout, "hi, Jane\n<fn greet>\n");
+    }
+
+    #[test]
+    fn test_this_in_closure() {
+        let (stdout, _) = run("class Greeter {
+                init(name) { this.name = name; }
+                greeter() {
+                    fun greet() { print \"hi, \" + this.name; }
+                    return greet;
+                }
+            }
+            var greet = Greeter(\"Ann\").greeter();
+            greet();");
+        assert_eq!(stdout, "hi, Ann\n");
+    }
+
+    #[test]
+    fn test_property_errors() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("class A {} A().missing;")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("undefined property".into()))
+        );
+        assert_eq!(
+            interpreter
+                .run_isolated("var a = 1; a.b;")
+                .map_err(unlocated),
+            Err(LuxError::Runtime(
+                "only instances and modules have properties".into()
+            ))
+        );
+        assert_eq!(
+            interpreter.run_isolated("\"s\".b = 1;").map_err(unlocated),
+            Err(LuxError::Runtime("only instances have fields".into()))
+        );
+    }
+
+    #[test]
+    fn test_init() {
+        let (stdout, _) = run("class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                    if (x == 0) return;
+                    this.moved = true;
+                }
+            }
+            var p = Point(1, 2);
+            print p.x + p.y;
+            print p.moved;
+            var origin = Point(0, 0);
+            print origin;
+            print origin.init(3, 4) == origin;
+            print origin.x;");
+        assert_eq!(stdout, "3\ntrue\nPoint instance\ntrue\n3\n");
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("class P { init(a) {} } P();")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("wrong number of arguments".into()))
+        );
+    }
+
+    #[test]
+    fn test_inheritance() {
+        let (stdout, _) = run("class Animal {
+                init(name) { this.name = name; }
+                speak() { print this.name + \" makes a sound\"; }
+                describe() { print \"I am \" + this.name; }
+            }
+            class Dog < Animal {
+                speak() { print this.name + \" barks\"; }
+            }
+            var dog = Dog(\"Rex\");
+            dog.speak();
+            dog.describe();
+            Animal(\"Cat\").speak();");
+        assert_eq!(stdout, "Rex barks\nI am Rex\nCat makes a sound\n");
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("var NotAClass = 1; class A < NotAClass {}")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("Superclass must be a class.".into()))
+        );
+    }
+
+    #[test]
+    fn test_super() {
+        let (stdout, _) = run("class Doughnut {
+                cook() { print \"Fry until golden brown.\"; }
+            }
+            class BostonCream < Doughnut {
+                cook() {
+                    super.cook();
+                    print \"Pipe full of custard and coat with chocolate.\";
+                }
+            }
+            BostonCream().cook();
+            class A { method() { print \"A method\"; } }
+            class B < A {
+                method() { print \"B method\"; }
+                test() { super.method(); }
+            }
+            class C < B {}
+            C().test();");
+        assert_eq!(
+            stdout,
+            "Fry until golden brown.\n\
+             Pipe full of custard and coat with chocolate.\n\
+             A method\n"
+        );
+    }
+
+    #[test]
+    fn test_getters() {
+        let (stdout, _) = run("class Circle {
+                init(radius) { this.radius = radius; }
+                area { return 3 * this.radius * this.radius; }
+                grow(by) { this.radius = this.radius + by; }
+                broken { return this.missing; }
+            }
+            class Ring < Circle {
+                area { return super.area - 1; }
+            }
+            var c = Circle(2);
+            print c.area;
+            c.grow(1);
+            print c.area;
+            print c.radius;
+            print Ring(1).area;");
+        assert_eq!(stdout, "12\n27\n3\n2\n");
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("class C { broken { return this.missing; } } C().broken;")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("undefined property".into()))
+        );
+    }
+
+    #[test]
+    fn test_conditional() {
+        let (stdout, _) = run("var n = 0;
+            fun bump() { n = n + 1; return n; }
+            print true ? bump() : bump();
+            print nil ? \"yes\" : false ? \"maybe\" : \"no\";
+            print 1 ? \"ok\" : missing;
+            print n;");
+        assert_eq!(stdout, "1\nno\nok\n1\n");
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let (stdout, _) = run("print nil ?? 1;
+            print false ?? 1;
+            print 0 ?? 1;
+            print \"\" ?? 1;
+            print nil ?? nil ?? 3;
+            print nil ?? false ?? 3;
+            print 2 ?? missing;");
+        assert_eq!(stdout, "1\nfalse\n0\n\n3\nfalse\n2\n");
+    }
+
+    #[test]
+    fn test_switch() {
+        let (stdout, _) = run("fun describe(x) {
+                switch (x) {
+                    case 1:
+                        print \"one\";
+                    case \"x\":
+                        var s = \"ex\";
+                        print s;
+                    case [1, 2]:
+                        print \"list\";
+                    default:
+                        print \"other\";
+                }
+            }
+            describe(1);
+            describe(1.0);
+            describe(\"x\");
+            describe([1, 2]);
+            describe(nil);
+            var calls = 0;
+            fun next() { calls += 1; return calls; }
+            switch (next()) { case 2: print \"no\"; }
+            print calls;");
+        assert_eq!(stdout, "one\none\nex\nlist\nother\n1\n");
+    }
+
+    #[test]
+    fn test_try_catch() {
+        let (stdout, _) = run(
+            "try { print 1; throw \"oops\"; print 2; } catch (e) { print e; }
+            fun fail() { throw [1, 2]; }
+            try { fail(); } catch (e) { print e[1]; }
+            try { 1 + nil; } catch (e) { print e.message; }
+            try { print missing; } catch (e) { print e; }
+            try {
+                try { throw 1; } finally { print \"inner finally\"; }
+            } catch (e) {
+                print \"outer caught\";
+            }
+            try { throw 1; } catch (e) { try { throw e + 1; } catch (e) { print e; } }
+            fun early() {
+                try { return \"returned\"; } finally { print \"cleanup\"; }
+            }
+            print early();
+            for (var i in [1, 2, 3]) {
+                try { if (i == 2) break; } finally { print i; }
+            }",
+        );
+        assert_eq!(
+            stdout,
+            "1\noops\n2\naddition can only act on a pair of numbers or strings\n\
+             Error instance\ninner finally\nouter caught\n2\ncleanup\nreturned\n1\n2\n"
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("throw \"boom\";")
+                .map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Thrown("boom".to_string())))
+        );
+        assert_eq!(
+            RuntimeError::Thrown("3".to_string()).to_string(),
+            "uncaught exception: 3"
+        );
+    }
+
+    #[test]
+    fn test_multiple_declarators() {
+        let (stdout, _) = run("var a = 1, b, c = a + 2;
+            print [a, b, c];
+            { var x = \"outer\", y = x; print y; }
+            for (var i = 0, j = 3; i < j; i += 1) print i * j;");
+        assert_eq!(stdout, "[1, nil, 3]\nouter\n0\n3\n6\n");
+    }
+
+    #[test]
+    fn test_tuples() {
+        let (stdout, _) = run("fun divmod(a, b) { return (a / b, a - a / b * b); }
+            var (q, r) = divmod(7, 2);
+            print [q, r];
+            var pair = (1, \"a\");
+            print pair;
+            print (1,);
+            print pair[1];
+            print pair == (1, \"a\");
+            print pair == (1, \"b\");
+            print pair == [1, \"a\"];
+            print type(pair);
+            for (var x in (3, 4)) print x;
+            var (a, b) = [5, 6];
+            print a + b;");
+        assert_eq!(
+            stdout,
+            "[3, 1]\n(1, \"a\")\n(1,)\na\ntrue\nfalse\nfalse\ntuple\n3\n4\n11\n"
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("var t = (1, 2); t[0] = 3;")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("tuples are immutable".into()))
+        );
+        assert_eq!(
+            interpreter
+                .run_isolated("var (a, b) = (1, 2, 3);")
+                .map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Detailed(
+                "can't destructure 3 values into 2 variables".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_for_in() {
+        let (stdout, _) = run("for (var x in [1, 2, 3, 4, 5]) {
+                if (x == 2) continue;
+                if (x == 4) break;
+                print x;
+            }
+            for (var key in {\"b\": 2, \"a\": 1}) print key;
+            for (c in \"hé\") print c;
+            var xs = [1, 2];
+            for (var x in xs) { xs[0] = 10; print x; }
+            var fs = [nil, nil];
+            for (var i in [0, 1]) {
+                fun f() { return i + 1; }
+                fs[i] = f;
+            }
+            print fs[0]() + fs[1]() * 10;");
+        assert_eq!(stdout, "1\n3\na\nb\nh\né\n1\n2\n21\n");
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .run_isolated("for (var x in 3) {}")
+                .map_err(unlocated),
+            Err(LuxError::Runtime(
+                "can only loop over lists, tuples, maps, strings and iterable instances".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_for_in_iterable_instances() {
+        let linked_list = "class Node {
+                init(value, rest) { this.value = value; this.rest = rest; }
+            }
+            class Cursor {
+                init(node) { this.node = nil; this.ahead = node; }
+                next() {
+                    this.node = this.ahead;
+                    if (this.node != nil) this.ahead = this.node.rest;
+                    return this.node;
+                }
+                value() { return this.node.value; }
+            }
+            class LinkedList {
+                init() { this.head = nil; }
+                push(value) { this.head = Node(value, this.head); return this; }
+                iterate() { return Cursor(this.head); }
+            }
+            var list = LinkedList().push(3).push(2).push(1);\n";
+        let (stdout, _) = run(&format!(
+            "{}for (var x in list) {{ if (x == 2) continue; print x; }}
+            for (var x in LinkedList()) print x;
+            var sum = 0;
+            for (var x in list) for (var y in list) sum += x * y;
+            print sum;
+            for (var x in list) {{ if (x == 2) break; print x; }}",
+            linked_list
+        ));
+        assert_eq!(stdout, "1\n3\n36\n1\n");
+
+        // A field holding a function is dispatched to like a method.
+        let (stdout, _) = run("class Once {
+                init() { this.done = false; }
+                next() { if (this.done) return nil; this.done = true; return true; }
+                value() { return \"once\"; }
+            }
+            class C {}
+            var c = C();
+            fun iterate() { return Once(); }
+            c.iterate = iterate;
+            for (var x in c) print x;");
+        assert_eq!(stdout, "once\n");
+
+        // Errors inside the protocol's methods are reported at their own
+        // lines, with the method in the trace.
+        let mut interpreter = Interpreter::new();
+        let error = interpreter
+            .run_isolated(&format!(
+                "{}class Bad {{ iterate() {{ return this; }}\n  next() {{ return nil + 1; }} }}\nfor (var x in Bad()) {{}}",
+                linked_list
+            ))
+            .unwrap_err();
+        let lines = linked_list.lines().count();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "[line {}] runtime error: addition can only act on a pair of numbers or strings\n  \
+                 in next (line {})",
+                lines + 2,
+                lines + 3
+            )
+        );
+        for (source, message) in [
+            (
+                "class A { iterate() { return 1; } } for (var x in A()) {}",
+                RuntimeError::Detailed("can't call next() on int".to_string()),
+            ),
+            (
+                "class A { iterate() { return this; } next() { return 1; } } for (var x in A()) {}",
+                "undefined property".into(),
+            ),
+            (
+                "class A {} for (var x in A()) {}",
+                "can only loop over lists, tuples, maps, strings and iterable instances".into(),
+            ),
+        ] {
+            assert_eq!(
+                interpreter.run_isolated(source).map_err(unlocated),
+                Err(LuxError::Runtime(message)),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_map_literals() {
+        let (stdout, _) = run("var m = {\"name\": \"lux\", \"year\": 2024, 1: [2],};
+            print m;
+            print m[\"name\"];
+            print m[\"missing\"];
+            m[\"year\"] += 1;
+            print m[\"year\"];
+            print {};
+            print {\"a\": 1, \"a\": 2};");
+        assert_eq!(
+            stdout,
+            "{1: [2], \"name\": \"lux\", \"year\": 2024}\nlux\nnil\n2025\n{}\n{\"a\": 2}\n"
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.run_isolated("({[]: 1});").map_err(unlocated),
+            Err(LuxError::Runtime(
+                "map keys must be strings, numbers, bools or nil".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_index_assignment() {
+        let (stdout, _) = run("var xs = [1, 2, 3];
+            var ys = xs;
+            ys[0] = 99;
+            print xs;
+            print xs[1] = 5;
+            xs[2] += 10;
+            print xs;
+            fun trace(label, value) { print label; return value; }
+            trace(\"list\", xs)[trace(\"index\", 0)] = trace(\"value\", 0);");
+        assert_eq!(stdout, "[99, 2, 3]\n5\n[99, 5, 13]\nlist\nindex\nvalue\n");
+
+        let mut interpreter = Interpreter::new();
+        interpreter.define_global("m", Value::map(HashMap::new()));
+        assert!(matches!(
+            interpreter.run_isolated("m[\"a\"] = 6; m[\"a\"] *= 7; m[\"a\"];"),
+            Ok(Value::Int(42))
+        ));
+        assert_eq!(
+            interpreter
+                .run_isolated("var xs = [1]; xs[1] = 2;")
+                .map_err(unlocated),
+            Err(LuxError::Runtime("list index out of range".into()))
+        );
+    }
+
+    #[test]
+    fn test_ints_and_floats() {
+        let (stdout, _) = run("print 7 / 2;
+            print -7 / 2;
+            print 7.0 / 2;
+            print 1 + 2;
+            print 1 + 0.5;
+            print 2 ** 10;
+            print 2 ** -1;
+            print 3 == 3.0;
+            print 2 < 2.5;
+            print [1, 2.5];");
+        assert_eq!(
+            stdout,
+            "3\n-3\n3.5\n3\n1.5\n1024\n0.5\ntrue\ntrue\n[1, 2.5]\n"
+        );
+        // Printing shows 3.0 as 3, and equality can't tell them apart either,
+        // so check the variants themselves.
+        let mut interpreter = Interpreter::new();
+        for (source, int) in [
+            ("7 / 2;", Some(3)),
+            ("-7 / 2;", Some(-3)),
+            ("1 + 2;", Some(3)),
+            ("2 ** 10;", Some(1024)),
+            ("-(3 * 4) - 1;", Some(-13)),
+            ("7.0 / 2;", None),
+            ("1.5 + 1.5;", None),
+            ("2 * 3.0;", None),
+            ("2 ** -1;", None),
+        ] {
+            let value = interpreter.run_isolated(source).unwrap();
+            match int {
+                Some(int) => assert!(matches!(value, Value::Int(n) if n == int), "{}", source),
+                None => assert!(matches!(value, Value::Number(_)), "{}", source),
+            }
+        }
+
+        let mut interpreter = Interpreter::new();
+        for source in ["1 / 0;", "1.0 / 0;", "0 / 0.0;", "-1.5 / -0.0;"] {
+            assert_eq!(
+                interpreter.run_isolated(source).map_err(unlocated),
+                Err(LuxError::Runtime("division by zero".into())),
+                "{}",
+                source
+            );
+        }
+        interpreter.set_ieee_division(true);
+        assert_eq!(
+            interpreter.run_isolated("1.0 / 0;"),
+            Ok(Value::Number(f64::INFINITY))
+        );
+        assert_eq!(
+            interpreter.run_isolated("1 / -0.0;"),
+            Ok(Value::Number(f64::NEG_INFINITY))
+        );
+        assert!(matches!(
+            interpreter.run_isolated("0 / 0.0;"),
+            Ok(Value::Number(n)) if n.is_nan()
+        ));
+        assert_eq!(
+            interpreter.run_isolated("1 / 0;").map_err(unlocated),
+            Err(LuxError::Runtime("division by zero".into()))
+        );
+        for source in [
+            "9223372036854775807 + 1;",
+            "-9223372036854775807 - 2;",
+            "4294967296 * 4294967296;",
+            "2 ** 63;",
+            "(-9223372036854775807 - 1) / -1;",
+            "var big = 9223372036854775807; big++;",
+        ] {
+            assert_eq!(
+                interpreter.run_isolated(source).map_err(unlocated),
+                Err(LuxError::Runtime("integer overflow".into())),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        let (stdout, _) = run("print \"apple\" < \"banana\";
+            print \"ab\" < \"abc\";
+            print \"abc\" <= \"abc\";
+            print \"abc\" > \"abc\";
+            print \"abc\" >= \"abc\";
+            print \"Zebra\" < \"apple\";
+            print \"é\" > \"z\";");
+        assert_eq!(stdout, "true\ntrue\ntrue\nfalse\ntrue\ntrue\ntrue\n");
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.run_isolated("\"1\" < 2;").map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Detailed(
+                "< can only compare two numbers or two strings, got string and int".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_string_concatenation_coerces() {
+        let (stdout, _) = run("print \"score: \" + 42;
+            print 1.5 + \"x\";
+            print \"\" + nil + true;
+            print [1, \"a\"] + \"!\";
+            print 1 + 2 + \"3\";");
+        assert_eq!(stdout, "score: 42\n1.5x\nniltrue\n[1, \"a\"]!\n33\n");
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict(true);
+        assert_eq!(
+            interpreter
+                .run_isolated("\"score: \" + 42;")
+                .map_err(unlocated),
+            Err(LuxError::Runtime(
+                "addition can only act on a pair of numbers or strings".into()
+            ))
+        );
+        assert_eq!(
+            interpreter.run_isolated("\"a\" + \"b\";"),
+            Ok(Value::String("ab".into()))
+        );
+    }
+
+    #[test]
+    fn test_shared_strings() {
+        // Strings are shared between the variables holding them, but still
+        // behave as values: building a new string never changes one that a
+        // variable already holds, and equality compares contents.
+        let (stdout, _) = run("var a = \"ab\";
+            var b = a;
+            b += \"c\";
+            print a;
+            print b;
+            var c = \"a\" + \"b\";
+            print a == c;
+            print identical(a, c);
+            print b == a + \"c\";
+            var m = {a: 1};
+            print m[c];
+            var parts = [a, a];
+            parts[0] = parts[0] * 2;
+            print parts;");
+        assert_eq!(stdout, "ab\nabc\ntrue\ntrue\ntrue\n1\n[\"abab\", \"ab\"]\n");
+    }
+
+    #[test]
+    fn test_string_repetition() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.run_isolated("\"ab\" * 3;"),
+            Ok(Value::String("ababab".into()))
+        );
+        assert_eq!(
+            interpreter.run_isolated("3 * \"ab\";"),
+            Ok(Value::String("ababab".into()))
+        );
+        assert_eq!(
+            interpreter.run_isolated("\"ab\" * 0;"),
+            Ok(Value::String("".into()))
+        );
+        assert_eq!(
+            interpreter.run_isolated("\"ab\" * 1.5;").map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Detailed(
+                "string repeat count must be a non-negative integer, got 1.5".to_string()
+            )))
+        );
+        assert_eq!(
+            interpreter.run_isolated("\"ab\" * -1;").map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Detailed(
+                "string repeat count must be a non-negative integer, got -1".to_string()
+            )))
+        );
+        assert!(interpreter.run_isolated("\"ab\" * 1e12;").is_err());
+    }
+
+    #[test]
+    fn test_exponent() {
+        let mut interpreter = Interpreter::new();
+        assert!(matches!(
+            interpreter.run_isolated("2 ** 3 ** 2;"),
+            Ok(Value::Int(512))
+        ));
+        assert!(matches!(
+            interpreter.run_isolated("-2 ** 2;"),
+            Ok(Value::Int(-4))
+        ));
+        assert!(matches!(
+            interpreter.run_isolated("4 ** 0.5;"),
+            Ok(Value::Number(2.0))
+        ));
+        assert_eq!(
+            interpreter.run_isolated("\"a\" ** 2;").map_err(unlocated),
+            Err(LuxError::Runtime(
+                "** can only act on a pair of numbers".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let (stdout, _) = run("var s = \"ab\";
+            s += \"cd\";
+            print s;
+            var n = 10;
+            n -= 4;
+            n *= 3;
+            n /= 2;
+            print n;
+            print n += 1;
+            class Box {}
+            var calls = 0;
+            fun get(b) { calls += 1; return b; }
+            var b = Box();
+            b.total = 1;
+            get(b).total += 5;
+            print b.total;
+            print calls;");
+        assert_eq!(stdout, "abcd\n9\n10\n6\n1\n");
+    }
+
+    #[test]
+    fn test_increment() {
+        let (stdout, _) = run("for (var i = 0; i < 3; i++) print i;
+            var n = 5;
+            print n++;
+            print n;
+            print ++n;
+            print n--;
+            print --n;
+            var x = 1.5;
+            x++;
+            print x;
+            fun counter() {
+                var count = 0;
+                fun next() { return ++count; }
+                return next;
+            }
+            var next = counter();
+            next();
+            print next();");
+        assert_eq!(stdout, "0\n1\n2\n5\n6\n7\n7\n5\n2.5\n2\n");
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let (stdout, _) = run("for (var i = 0; i < 3; i = i + 1) print i;
+            var i = \"outer\";
+            for (i = 0; i < 1; i = i + 1) {}
+            print i;");
+        assert_eq!(stdout, "0\n1\n2\n1\n");
+    }
+
+    #[test]
+    fn test_globals_from_host() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_global("input", Value::list(vec![Value::Number(1.0)]));
+        interpreter
+            .interpret(&parse("var output = input;"))
+            .unwrap();
+        assert_eq!(
+            interpreter.get_global("output"),
+            Some(Value::list(vec![Value::Number(1.0)]))
+        );
+        assert_eq!(interpreter.get_global("missing"), None);
+    }
+
+    #[test]
+    fn test_while_checks_condition_before_body() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.interpret(&parse("while (false) -\"a\";")),
+            Ok(())
+        );
+        assert_eq!(
+            interpreter
+                .interpret(&parse("while (-\"a\") nil;"))
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message("negation can only act on a number"))
+        );
+    }
+
+    #[test]
+    fn test_list_literals() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(&parse("var empty = []; var nested = [1, [-2, \"a\"]];"))
+            .unwrap();
+        assert_eq!(interpreter.get_global("empty"), Some(Value::list(vec![])));
+        assert_eq!(
+            interpreter.get_global("nested"),
+            Some(Value::list(vec![
+                Value::Int(1),
+                Value::list(vec![Value::Int(-2), Value::String("a".into())]),
+            ]))
+        );
+        // Equality can't tell 1 from 1.0, so check the kinds of number too.
+        assert_eq!(
+            interpreter
+                .run_isolated("[type(nested[0]), type(nested[1][0])];")
+                .unwrap()
+                .to_string(),
+            "[\"int\", \"int\"]"
+        );
+    }
+
+    #[test]
+    fn test_list_literals_compare_structurally() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .interpret(&parse(
+                "var same = [1, [2, \"a\"]] == [1, [2, \"a\"]]; var different = [] != [nil];",
+            ))
+            .unwrap();
+        assert_eq!(interpreter.get_global("same"), Some(Value::Bool(true)));
+        assert_eq!(interpreter.get_global("different"), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_interrupt_from_another_thread() {
+        for endless in [
+            "while (true) nil;",
+            "class Forever { iterate() { return this; } next() { return true; } value() {} }
+            for (var x in Forever()) {}",
+        ] {
+            interrupt_from_another_thread(endless);
+        }
+    }
+
+    fn interrupt_from_another_thread(endless: &'static str) {
+        let (handle_sender, handle_receiver) = mpsc::channel();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            let mut interpreter = Interpreter::new();
+            handle_sender.send(interpreter.interrupt_handle()).unwrap();
+            let interrupted = interpreter.interpret(&parse(endless));
+            let resumed = interpreter.interpret(&parse("var done = true;"));
+            result_sender
+                .send((
+                    interrupted,
+                    resumed,
+                    interpreter.get_global("done") == Some(Value::Bool(true)),
+                ))
+                .unwrap();
+        });
+        let handle = handle_receiver.recv().unwrap();
+        handle.interrupt();
+        let (interrupted, resumed, done) = result_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("interpreter did not stop");
+        worker.join().unwrap();
+        assert_eq!(interrupted, Err(RuntimeError::Interrupted));
+        assert_eq!(resumed, Ok(()));
+        assert!(done);
+    }
+
+    /// Write `files` under a fresh temporary directory, returning its path.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lux-{}-{}", name, std::process::id()));
+        for (path, source) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
+    /// Run the script at `path` and return what it printed.
+    fn run_script(path: &Path) -> Result<String, RuntimeError> {
+        let stdout = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_output(stdout.clone(), io::sink());
+        interpreter.set_script_path(path);
+        let statements = parse(&std::fs::read_to_string(path).unwrap());
+        Resolver::new(&mut interpreter, &mut StdoutReporter::default()).resolve(&statements);
+        interpreter.interpret(&statements)?;
+        Ok(stdout.contents())
+    }
+
+    #[test]
+    fn test_shebang() {
+        let dir = write_files(
+            "shebang",
+            &[
+                (
+                    "main.lux",
+                    "#!/usr/bin/env lux\nimport \"lib.lux\";\nprint x;\n",
+                ),
+                ("lib.lux", "#!/usr/bin/env lux\nvar x = 1;\n"),
+                ("bad.lux", "#!/usr/bin/env lux\nvar = 1;\n"),
+                ("uses_bad.lux", "import \"bad.lux\";"),
+            ],
+        );
+        assert_eq!(run_script(&dir.join("main.lux")), Ok("1\n".to_string()));
+        assert_eq!(
+            run_script(&dir.join("uses_bad.lux")).map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Detailed(
+                "[bad.lux line 2, col 5] Error at '=': Expected variable name, found '='"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = write_files(
+            "import",
+            &[
+                (
+                    "main.lux",
+                    "import \"lib/math.lux\" as math;
+                    import \"lib/math.lux\";
+                    print math.square(3);
+                    print square(4);
+                    print math.count;
+                    print math;
+                    print type(math);",
+                ),
+                (
+                    "lib/math.lux",
+                    "import \"helpers.lux\";
+                    var count = 0;
+                    fun square(x) { count = count + 1; return identity(x) * x; }",
+                ),
+                (
+                    "lib/helpers.lux",
+                    "fun identity(x) { return x; }
+                    print \"helpers loaded\";",
+                ),
+            ],
+        );
+        assert_eq!(
+            run_script(&dir.join("main.lux")),
+            Ok("helpers loaded\n9\n16\n2\n<module lib/math.lux>\nmodule\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let dir = write_files(
+            "import-errors",
+            &[
+                ("main.lux", "import \"a.lux\";"),
+                ("a.lux", "import \"main.lux\";"),
+                ("missing_export.lux", "import \"b.lux\" as b; print b.nope;"),
+                ("b.lux", "var x = 1;"),
+                ("bad_syntax.lux", "import \"c.lux\";"),
+                ("c.lux", "var x = 1;\nvar = 2;"),
+                ("failing.lux", "import \"d.lux\";"),
+                ("d.lux", "print -\"x\";"),
+                ("nested.lux", "{ import \"b.lux\"; }"),
+            ],
+        );
+        assert_eq!(
+            run_script(&dir.join("main.lux")).map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Detailed(
+                "circular import of 'main.lux' (in a.lux)".to_string()
+            ))
+        );
+        assert_eq!(
+            run_script(&dir.join("missing_export.lux")).map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Detailed(
+                "module 'b.lux' has no export 'nope'".to_string()
+            ))
+        );
+        assert_eq!(
+            run_script(&dir.join("bad_syntax.lux")).map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Detailed(
+                "[c.lux line 2, col 5] Error at '=': Expected variable name, found '='".to_string()
+            ))
+        );
+        assert!(matches!(
+            run_script(&dir.join("failing.lux")).map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Detailed(message)) if message.ends_with(" (in d.lux)")
+        ));
+
+        let mut reporter = BufferedReporter::default();
+        let statements = parse(&std::fs::read_to_string(dir.join("nested.lux")).unwrap());
+        Resolver::new(&mut Interpreter::new(), &mut reporter).resolve(&statements);
+        assert_eq!(
+            reporter.messages,
+            ["[line 1, col 3] Error at 'import': Can only import at the top level."]
+        );
     }
 }
@@ -0,0 +1,142 @@
+pub mod ast_printer;
+pub mod class;
+pub mod environment;
+pub mod expressions;
+pub mod function;
+pub mod interpreter;
+pub mod module;
+pub mod natives;
+pub mod parser;
+pub mod reporter;
+pub mod resolver;
+pub mod scanner;
+pub mod statements;
+pub mod symbol;
+pub mod token;
+pub mod value;
+
+use std::fmt;
+
+use expressions::Expr;
+use interpreter::{Interpreter, RuntimeError};
+use reporter::BufferedReporter;
+use value::Value;
+
+/// Everything that can go wrong running a snippet of Lux through the
+/// embedding API.
+#[derive(Debug, PartialEq)]
+pub enum LuxError {
+    /// Scanner and parser diagnostics; the snippet did not run.
+    Compile(Vec<String>),
+    Runtime(RuntimeError),
+}
+
+impl From<RuntimeError> for LuxError {
+    fn from(error: RuntimeError) -> Self {
+        Self::Runtime(error)
+    }
+}
+
+impl fmt::Display for LuxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Compile(messages) => write!(f, "{}", messages.join("\n")),
+            Self::Runtime(error) => write!(f, "{}", reporter::runtime_message(&None, error)),
+        }
+    }
+}
+
+/// A parsed expression that can be evaluated many times without scanning
+/// and parsing it again. It holds no interpreter state, so it can be built
+/// once and shared across threads.
+#[derive(Clone, Debug)]
+pub struct CompiledExpr {
+    expr: Expr,
+}
+
+impl CompiledExpr {
+    /// Evaluate the expression with `bindings` defined as variables on top
+    /// of the interpreter's globals. The bindings are gone afterwards.
+    pub fn eval(
+        &self,
+        interpreter: &mut Interpreter,
+        bindings: &[(&str, Value)],
+    ) -> Result<Value, RuntimeError> {
+        interpreter.evaluate_with(&self.expr, bindings)
+    }
+}
+
+/// Parse `source` as a single expression, e.g. `price * (1 + rate)`.
+pub fn compile_expression(source: &str) -> Result<CompiledExpr, LuxError> {
+    let mut reporter = BufferedReporter::default();
+    let mut scanner = scanner::Scanner::new(source, &mut reporter);
+    scanner.scan_tokens();
+    let tokens = scanner.into_tokens();
+    let expr = parser::Parser::new(tokens, &mut reporter).parse_expression();
+    match expr {
+        Some(expr) if reporter.messages.is_empty() => Ok(CompiledExpr { expr }),
+        _ => Err(LuxError::Compile(reporter.messages)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compiled_expression() {
+        let compiled = compile_expression("price * (1 + rate)").unwrap();
+        let mut interpreter = Interpreter::new();
+        for (price, expected) in [(100.0, 125.0), (8.0, 10.0)] {
+            let bindings = [
+                ("price", Value::Number(price)),
+                ("rate", Value::Number(0.25)),
+            ];
+            assert_eq!(
+                compiled.eval(&mut interpreter, &bindings),
+                Ok(Value::Number(expected))
+            );
+        }
+        assert_eq!(interpreter.get_global("price"), None);
+        assert_eq!(
+            compiled
+                .eval(&mut interpreter, &[("price", Value::Number(1.0))])
+                .map_err(RuntimeError::into_unlocated),
+            Err(RuntimeError::Message("undefined variable"))
+        );
+    }
+
+    #[test]
+    fn test_compiled_expression_sees_globals() {
+        let compiled = compile_expression("[base, x]").unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.define_global("base", Value::Number(1.0));
+        assert_eq!(
+            compiled.eval(&mut interpreter, &[("x", Value::Nil)]),
+            Ok(Value::list(vec![Value::Number(1.0), Value::Nil]))
+        );
+    }
+
+    #[test]
+    fn test_compile_expression_errors() {
+        assert_eq!(
+            compile_expression("1 +").map(|_| ()),
+            Err(LuxError::Compile(vec![
+                "[line 1, col 4] Error at end: Unexpected end of input, expected expression."
+                    .to_string()
+            ]))
+        );
+        assert_eq!(
+            compile_expression("1; 2").map(|_| ()),
+            Err(LuxError::Compile(vec![
+                "[line 1, col 2] Error at ';': Expected end of expression, found ';'".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_compiled_expression_is_send() {
+        fn assert_send<T: Send + Sync>() {}
+        assert_send::<CompiledExpr>();
+    }
+}
@@ -1,61 +1,128 @@
-mod ast_printer;
-mod environment;
-mod expressions;
-mod interpreter;
-mod parser;
-mod reporter;
-mod scanner;
-mod statements;
-mod token;
-use interpreter::Interpreter;
+use lux::interpreter::Interpreter;
+use lux::reporter::{Reporter, StdoutReporter};
+use lux::resolver::Resolver;
+use lux::statements::Stmt;
+use lux::{parser, scanner};
+use std::cell::RefCell;
 use std::env;
 use std::fs;
 use std::io;
 use std::io::Write;
+use std::path::Path;
+use std::process;
 
-use crate::reporter::StdoutReporter;
-
-struct Lux;
+#[derive(Default)]
+struct Lux {
+    /// Suppress warning diagnostics (`--no-warn`).
+    no_warn: bool,
+    /// Don't stringify non-strings joined to a string with `+` (`--strict`).
+    strict: bool,
+    /// Let float division by zero give infinity or NaN (`--ieee-division`).
+    ieee_division: bool,
+    /// Warn about local declarations that shadow another (`--warn-shadow`).
+    warn_shadow: bool,
+}
 
 impl Lux {
-    fn run_file(file_path: &str) -> Result<(), std::io::Error> {
+    /// Run the script at `file_path`, exiting with status 65 if it does not
+    /// compile and 70 if it fails while running.
+    fn run_file(&self, file_path: &str) -> Result<(), std::io::Error> {
         let program = fs::read_to_string(file_path)?;
-        let mut interpreter = interpreter::Interpreter::new();
-        Self::run(&program, &mut interpreter);
+        let mut interpreter = self.interpreter();
+        interpreter.set_script_path(Path::new(file_path));
+        let mut reporter = self.reporter();
+        self.run(&program, &mut reporter, &mut interpreter);
+        if reporter.had_error() {
+            process::exit(65);
+        }
+        if reporter.had_runtime_error() {
+            process::exit(70);
+        }
         Ok(())
     }
 
-    fn run_prompt() -> Result<(), std::io::Error> {
-        let mut interpreter = interpreter::Interpreter::new();
+    fn run_prompt(&self) -> Result<(), std::io::Error> {
+        let mut interpreter = self.interpreter();
+        let reporter = RefCell::new(self.reporter());
+        let (mut scanning, mut parsing) = (&reporter, &reporter);
+        // Reused for every entry, so that line numbers carry on from one
+        // entry to the next.
+        let mut scanner = scanner::Scanner::new("", &mut scanning);
+        let mut parser = parser::Parser::new(Vec::new(), &mut parsing);
         loop {
             print!("> ");
             io::stdout().flush()?;
             let mut buf = String::new();
-            let _bytes = io::stdin().read_line(&mut buf)?;
-            Self::run(&buf, &mut interpreter);
+            if io::stdin().read_line(&mut buf)? == 0 {
+                return Ok(());
+            }
+            reporter.borrow_mut().reset();
+            scanner.reset(&buf, true);
+            scanner.scan_tokens();
+            parser.reset(std::mem::take(&mut scanner.tokens));
+            // The errors have been printed already, so just read the next
+            // entry.
+            if let Ok(statements) = parser.parse() {
+                self.execute(&statements, &mut reporter.borrow_mut(), &mut interpreter);
+            }
         }
     }
 
-    fn run(source: &str, interpreter: &mut Interpreter) {
+    fn interpreter(&self) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict(self.strict);
+        interpreter.set_ieee_division(self.ieee_division);
+        interpreter
+    }
+
+    fn reporter(&self) -> StdoutReporter {
         let mut reporter = StdoutReporter::default();
-        let mut scanner = scanner::Scanner::new(source, &mut reporter);
+        reporter.no_warn = self.no_warn;
+        reporter
+    }
+
+    fn run(&self, source: &str, reporter: &mut StdoutReporter, interpreter: &mut Interpreter) {
+        let mut scanner = scanner::Scanner::new(source, reporter);
         scanner.scan_tokens();
         let tokens = scanner.into_tokens();
-        let mut parser = parser::Parser::new(tokens, &mut reporter);
-        let statements = parser.parse();
-        match interpreter.interpret(&statements) {
-            Ok(_) => {}
-            Err(message) => println!("error in interpreter: {}", message),
+        if let Ok(statements) = parser::Parser::new(tokens, reporter).parse() {
+            self.execute(&statements, reporter, interpreter);
+        }
+    }
+
+    /// Resolve and run `statements`, unless `reporter` has seen errors in
+    /// them. A runtime error is reported to `reporter`.
+    fn execute(
+        &self,
+        statements: &Vec<Stmt>,
+        reporter: &mut StdoutReporter,
+        interpreter: &mut Interpreter,
+    ) {
+        let mut resolver = Resolver::new(interpreter, reporter);
+        resolver.set_warn_shadow(self.warn_shadow);
+        resolver.resolve(statements);
+        if reporter.had_error() {
+            return;
+        }
+        if let Err(error) = interpreter.interpret(statements) {
+            reporter.runtime_error(&error);
         }
     }
 }
 
 fn main() -> Result<(), std::io::Error> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() == 2 {
-        Lux::run_file(&args[1])?;
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let lux = Lux {
+        no_warn: flags.iter().any(|flag| flag == "--no-warn"),
+        strict: flags.iter().any(|flag| flag == "--strict"),
+        ieee_division: flags.iter().any(|flag| flag == "--ieee-division"),
+        warn_shadow: flags.iter().any(|flag| flag == "--warn-shadow"),
+    };
+    if args.len() == 1 {
+        lux.run_file(&args[0])?;
     } else {
-        Lux::run_prompt()?;
+        lux.run_prompt()?;
     }
     Ok(())
 }
@@ -0,0 +1,41 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::symbol::Symbol;
+use crate::value::Value;
+
+/// A file loaded by `import`. Its top level ran once in a scope of its own,
+/// and every global it defined there is an export.
+#[derive(Debug)]
+pub struct Module {
+    /// The path as written in the first `import` of the file.
+    pub name: String,
+    pub globals: Rc<RefCell<Environment>>,
+}
+
+impl Module {
+    pub fn new(name: String, globals: Rc<RefCell<Environment>>) -> Self {
+        Self { name, globals }
+    }
+
+    /// The export `name`. Natives are not exports, so `m.clock` is undefined.
+    pub fn get(&self, name: Symbol) -> Option<Value> {
+        self.globals.borrow().get_own(name)
+    }
+
+    pub fn exports(&self) -> Vec<(Symbol, Value)> {
+        self.globals
+            .borrow()
+            .bindings()
+            .map(|(name, value)| (name, value.clone()))
+            .collect()
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
@@ -0,0 +1,819 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::class::{Class, Instance};
+use crate::function::{Callable, NativeFunction};
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::reporter::BufferedReporter;
+use crate::scanner::Scanner;
+use crate::token::TokenType;
+use crate::value::{self, fmt_value, Value};
+
+/// Register the functions every script can call without defining them.
+/// Each checks the types of its arguments; the interpreter has already
+/// checked how many there are.
+pub fn define_all(interpreter: &mut Interpreter) {
+    interpreter.define_native("clock", 0, clock);
+    interpreter.define_native("type", 1, type_of);
+    interpreter.define_native("identical", 2, identical);
+    interpreter.define_native("assertEqual", 2, assert_equal);
+    interpreter.define_native("fields", 1, fields);
+    interpreter.define_native("hasField", 2, has_field);
+    interpreter.define_native("getField", 2, get_field);
+    interpreter.define_native("setField", 3, set_field);
+    interpreter.define_native("len", 1, len);
+    interpreter.define_native("substring", 3, substring);
+    interpreter.define_native("upper", 1, upper);
+    interpreter.define_native("lower", 1, lower);
+    interpreter.define_native("readLine", 0, read_line);
+    interpreter.define_native("lines", 0, lines);
+    interpreter.define_native("str", 1, str);
+    interpreter.define_native("num", 1, num);
+    interpreter.define_native("floor", 1, floor);
+    interpreter.define_native("ceil", 1, ceil);
+    interpreter.define_native("round", 1, round);
+    interpreter.define_native("toFixed", 2, to_fixed);
+    interpreter.define_native("toPrecision", 2, to_precision);
+    interpreter.define_native("sqrt", 1, sqrt);
+    interpreter.define_native("abs", 1, abs);
+    interpreter.define_variadic_native("min", 2, min);
+    interpreter.define_variadic_native("max", 2, max);
+    interpreter.define_native("random", 0, random);
+    interpreter.define_native("randomInt", 2, random_int);
+    interpreter.define_native("seed", 1, seed);
+}
+
+/// A small pseudo-random generator (SplitMix64). Each interpreter owns one,
+/// so seeding it makes that interpreter's sequence repeatable without
+/// affecting any other.
+#[derive(Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// A generator seeded from the system clock.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A float in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An integer in `0..bound`, which must not be zero.
+    fn below(&mut self, bound: u128) -> u128 {
+        (self.next_u64() as u128 * bound) >> 64
+    }
+}
+
+/// Seconds since the Unix epoch, for timing scripts.
+fn clock(_interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| "system clock is before the Unix epoch")?;
+    Ok(Value::Number(elapsed.as_secs_f64()))
+}
+
+/// The name of the argument's type, or of its class for an instance.
+fn type_of(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let name = match &arguments[0] {
+        Value::Instance(instance) => instance.class.name.clone(),
+        value => value.type_name().to_string(),
+    };
+    Ok(Value::String(name.into()))
+}
+
+/// Whether both arguments are the very same value, where `==` compares the
+/// contents of lists and maps.
+fn identical(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Bool(value::identical(&arguments[0], &arguments[1])))
+}
+
+/// Fail unless the arguments are equal as `==` compares them, so lists and
+/// maps are compared by their contents.
+fn assert_equal(
+    _interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    if value::deep_eq(&arguments[0], &arguments[1]) {
+        return Ok(Value::Nil);
+    }
+    Err(RuntimeError::Detailed(format!(
+        "assertEqual() failed: {} is not equal to {}",
+        fmt_value(&arguments[0]),
+        fmt_value(&arguments[1])
+    )))
+}
+
+/// The argument at `index` to the native `function`, which must be an
+/// instance.
+fn instance_argument<'a>(
+    arguments: &'a [Value],
+    index: usize,
+    function: &str,
+) -> Result<&'a Rc<Instance>, RuntimeError> {
+    match &arguments[index] {
+        Value::Instance(instance) => Ok(instance),
+        value => Err(RuntimeError::Detailed(format!(
+            "{}() expects an instance as argument {}, got {}",
+            function,
+            index + 1,
+            value.type_name()
+        ))),
+    }
+}
+
+/// The names of an instance's fields as a sorted list of strings. Methods
+/// are not fields.
+fn fields(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_argument(&arguments, 0, "fields")?;
+    let names = instance
+        .field_names()
+        .into_iter()
+        .map(|name| Value::String(name.into()));
+    Ok(Value::list(names.collect()))
+}
+
+fn has_field(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_argument(&arguments, 0, "hasField")?;
+    let name = string_argument(&arguments, 1, "hasField")?;
+    Ok(Value::Bool(instance.field(name).is_some()))
+}
+
+/// The field `name` of an instance, or nil if it has no such field.
+fn get_field(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_argument(&arguments, 0, "getField")?;
+    let name = string_argument(&arguments, 1, "getField")?;
+    Ok(instance.field(name).unwrap_or(Value::Nil))
+}
+
+/// Set the field `name` of an instance, returning the value like an
+/// assignment does.
+fn set_field(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let instance = instance_argument(&arguments, 0, "setField")?;
+    let name = string_argument(&arguments, 1, "setField")?;
+    instance.set(name, arguments[2].clone());
+    Ok(arguments[2].clone())
+}
+
+/// The argument at `index` to the native `function`, which must be a string.
+fn string_argument<'a>(
+    arguments: &'a [Value],
+    index: usize,
+    function: &str,
+) -> Result<&'a str, RuntimeError> {
+    match &arguments[index] {
+        Value::String(string) => Ok(string),
+        value => Err(RuntimeError::Detailed(format!(
+            "{}() expects a string as argument {}, got {}",
+            function,
+            index + 1,
+            value.type_name()
+        ))),
+    }
+}
+
+/// The argument at `index` to the native `function`, which must be a whole
+/// number, though it may be written as a float.
+fn integer_argument(
+    arguments: &[Value],
+    index: usize,
+    function: &str,
+) -> Result<i64, RuntimeError> {
+    match &arguments[index] {
+        Value::Int(value) => Ok(*value),
+        Value::Number(value) if value.fract() == 0.0 => Ok(*value as i64),
+        value => Err(RuntimeError::Detailed(format!(
+            "{}() expects an integer as argument {}, got {}",
+            function,
+            index + 1,
+            fmt_value(value)
+        ))),
+    }
+}
+
+/// The number of characters in a string, or of elements in a list, tuple
+/// or map.
+fn len(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let length = match &arguments[0] {
+        Value::String(string) => string.chars().count(),
+        Value::List(values) => values.borrow().len(),
+        Value::Tuple(values) => values.len(),
+        Value::Map(entries) => entries.borrow().len(),
+        value => {
+            return Err(RuntimeError::Detailed(format!(
+                "len() expects a string, list, tuple or map, got {}",
+                value.type_name()
+            )))
+        }
+    };
+    Ok(Value::Int(length as i64))
+}
+
+/// The characters of a string from `start` up to but not including `end`.
+fn substring(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let string = string_argument(&arguments, 0, "substring")?;
+    let start = integer_argument(&arguments, 1, "substring")?;
+    let end = integer_argument(&arguments, 2, "substring")?;
+    let length = string.chars().count() as i64;
+    if start < 0 || start > end || end > length {
+        return Err(RuntimeError::Detailed(format!(
+            "substring range {}..{} is out of bounds for a string of {} characters",
+            start, end, length
+        )));
+    }
+    let chars = string
+        .chars()
+        .skip(start as usize)
+        .take((end - start) as usize);
+    Ok(Value::String(chars.collect::<String>().into()))
+}
+
+fn upper(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::String(
+        string_argument(&arguments, 0, "upper")?
+            .to_uppercase()
+            .into(),
+    ))
+}
+
+fn lower(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::String(
+        string_argument(&arguments, 0, "lower")?
+            .to_lowercase()
+            .into(),
+    ))
+}
+
+/// The next line of input without its line ending, or nil at end of input.
+fn read_line(interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(interpreter
+        .read_line()?
+        .map_or(Value::Nil, |line| Value::String(line.into())))
+}
+
+/// An iterable over the rest of the input, so `for (line in lines())` runs
+/// once per line, without its line ending, until end of input. Lines are
+/// read one at a time as the loop asks for them, not up front.
+fn lines(_interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let lines = Instance::new(native_class("Lines"));
+    let iterate = NativeFunction::new("iterate", 0, line_iterator);
+    lines.set("iterate", Value::Callable(Rc::new(iterate)));
+    Ok(Value::Instance(Rc::new(lines)))
+}
+
+/// What `iterate()` on `lines()` returns: an iterator whose `next()` reads
+/// a line and whose `value()` is the line it read.
+fn line_iterator(
+    _interpreter: &mut Interpreter,
+    _arguments: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let iterator = Instance::new(native_class("LineIterator"));
+    let line = Rc::new(RefCell::new(Value::Nil));
+    for name in ["next", "value"] {
+        let method = LineMethod {
+            name,
+            line: Rc::clone(&line),
+        };
+        iterator.set(name, Value::Callable(Rc::new(method)));
+    }
+    Ok(Value::Instance(Rc::new(iterator)))
+}
+
+/// A class with no methods, for the instances natives hand back.
+fn native_class(name: &str) -> Rc<Class> {
+    Rc::new(Class::new(name.to_string(), None, HashMap::new()))
+}
+
+/// The `next()` or `value()` of a line iterator, sharing the line last
+/// read.
+#[derive(Debug)]
+struct LineMethod {
+    name: &'static str,
+    line: Rc<RefCell<Value>>,
+}
+
+impl Callable for LineMethod {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        if self.name == "value" {
+            return Ok(self.line.borrow().clone());
+        }
+        let line = read_line(interpreter, arguments)?;
+        let more = !matches!(line, Value::Nil);
+        *self.line.borrow_mut() = line;
+        Ok(if more { Value::Bool(true) } else { Value::Nil })
+    }
+}
+
+impl fmt::Display for LineMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// The argument as `print` would show it.
+fn str(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::String(interpreter.stringify(&arguments[0])?.into()))
+}
+
+/// The number written in a string, which may be surrounded by whitespace and
+/// start with a minus but is otherwise a number literal as the scanner reads
+/// it, e.g. `" -0x1f "`. Anything else yields nil.
+fn num(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let text = string_argument(&arguments, 0, "num")?.trim();
+    let (negative, literal) = match text.strip_prefix('-') {
+        Some(literal) => (true, literal),
+        None => (false, text),
+    };
+    let mut reporter = BufferedReporter::default();
+    let mut scanner = Scanner::new(literal, &mut reporter);
+    scanner.scan_tokens();
+    let tokens = scanner.into_tokens();
+    if !reporter.messages.is_empty() || tokens.len() != 2 || tokens[0].lexeme != literal {
+        return Ok(Value::Nil);
+    }
+    Ok(match tokens[0].token_type {
+        TokenType::Int(value) if negative => Value::Int(-value),
+        TokenType::Int(value) => Value::Int(value),
+        TokenType::Number(value) if negative => Value::Number(-value),
+        TokenType::Number(value) => Value::Number(value),
+        _ => Value::Nil,
+    })
+}
+
+/// The argument at `index` to the native `function`, which must be an int or
+/// a float.
+fn number_argument(arguments: &[Value], index: usize, function: &str) -> Result<f64, RuntimeError> {
+    arguments[index].as_f64().ok_or_else(|| {
+        RuntimeError::Detailed(format!(
+            "{}() expects a number as argument {}, got {}",
+            function,
+            index + 1,
+            arguments[index].type_name()
+        ))
+    })
+}
+
+/// `number` as an int when it is a whole number that fits one, which it is
+/// after rounding unless it was huge, infinite or NaN to begin with.
+fn whole_number(number: f64) -> Value {
+    if number.is_finite() && number.abs() < i64::MAX as f64 {
+        Value::Int(number as i64)
+    } else {
+        Value::Number(number)
+    }
+}
+
+fn floor(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(whole_number(
+        number_argument(&arguments, 0, "floor")?.floor(),
+    ))
+}
+
+fn ceil(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(whole_number(number_argument(&arguments, 0, "ceil")?.ceil()))
+}
+
+/// Rounds halfway cases away from zero, so `round(2.5)` is 3.
+fn round(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(whole_number(
+        number_argument(&arguments, 0, "round")?.round(),
+    ))
+}
+
+/// The number as a string with exactly `digits` digits after the point.
+fn to_fixed(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let number = number_argument(&arguments, 0, "toFixed")?;
+    let digits = number_argument(&arguments, 1, "toFixed")?;
+    Ok(Value::String(value::fmt_fixed(number, digits)?.into()))
+}
+
+/// The number as a string with `digits` significant digits.
+fn to_precision(
+    _interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    let number = number_argument(&arguments, 0, "toPrecision")?;
+    let digits = number_argument(&arguments, 1, "toPrecision")?;
+    Ok(Value::String(value::fmt_precision(number, digits)?.into()))
+}
+
+/// The square root of a negative number is an error rather than NaN.
+fn sqrt(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let number = number_argument(&arguments, 0, "sqrt")?;
+    if number < 0.0 {
+        return Err(RuntimeError::Detailed(format!(
+            "sqrt() expects a non-negative number, got {}",
+            fmt_value(&arguments[0])
+        )));
+    }
+    Ok(Value::Number(number.sqrt()))
+}
+
+fn abs(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Int(value) => value
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or("integer overflow".into()),
+        _ => Ok(Value::Number(number_argument(&arguments, 0, "abs")?.abs())),
+    }
+}
+
+/// The argument that comes first when ordered by `keep`, returned as it
+/// was passed so that ints stay ints.
+fn extreme(
+    arguments: Vec<Value>,
+    function: &str,
+    keep: fn(f64, f64) -> bool,
+) -> Result<Value, RuntimeError> {
+    let mut best = 0;
+    for index in 1..arguments.len() {
+        if keep(
+            number_argument(&arguments, index, function)?,
+            number_argument(&arguments, best, function)?,
+        ) {
+            best = index;
+        }
+    }
+    Ok(arguments.into_iter().nth(best).unwrap_or(Value::Nil))
+}
+
+fn min(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    extreme(arguments, "min", |candidate, best| candidate < best)
+}
+
+fn max(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    extreme(arguments, "max", |candidate, best| candidate > best)
+}
+
+/// A float in [0, 1).
+fn random(interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Number(interpreter.rng().next_f64()))
+}
+
+/// An int between `min` and `max`, both included.
+fn random_int(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let min = integer_argument(&arguments, 0, "randomInt")?;
+    let max = integer_argument(&arguments, 1, "randomInt")?;
+    if min > max {
+        return Err(RuntimeError::Detailed(format!(
+            "randomInt() expects min <= max, got {} and {}",
+            min, max
+        )));
+    }
+    let span = (max as i128 - min as i128 + 1) as u128;
+    let offset = interpreter.rng().below(span);
+    Ok(Value::Int((min as i128 + offset as i128) as i64))
+}
+
+/// Restart the sequence of `random` and `randomInt` from `n`.
+fn seed(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    let seed = integer_argument(&arguments, 0, "seed")?;
+    *interpreter.rng() = Rng::new(seed as u64);
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LuxError;
+
+    /// `error` without where it was raised, which the interpreter's tests
+    /// cover.
+    fn unlocated(error: LuxError) -> LuxError {
+        match error {
+            LuxError::Runtime(error) => LuxError::Runtime(error.into_unlocated()),
+            error => error,
+        }
+    }
+
+    fn eval(source: &str) -> Result<Value, LuxError> {
+        Interpreter::new().run_isolated(source).map_err(unlocated)
+    }
+
+    fn error(message: &str) -> Result<Value, LuxError> {
+        Err(LuxError::Runtime(RuntimeError::Detailed(
+            message.to_string(),
+        )))
+    }
+
+    #[test]
+    fn test_seeded_random_is_repeatable() {
+        let source = "seed(42); [random(), random(), randomInt(1, 6), randomInt(-3, 3)];";
+        let first = eval(source).unwrap();
+        assert_eq!(eval(source).unwrap(), first);
+        assert_ne!(
+            eval("seed(43); [random(), random()];").unwrap(),
+            eval("seed(42); [random(), random()];").unwrap()
+        );
+
+        let mut interpreter = Interpreter::new();
+        let values = interpreter
+            .run_isolated(
+                "seed(7); var xs = []; for (var i in [1, 2, 3]) { xs = [xs, random()]; } xs;",
+            )
+            .unwrap();
+        assert_eq!(
+            interpreter.run_isolated(
+                "seed(7); var xs = []; for (var i in [1, 2, 3]) { xs = [xs, random()]; } xs;"
+            ),
+            Ok(values)
+        );
+    }
+
+    #[test]
+    fn test_random_ranges() {
+        let mut interpreter = Interpreter::new();
+        for _ in 0..100 {
+            let Ok(Value::Number(x)) = interpreter.run_isolated("random();") else {
+                panic!("random should return a float");
+            };
+            assert!((0.0..1.0).contains(&x));
+            let Ok(Value::Int(n)) = interpreter.run_isolated("randomInt(-2, 2.0);") else {
+                panic!("randomInt should return an int");
+            };
+            assert!((-2..=2).contains(&n));
+        }
+        assert!(matches!(
+            interpreter.run_isolated("randomInt(5, 5);"),
+            Ok(Value::Int(5))
+        ));
+        assert!(interpreter
+            .run_isolated("randomInt(-9223372036854775807 - 1, 9223372036854775807);")
+            .is_ok());
+        assert_eq!(
+            interpreter
+                .run_isolated("randomInt(3, 1);")
+                .map_err(unlocated),
+            error("randomInt() expects min <= max, got 3 and 1")
+        );
+        assert_eq!(
+            interpreter
+                .run_isolated("randomInt(1, 2.5);")
+                .map_err(unlocated),
+            error("randomInt() expects an integer as argument 2, got 2.5")
+        );
+    }
+
+    #[test]
+    fn test_equality_and_identity() {
+        assert_eq!(
+            eval(
+                "var a = [1, [2, {\"k\": [3]}]];
+                var b = [1, [2, {\"k\": [3]}]];
+                var cyclic = [1];
+                cyclic[0] = cyclic;
+                var other = [1];
+                other[0] = other;
+                [a == b, a != b, identical(a, b), identical(a, a),
+                 {\"x\": 1, \"y\": 2} == {\"y\": 2, \"x\": 1}, [1, \"1\"] == [1, 1],
+                 cyclic == other, identical(cyclic, other)];"
+            ),
+            Ok(Value::list(
+                [true, false, false, true, true, false, true, false]
+                    .map(Value::Bool)
+                    .to_vec()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_assert_equal() {
+        assert_eq!(
+            eval("assertEqual([1, {\"k\": [2]}], [1, {\"k\": [2]}]); assertEqual(1, 1.0);"),
+            Ok(Value::Nil)
+        );
+        assert_eq!(
+            eval("assertEqual([1, \"a\"], [1, \"b\"]);"),
+            error("assertEqual() failed: [1, \"a\"] is not equal to [1, \"b\"]")
+        );
+        assert_eq!(
+            eval("assertEqual(nil, false);"),
+            error("assertEqual() failed: nil is not equal to false")
+        );
+    }
+
+    #[test]
+    fn test_reflection() {
+        assert_eq!(
+            eval(
+                "class P { var x = 1; init() { this.y = 2; } method() {} }
+                var p = P();
+                p.z = 3;
+                var name = \"w\";
+                setField(p, name, 4);
+                [fields(p), hasField(p, \"z\"), hasField(p, \"method\"),
+                 getField(p, \"w\"), getField(p, \"missing\"), p.w];"
+            )
+            .unwrap()
+            .to_string(),
+            "[[\"w\", \"x\", \"y\", \"z\"], true, false, 4, nil, 4]"
+        );
+        assert_eq!(
+            eval("fields([]);"),
+            error("fields() expects an instance as argument 1, got list")
+        );
+        assert_eq!(
+            eval("class P {} getField(P(), 1);"),
+            error("getField() expects a string as argument 2, got int")
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input("one\r\ntwo\n\nfour\n".as_bytes());
+        assert_eq!(
+            interpreter
+                .run_isolated(
+                    "var seen = [];
+                    for (line in lines()) { seen = [seen, len(line)]; }
+                    for (line in lines()) seen = 1;
+                    [seen, readLine()];"
+                )
+                .unwrap()
+                .to_string(),
+            "[[[[[[], 3], 3], 0], 4], nil]"
+        );
+
+        // Each pass reads one line, so input read elsewhere in the loop is
+        // not seen by it, and breaking out leaves the rest unread.
+        interpreter.set_input("a\nb\nc\nd\ne\nlast".as_bytes());
+        assert_eq!(
+            interpreter
+                .run_isolated(
+                    "var seen = [];
+                    for (line in lines()) {
+                        seen = [seen, line, readLine()];
+                        if (line == \"c\") break;
+                    }
+                    var rest = [];
+                    for (line in lines()) rest = [rest, line];
+                    [seen, rest, type(lines())];"
+                )
+                .unwrap()
+                .to_string(),
+            "[[[[], \"a\", \"b\"], \"c\", \"d\"], [[[], \"e\"], \"last\"], \"Lines\"]"
+        );
+
+        interpreter.set_input(&b"fine\n\xff\n"[..]);
+        assert_eq!(
+            interpreter
+                .run_isolated("for (line in lines()) {}")
+                .map_err(unlocated),
+            Err(LuxError::Runtime(RuntimeError::Message(
+                "input is not valid UTF-8"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_str_and_num() {
+        assert_eq!(
+            eval("str(1.5) + str([1, \"a\"]);"),
+            Ok(Value::String("1.5[1, \"a\"]".into()))
+        );
+        assert_eq!(eval("str(nil);"), Ok(Value::String("nil".into())));
+        assert!(matches!(eval("num(\" 42\n\");"), Ok(Value::Int(42))));
+        assert!(matches!(eval("num(\"-2.5\");"), Ok(Value::Number(-2.5))));
+        assert!(matches!(eval("num(\"0x1f\");"), Ok(Value::Int(31))));
+        assert!(matches!(eval("num(\"0b101\");"), Ok(Value::Int(5))));
+        for text in [
+            "", "abc", "1 2", "- 1", "--1", "1.", "1 + 1", "0xz", "'1'", "1 // one",
+        ] {
+            assert_eq!(
+                eval(&format!("num(\"{}\");", text)),
+                Ok(Value::Nil),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            eval("num(1);"),
+            error("num() expects a string as argument 1, got int")
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.run_isolated(
+                "var all = true;
+                for (var x in [0, 42, -7, 3.5, -0.25, 0.1, 1234567.125, 10.0 ** 21, 9223372036854775807]) {
+                    all = all and num(str(x)) == x;
+                }
+                all;"
+            ),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert!(matches!(eval("floor(3.7);"), Ok(Value::Int(3))));
+        assert!(matches!(eval("floor(-3.2);"), Ok(Value::Int(-4))));
+        assert!(matches!(eval("ceil(3.2);"), Ok(Value::Int(4))));
+        assert!(matches!(eval("round(2.5);"), Ok(Value::Int(3))));
+        assert!(matches!(eval("round(-2.5);"), Ok(Value::Int(-3))));
+        assert!(matches!(eval("floor(7);"), Ok(Value::Int(7))));
+        assert!(matches!(
+            eval("floor(10.0 ** 300);"),
+            Ok(Value::Number(1e300))
+        ));
+        assert_eq!(eval("floor(3.7) + 1;").unwrap().to_string(), "4");
+    }
+
+    #[test]
+    fn test_to_fixed_and_to_precision() {
+        assert_eq!(
+            eval(
+                "[toFixed(3.14159, 2), toFixed(2.675, 2), toFixed(7, 1), toFixed(-0.0001, 2),
+                 toPrecision(1234.5, 3), toPrecision(0.000123, 2), toPrecision(10.0 ** 400, 3)];"
+            )
+            .unwrap()
+            .to_string(),
+            "[\"3.14\", \"2.67\", \"7.0\", \"-0.00\", \"1.23e3\", \"0.00012\", \"inf\"]"
+        );
+        assert_eq!(
+            eval("toFixed(1, 1.5);"),
+            Err(LuxError::Runtime(RuntimeError::Message(
+                "digit count must be an integer between 0 and 100"
+            )))
+        );
+        assert_eq!(
+            eval("toPrecision(1, 0);"),
+            Err(LuxError::Runtime(RuntimeError::Message(
+                "digit count must be an integer between 1 and 100"
+            )))
+        );
+        assert_eq!(
+            eval("toFixed(\"1\", 2);"),
+            error("toFixed() expects a number as argument 1, got string")
+        );
+    }
+
+    #[test]
+    fn test_sqrt_and_abs() {
+        assert!(matches!(eval("sqrt(16);"), Ok(Value::Number(4.0))));
+        assert_eq!(
+            eval("sqrt(-4);"),
+            error("sqrt() expects a non-negative number, got -4")
+        );
+        assert!(matches!(eval("abs(-3);"), Ok(Value::Int(3))));
+        assert!(matches!(eval("abs(-2.5);"), Ok(Value::Number(2.5))));
+        assert_eq!(
+            eval("abs(\"x\");"),
+            error("abs() expects a number as argument 1, got string")
+        );
+    }
+
+    #[test]
+    fn test_min_and_max() {
+        assert!(matches!(eval("min(3, 1, 2);"), Ok(Value::Int(1))));
+        assert!(matches!(eval("max(3, 1.5, 2);"), Ok(Value::Int(3))));
+        assert!(matches!(eval("max(1, 2.5);"), Ok(Value::Number(2.5))));
+        assert_eq!(
+            eval("min(1, 2, nil);"),
+            error("min() expects a number as argument 3, got nil")
+        );
+        assert_eq!(
+            eval("max(\"a\", 1);"),
+            error("max() expects a number as argument 1, got string")
+        );
+        assert_eq!(
+            eval("min(1);"),
+            Err(LuxError::Runtime("wrong number of arguments".into()))
+        );
+    }
+}
@@ -1,49 +1,340 @@
 use crate::expressions::{
-    BinaryExpr, Expr, GroupingExpr, LiteralExpr, LiteralValue, UnaryExpr, VarExpr,
+    AssignExpr, BinaryExpr, CallExpr, ConditionalExpr, ErrorExpr, Expr, ExprId, GetExpr,
+    GroupingExpr, IfExpr, IncrementExpr, IndexExpr, IndexSetExpr, ListExpr, LiteralExpr,
+    LiteralValue, LogicalExpr, MapExpr, SetExpr, SuperExpr, ThisExpr, TupleExpr, UnaryExpr,
+    VarExpr,
 };
 use crate::reporter::Reporter;
-use crate::statements::{ExprStmt, PrintStmt, Stmt, VarStmt};
+use crate::statements::{
+    BlockStmt, BreakStmt, ClassStmt, ContinueStmt, DestructureStmt, DoWhileStmt, EprintStmt,
+    ExprStmt, ForInStmt, FunctionStmt, IfStmt, ImportStmt, PrintStmt, ReturnStmt, Stmt, SwitchStmt,
+    ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use crate::symbol::Symbol;
 use crate::token::{Token, TokenType};
+use crate::value::{deep_eq, Value};
+use std::rc::Rc;
+
+/// What sort of problem a `ParseError` is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The tokens can't be part of any valid program.
+    Syntax,
+    /// The input ended in the middle of a construct, so more of it might
+    /// complete the program, as when a REPL entry continues on the next
+    /// line.
+    Incomplete,
+}
 
+/// A syntax error, reported at `token`.
+#[derive(Clone, Debug)]
 pub struct ParseError {
-    token: Token,
-    message: String,
+    // Boxed to keep the `Result`s the parser passes around small.
+    pub token: Box<Token>,
+    pub message: String,
+    /// The token types that would have been accepted in place of `token`,
+    /// when the error is that a particular one was missing.
+    pub expected: Box<[TokenType]>,
+    pub kind: ParseErrorKind,
 }
 impl ParseError {
+    /// An error at `token`, which is incomplete input if that is the end.
     pub fn new(token: Token, message: String) -> Self {
-        Self { token, message }
+        let kind = match token.token_type {
+            TokenType::EndOfFile => ParseErrorKind::Incomplete,
+            _ => ParseErrorKind::Syntax,
+        };
+        Self {
+            token: Box::new(token),
+            message,
+            expected: Box::new([]),
+            kind,
+        }
+    }
+
+    /// An error for finding `token` where one of `expected` should be. The
+    /// message, which says what was expected, goes on to say what was found.
+    pub fn expected(token: Token, expected: &[TokenType], message: &str) -> Self {
+        let found = match token.token_type {
+            TokenType::EndOfFile => "end of input".to_string(),
+            _ => format!("'{}'", token.lexeme),
+        };
+        Self {
+            expected: expected.into(),
+            ..Self::new(token, format!("{}, found {}", message, found))
+        }
     }
 }
 // Statement grammar:
 // program        → declaration* EOF ;
-// declaration    → varDecl
+// declaration    → classDecl
+//                | funDecl
+//                | varDecl
+//                | importDecl
 //                | statement ;
+// classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )?
+//                  "{" ( method | field )* "}" ;
+// method         → IDENTIFIER ( "(" parameters? ")" )? block ;
+// field          → "var" declarator ( "," declarator )* ";" ;
+// funDecl        → "fun" function ;
+// function       → IDENTIFIER "(" parameters? ")" block ;
+// parameters     → IDENTIFIER ( "," IDENTIFIER )* ( "," "..." IDENTIFIER )?
+//                | "..." IDENTIFIER ;
+// varDecl        → "var" declarator ( "," declarator )* ";"
+//                | "var" "(" IDENTIFIER ( "," IDENTIFIER )* ")" "=" expression ";" ;
+// declarator     → IDENTIFIER ( "=" expression )? ;
+// importDecl     → "import" STRING ( "as" IDENTIFIER )? ";" ;
 // statement      → exprStmt
-//                | printStmt ;
+//                | forStmt
+//                | ifStmt
+//                | printStmt
+//                | eprintStmt
+//                | whileStmt
+//                | doWhileStmt
+//                | breakStmt
+//                | continueStmt
+//                | returnStmt
+//                | switchStmt
+//                | throwStmt
+//                | tryStmt
+//                | block ;
+// forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+//                  expression? ";"
+//                  expression? ")" statement
+//                | "for" "(" "var"? IDENTIFIER "in" expression ")" statement ;
+// breakStmt      → "break" ";" ;
+// continueStmt   → "continue" ";" ;
+// returnStmt     → "return" expression? ";" ;
+// switchStmt     → "switch" "(" expression ")" "{"
+//                  ( "case" expression ":" declaration* )*
+//                  ( "default" ":" declaration* )? "}" ;
+// throwStmt      → "throw" expression ";" ;
+// tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )?
+//                  ( "finally" block )? ;
+// block          → "{" declaration* "}" ;
+// printStmt      → "print" ( expression ( "," expression )* )? ";" ;
+// eprintStmt     → "eprint" expression ";" ;
+// ifStmt         → "if" "(" expression ")" statement
+//                  ( "else" statement )? ;
+// whileStmt      → "while" "(" expression ")" statement ;
+// doWhileStmt    → "do" statement "while" "(" expression ")" ";" ;
 
-// Expression grammar:
-// expression     → equality ;
+// Expression grammar, where the binary operators from coalesce to exponent
+// are parsed by `Parser::binary` from the table in `binary_operator`:
+// expression     → assignment ;
+// assignment     → ( call "." IDENTIFIER | call "[" expression "]" | IDENTIFIER )
+//                  ( "=" | "+=" | "-=" | "*=" | "/=" ) assignment
+//                | conditional ;
+// conditional    → coalesce ( "?" expression ":" conditional )? ;
+// coalesce       → logic_or ( "??" logic_or )* ;
+// logic_or       → logic_and ( ( "or" | "xor" ) logic_and )* ;
+// logic_and      → equality ( "and" equality )* ;
 // equality       → comparison ( ( "!=" | "==" ) comparison )* ;
 // comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
 // term           → factor ( ( "-" | "+" ) factor )* ;
 // factor         → unary ( ( "/" | "*" ) unary )* ;
-// unary          → ( "!" | "-" ) unary
-//                | primary ;
-// primary        → NUMBER | STRING | "true" | "false" | "nil"
-//                | "(" expression ")" ;
+// unary          → ( "!" | "not" | "-" ) unary
+//                | ( "++" | "--" ) call
+//                | exponent ;
+// exponent       → call ( "**" unary )? ;
+// call           → primary ( "(" arguments? ")" | "." IDENTIFIER
+//                  | "[" expression "]" )* ( "++" | "--" )? ;
+// arguments      → expression ( "," expression )* ;
+// primary        → NUMBER | STRING | "true" | "false" | "nil" | "this"
+//                | IDENTIFIER | "super" "." IDENTIFIER
+//                | "(" expression ")"
+//                | "(" expression "," ( expression ( "," expression )* ","? )? ")"
+//                | "[" ( expression ( "," expression )* )? "]"
+//                | "{" ( entry ( "," entry )* ","? )? "}"
+//                | ifExpr ;
+// entry          → expression ":" expression ;
+// ifExpr         → "if" "(" expression ")" expression "else" expression ;
 
-pub struct Parser<'a> {
+/// Parses tokens from `I`, which may be a `Vec`'s iterator or a lazy
+/// `Scanner`.
+pub struct Parser<'a, I = std::vec::IntoIter<Token>> {
+    /// The tokens pulled from `source` so far.
     pub tokens: Vec<Token>,
     pub current: usize,
+    source: I,
     pub reporter: &'a mut dyn Reporter,
+    /// Number of loops enclosing the statement being parsed, so that
+    /// `break` and `continue` outside of any loop are rejected before the program runs.
+    loop_depth: usize,
+    /// Number of function bodies enclosing the statement being parsed, so
+    /// that `return` at the top level is rejected the same way.
+    function_depth: usize,
+    /// The errors found so far, each already reported.
+    errors: Vec<ParseError>,
+    /// How deeply the expression being parsed is nested, and how deeply it
+    /// may be before it is rejected rather than risk overflowing the stack.
+    depth: usize,
+    max_depth: usize,
 }
 
-impl<'a> Parser<'a> {
-    pub fn new(tokens: Vec<Token>, reporter: &'a mut dyn Reporter) -> Self {
-        Self {
-            tokens,
+/// How tightly a binary operator binds, from loosest to tightest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Coalesce,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Exponent,
+}
+
+impl Precedence {
+    /// The next tighter level, for the right operand of a left-associative
+    /// operator.
+    fn tighter(self) -> Self {
+        match self {
+            Self::Coalesce => Self::Or,
+            Self::Or => Self::And,
+            Self::And => Self::Equality,
+            Self::Equality => Self::Comparison,
+            Self::Comparison => Self::Term,
+            Self::Term => Self::Factor,
+            Self::Factor | Self::Exponent => Self::Exponent,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+struct BinaryOperator {
+    precedence: Precedence,
+    associativity: Associativity,
+    /// Makes the node for `left operator right`.
+    build: fn(Expr, Token, Expr) -> Expr,
+}
+
+fn binary_node(left: Expr, operator: Token, right: Expr) -> Expr {
+    Expr::Binary(Box::new(BinaryExpr {
+        left,
+        operator,
+        right,
+    }))
+}
+
+fn logical_node(left: Expr, operator: Token, right: Expr) -> Expr {
+    Expr::Logical(Box::new(LogicalExpr {
+        left,
+        operator,
+        right,
+    }))
+}
+
+/// The operator table for `Parser::binary`, following the expression
+/// grammar above: a new binary operator needs only a row here.
+fn binary_operator(token_type: &TokenType) -> Option<BinaryOperator> {
+    use Associativity::{Left, Right};
+    let (precedence, associativity, build): (_, _, fn(Expr, Token, Expr) -> Expr) = match token_type
+    {
+        TokenType::QuestionQuestion => (Precedence::Coalesce, Left, logical_node),
+        TokenType::Or | TokenType::Xor => (Precedence::Or, Left, logical_node),
+        TokenType::And => (Precedence::And, Left, logical_node),
+        TokenType::BangEqual | TokenType::EqualEqual => (Precedence::Equality, Left, binary_node),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+            (Precedence::Comparison, Left, binary_node)
+        }
+        TokenType::Minus | TokenType::Plus => (Precedence::Term, Left, binary_node),
+        TokenType::Slash | TokenType::Star => (Precedence::Factor, Left, binary_node),
+        TokenType::StarStar => (Precedence::Exponent, Right, binary_node),
+        _ => return None,
+    };
+    Some(BinaryOperator {
+        precedence,
+        associativity,
+        build,
+    })
+}
+
+/// The default for `Parser::set_max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// The most arguments a call may pass, and the most parameters a function
+/// may declare besides a rest parameter. Past this the parser reports one
+/// error and carries on.
+pub const MAX_ARGUMENTS: usize = 255;
+
+impl<'a, I: Iterator<Item = Token>> Parser<'a, I> {
+    /// A parser over `tokens`, which must end with an EndOfFile token.
+    /// Tokens are pulled only as parsing reaches them.
+    pub fn new(tokens: impl IntoIterator<IntoIter = I>, reporter: &'a mut dyn Reporter) -> Self {
+        let mut parser = Self {
+            tokens: Vec::new(),
             current: 0,
+            source: tokens.into_iter(),
             reporter,
+            loop_depth: 0,
+            function_depth: 0,
+            errors: Vec::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+        };
+        parser.pull(0);
+        parser
+    }
+
+    /// Start over on new `tokens`, keeping the buffer for them.
+    pub fn reset(&mut self, tokens: impl IntoIterator<IntoIter = I>) {
+        self.tokens.clear();
+        self.current = 0;
+        self.source = tokens.into_iter();
+        self.loop_depth = 0;
+        self.function_depth = 0;
+        self.errors.clear();
+        self.depth = 0;
+        self.pull(0);
+    }
+
+    /// Limit how deeply expressions may nest, counting parentheses,
+    /// brackets, unary operators, chained assignments or conditionals, and
+    /// the operators of a chain such as `1 + 2 + 3`.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Run `parse` one level deeper, failing if that is too deep.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let depth = self.depth;
+        self.deeper()?;
+        let result = parse(self);
+        self.depth = depth;
+        result
+    }
+
+    /// Go one level deeper, failing at the next token if that is too deep.
+    fn deeper(&mut self) -> Result<(), ParseError> {
+        if self.depth >= self.max_depth {
+            let token = self.peek().clone();
+            return Err(ParseError::new(
+                token,
+                "Expression too deeply nested.".to_string(),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Pull tokens from the source until the one at `index` is available,
+    /// if there are that many. Comments are dropped on the way.
+    fn pull(&mut self, index: usize) {
+        while self.tokens.len() <= index {
+            match self.source.next() {
+                Some(token) if token.token_type.is_trivia() => {}
+                Some(token) => self.tokens.push(token),
+                None => break,
+            }
         }
     }
 
@@ -66,6 +357,7 @@ impl<'a> Parser<'a> {
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
+            self.pull(self.current);
         }
         self.previous()
     }
@@ -79,151 +371,928 @@ impl<'a> Parser<'a> {
     }
 
     fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
-        if self.check(&token_type) {
+        self.consume_one_of(&[token_type], message)
+    }
+
+    /// Consume a token of any of `token_types`, or fail with `message`,
+    /// which should name them all.
+    fn consume_one_of(
+        &mut self,
+        token_types: &[TokenType],
+        message: &str,
+    ) -> Result<&Token, ParseError> {
+        if token_types.iter().any(|token_type| self.check(token_type)) {
             Ok(self.advance())
         } else {
             let token = self.peek().clone();
-            Err(ParseError::new(token, message.to_string()))
+            Err(ParseError::expected(token, token_types, message))
         }
     }
 
-    fn declaration(&mut self) -> Stmt {
-        let nil_stub_stmt = Stmt::Expr(Box::new(ExprStmt {
-            expr: Expr::Literal(Box::new(LiteralExpr {
-                value: LiteralValue::Nil,
-            })),
-        }));
-        if self.match_token_types(&[TokenType::Var]) {
-            match self.var_declaration() {
-                Ok(stmt) => stmt,
-                Err(error) => {
-                    self.synchronize();
-                    self.reporter.parser_error(&error.token, &error.message);
-                    nil_stub_stmt
-                }
+    /// Report and record `error`. Called directly, this lets parsing carry
+    /// on where the parser is not confused by the error.
+    fn error(&mut self, error: ParseError) {
+        self.reporter.parser_error(&error.token, &error.message);
+        self.errors.push(error);
+    }
+
+    /// The next declaration, or `None` if it has an error, which is reported
+    /// and recorded before skipping to where the next one should start.
+    fn declaration(&mut self) -> Option<Stmt> {
+        let result = if self.match_token_types(&[TokenType::Class]) {
+            self.class_declaration()
+        } else if self.match_token_types(&[TokenType::Fun]) {
+            self.function("function")
+                .map(|function| Stmt::Function(Box::new(function)))
+        } else if self.match_token_types(&[TokenType::Var]) {
+            self.var_declaration()
+        } else if self.match_token_types(&[TokenType::Import]) {
+            self.import_declaration()
+        } else {
+            self.statement()
+        };
+        match result {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.synchronize();
+                self.error(error);
+                None
             }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "Expected class name")?
+            .clone();
+        let superclass = if self.match_token_types(&[TokenType::Less]) {
+            let name = self
+                .consume(TokenType::Identifier, "Expected superclass name")?
+                .clone();
+            Some(VarExpr {
+                id: ExprId::fresh(),
+                name,
+            })
         } else {
-            match self.statement() {
-                Ok(stmt) => stmt,
-                Err(error) => {
-                    self.synchronize();
-                    self.reporter.parser_error(&error.token, &error.message);
-                    nil_stub_stmt
-                }
+            None
+        };
+        self.consume(TokenType::LeftBrace, "Expected '{' before class body")?;
+        let mut methods = Vec::new();
+        let mut field_assignments = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_token_types(&[TokenType::Var]) {
+                self.field_declaration(&mut field_assignments)?;
+            } else if self.check(&TokenType::Identifier) {
+                methods.push(self.function("method")?);
+            } else {
+                let token = self.peek().clone();
+                return Err(ParseError::expected(
+                    token,
+                    &[TokenType::Var, TokenType::Identifier, TokenType::RightBrace],
+                    "Expected method or field declaration in class body",
+                ));
             }
         }
+        self.consume(TokenType::RightBrace, "Expected '}' after class body")?;
+        let field_initializer = (!field_assignments.is_empty()).then(|| FunctionStmt {
+            name: Token {
+                token_type: TokenType::Identifier,
+                lexeme: "fields".to_string(),
+                line: name.line,
+                column: name.column,
+                span: name.span.clone(),
+                symbol: Some(Symbol::FIELDS),
+            },
+            params: Vec::new(),
+            rest: None,
+            body: Rc::new(field_assignments),
+            is_getter: false,
+        });
+        Ok(Stmt::Class(Box::new(ClassStmt {
+            name,
+            superclass,
+            methods,
+            field_initializer,
+        })))
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+    /// Parse the rest of `var a = 1, b;` in a class body into statements
+    /// assigning each default to `this`.
+    fn field_declaration(&mut self, assignments: &mut Vec<Stmt>) -> Result<(), ParseError> {
+        loop {
+            let name = self
+                .consume(TokenType::Identifier, "Expected field name")?
+                .clone();
+            let value = if self.match_token_types(&[TokenType::Equal]) {
+                self.expression()?
+            } else {
+                Expr::Literal(Box::new(LiteralExpr {
+                    value: LiteralValue::Nil,
+                }))
+            };
+            let this = Expr::This(Box::new(ThisExpr {
+                id: ExprId::fresh(),
+                keyword: Token {
+                    token_type: TokenType::This,
+                    lexeme: "this".to_string(),
+                    line: name.line,
+                    column: name.column,
+                    span: name.span.clone(),
+                    symbol: Some(Symbol::THIS),
+                },
+            }));
+            assignments.push(Stmt::Expr(Box::new(ExprStmt {
+                expr: Expr::Set(Box::new(SetExpr {
+                    object: this,
+                    name,
+                    operator: None,
+                    value,
+                })),
+            })));
+            if !self.match_token_types(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after field declaration")?;
+        Ok(())
+    }
+
+    fn function(&mut self, kind: &str) -> Result<FunctionStmt, ParseError> {
         let name = self
-            .consume(TokenType::Identifier, "Expect variable name.")?
+            .consume(TokenType::Identifier, &format!("Expected {} name", kind))?
             .clone();
-        let initializer = if self.match_token_types(&[TokenType::Equal]) {
-            Some(self.expression()?)
+        // Only methods can leave out the parameter list, which makes them
+        // getters.
+        let is_getter = kind == "method" && !self.check(&TokenType::LeftParen);
+        let mut params = Vec::new();
+        let mut rest = None;
+        if !is_getter {
+            self.parameters(kind, &mut params, &mut rest)?;
+        }
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expected '{{' before {} body", kind),
+        )?;
+        // A loop around the declaration does not extend into the body.
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        Ok(FunctionStmt {
+            name,
+            params,
+            rest,
+            body: Rc::new(body?),
+            is_getter,
+        })
+    }
+
+    fn parameters(
+        &mut self,
+        kind: &str,
+        params: &mut Vec<Token>,
+        rest: &mut Option<Token>,
+    ) -> Result<(), ParseError> {
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expected '(' after {} name", kind),
+        )?;
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if params.len() == MAX_ARGUMENTS {
+                    let token = self.peek().clone();
+                    let message = format!("Can't have more than {} parameters.", MAX_ARGUMENTS);
+                    self.error(ParseError::new(token, message));
+                }
+                if self.match_token_types(&[TokenType::DotDotDot]) {
+                    *rest = Some(
+                        self.consume(TokenType::Identifier, "Expected parameter name after '...'")?
+                            .clone(),
+                    );
+                    if self.check(&TokenType::Comma) {
+                        return Err(ParseError::new(
+                            self.peek().clone(),
+                            "Variadic parameter must be the last one.".to_string(),
+                        ));
+                    }
+                    break;
+                }
+                params.push(
+                    self.consume(TokenType::Identifier, "Expected parameter name")?
+                        .clone(),
+                );
+                if !self.match_token_types(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+        Ok(())
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token_types(&[TokenType::LeftParen]) {
+            return self.destructuring_declaration();
+        }
+        let mut declarators = Vec::new();
+        loop {
+            let name = self
+                .consume(TokenType::Identifier, "Expected variable name")?
+                .clone();
+            let initializer = if self.match_token_types(&[TokenType::Equal]) {
+                let value = self.expression();
+                Some(value.or_else(|error| self.recover_item(error, &[TokenType::Semicolon]))?)
+            } else {
+                None
+            };
+            declarators.push((name, initializer));
+            if self.check(&TokenType::Identifier) {
+                // A forgotten comma: report it and read on as if it were there.
+                let token = self.peek().clone();
+                self.error(ParseError::expected(
+                    token,
+                    &[TokenType::Comma, TokenType::Semicolon],
+                    "Expected ',' or ';' after variable declaration",
+                ));
+                continue;
+            }
+            if !self.match_token_types(&[TokenType::Comma]) {
+                break;
+            }
+            if !self.check(&TokenType::Identifier) {
+                return Err(ParseError::expected(
+                    self.peek().clone(),
+                    &[TokenType::Identifier],
+                    "Expected variable name after ','",
+                ));
+            }
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expected ';' after variable declaration",
+        )?;
+        Ok(Stmt::Var(Box::new(VarStmt { declarators })))
+    }
+
+    fn import_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let TokenType::String(path) = self.peek().token_type.clone() else {
+            let token = self.peek().clone();
+            return Err(ParseError::expected(
+                token,
+                &[],
+                "Expected module path after 'import'",
+            ));
+        };
+        self.advance();
+        let alias = if self.match_token_types(&[TokenType::As]) {
+            Some(
+                self.consume(TokenType::Identifier, "Expected module name after 'as'")?
+                    .clone(),
+            )
         } else {
             None
         };
+        self.consume(TokenType::Semicolon, "Expected ';' after import")?;
+        Ok(Stmt::Import(Box::new(ImportStmt {
+            keyword,
+            path,
+            alias,
+        })))
+    }
+
+    fn destructuring_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let mut names = Vec::new();
+        loop {
+            names.push(
+                self.consume(TokenType::Identifier, "Expected variable name")?
+                    .clone(),
+            );
+            if !self.match_token_types(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after variable names")?;
+        self.consume(TokenType::Equal, "Expected '=' after destructuring pattern")?;
+        let initializer = self.expression()?;
         self.consume(
             TokenType::Semicolon,
-            "Expect ';' after variable declaration.",
+            "Expected ';' after variable declaration",
         )?;
-        Ok(Stmt::Var(Box::new(VarStmt { name, initializer })))
+        Ok(Stmt::Destructure(Box::new(DestructureStmt {
+            names,
+            initializer,
+        })))
     }
 
     fn statement(&mut self) -> Result<Stmt, ParseError> {
-        if self.match_token_types(&[TokenType::Print]) {
+        if self.match_token_types(&[TokenType::For]) {
+            self.for_statement()
+        } else if self.match_token_types(&[TokenType::If]) {
+            self.if_statement()
+        } else if self.match_token_types(&[TokenType::Print]) {
             self.print_statement()
+        } else if self.match_token_types(&[TokenType::Eprint]) {
+            self.eprint_statement()
+        } else if self.match_token_types(&[TokenType::While]) {
+            self.while_statement()
+        } else if self.match_token_types(&[TokenType::Do]) {
+            self.do_while_statement()
+        } else if self.match_token_types(&[TokenType::Break]) {
+            self.break_statement()
+        } else if self.match_token_types(&[TokenType::Continue]) {
+            self.continue_statement()
+        } else if self.match_token_types(&[TokenType::Return]) {
+            self.return_statement()
+        } else if self.match_token_types(&[TokenType::Switch]) {
+            self.switch_statement()
+        } else if self.match_token_types(&[TokenType::Throw]) {
+            self.throw_statement()
+        } else if self.match_token_types(&[TokenType::Try]) {
+            self.try_statement()
+        } else if self.match_token_types(&[TokenType::LeftBrace]) {
+            Ok(Stmt::Block(Box::new(BlockStmt {
+                statements: self.block()?,
+            })))
         } else {
             self.expression_statement()
         }
     }
 
+    /// An `else` binds to the nearest `if`, since the then branch is parsed
+    /// first and greedily takes it. A `;` right after the condition is an
+    /// empty then branch, which is legal but warned about.
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'if'")?;
+        let condition = self.expression()?;
+        self.warn_assignment_in_condition(&condition);
+        self.consume(TokenType::RightParen, "Expected ')' after if condition")?;
+        let then_branch = if self.match_token_types(&[TokenType::Semicolon]) {
+            let semicolon = self.previous().clone();
+            self.reporter.parser_warning(
+                &semicolon,
+                "Empty body after 'if'; the ';' ends the statement.",
+            );
+            Stmt::Block(Box::new(BlockStmt {
+                statements: Vec::new(),
+            }))
+        } else {
+            self.statement()?
+        };
+        let else_branch = if self.match_token_types(&[TokenType::Else]) {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+        Ok(Stmt::If(Box::new(IfStmt {
+            condition,
+            then_branch,
+            else_branch,
+        })))
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            self.push_declaration(&mut statements);
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after block")?;
+        Ok(statements)
+    }
+
+    /// Parse the next declaration of a block onto `statements`, warning at
+    /// it if an earlier statement always jumps out. Only the first
+    /// statement of such a dead run is flagged.
+    fn push_declaration(&mut self, statements: &mut Vec<Stmt>) {
+        let jumps = |stmt: &Stmt| {
+            matches!(
+                stmt,
+                Stmt::Return(_) | Stmt::Break(_) | Stmt::Continue(_) | Stmt::Throw(_)
+            )
+        };
+        if let Some((last, earlier)) = statements.split_last() {
+            if jumps(last) && !earlier.iter().any(jumps) {
+                let token = self.peek().clone();
+                self.reporter.parser_warning(&token, "Unreachable code.");
+            }
+        }
+        statements.extend(self.declaration());
+    }
+
+    fn switch_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'switch'")?;
+        let subject = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after switch value")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' before switch cases")?;
+        let mut cases = Vec::new();
+        let mut literals: Vec<Value> = Vec::new();
+        while self.match_token_types(&[TokenType::Case]) {
+            let token = self.peek().clone();
+            let value = self.expression()?;
+            // A repeated literal can never match, as the earlier case wins.
+            if let Expr::Literal(literal) = &value {
+                let literal = Value::from(literal.value.clone());
+                if literals.iter().any(|seen| deep_eq(seen, &literal)) {
+                    self.reporter
+                        .parser_warning(&token, "Duplicate case value; it can never match.");
+                }
+                literals.push(literal);
+            }
+            self.consume(TokenType::Colon, "Expected ':' after case value")?;
+            cases.push((value, self.case_body()));
+        }
+        let default = if self.match_token_types(&[TokenType::Default]) {
+            self.consume(TokenType::Colon, "Expected ':' after 'default'")?;
+            Some(self.case_body())
+        } else {
+            None
+        };
+        if self.check(&TokenType::Case) || self.check(&TokenType::Default) {
+            return Err(ParseError::expected(
+                self.peek().clone(),
+                &[TokenType::RightBrace],
+                "Expected '}' after the 'default' case, which must be the last",
+            ));
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after switch cases")?;
+        Ok(Stmt::Switch(Box::new(SwitchStmt {
+            subject,
+            cases,
+            default,
+        })))
+    }
+
+    /// The statements of a case, which run up to the next case or the end of
+    /// the switch.
+    fn case_body(&mut self) -> Vec<Stmt> {
+        let mut statements = Vec::new();
+        while !self.check(&TokenType::Case)
+            && !self.check(&TokenType::Default)
+            && !self.check(&TokenType::RightBrace)
+            && !self.is_at_end()
+        {
+            self.push_declaration(&mut statements);
+        }
+        statements
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expected ';' after thrown value")?;
+        Ok(Stmt::Throw(Box::new(ThrowStmt { keyword, value })))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftBrace, "Expected '{' after 'try'")?;
+        let body = self.block()?;
+        let catch = if self.match_token_types(&[TokenType::Catch]) {
+            self.consume(TokenType::LeftParen, "Expected '(' after 'catch'")?;
+            let name = self
+                .consume(TokenType::Identifier, "Expected catch variable name")?
+                .clone();
+            self.consume(TokenType::RightParen, "Expected ')' after catch variable")?;
+            self.consume(TokenType::LeftBrace, "Expected '{' before catch body")?;
+            Some((name, self.block()?))
+        } else {
+            None
+        };
+        let finally = if self.match_token_types(&[TokenType::Finally]) {
+            self.consume(TokenType::LeftBrace, "Expected '{' after 'finally'")?;
+            Some(self.block()?)
+        } else {
+            None
+        };
+        if catch.is_none() && finally.is_none() {
+            return Err(ParseError::expected(
+                self.peek().clone(),
+                &[TokenType::Catch, TokenType::Finally],
+                "Expected 'catch' or 'finally' after try block",
+            ));
+        }
+        Ok(Stmt::Try(Box::new(TryStmt {
+            body,
+            catch,
+            finally,
+        })))
+    }
+
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let mut expressions = Vec::new();
+        if !self.check(&TokenType::Semicolon) {
+            expressions.push(self.expression()?);
+            while self.match_token_types(&[TokenType::Comma]) {
+                if self.check(&TokenType::Semicolon) {
+                    return Err(ParseError::expected(
+                        self.peek().clone(),
+                        &[],
+                        "Expected expression after ','",
+                    ));
+                }
+                expressions.push(self.expression()?);
+            }
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after value")?;
+        Ok(Stmt::Print(Box::new(PrintStmt { expressions })))
+    }
+
+    fn eprint_statement(&mut self) -> Result<Stmt, ParseError> {
         let value = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print(Box::new(PrintStmt { expr: value })))
+        self.consume(TokenType::Semicolon, "Expected ';' after value")?;
+        Ok(Stmt::Eprint(Box::new(EprintStmt { expr: value })))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'while'")?;
+        let condition = self.expression()?;
+        self.warn_assignment_in_condition(&condition);
+        self.consume(TokenType::RightParen, "Expected ')' after condition")?;
+        let body = self.loop_body()?;
+        Ok(Stmt::While(Box::new(WhileStmt {
+            condition,
+            body,
+            increment: None,
+        })))
+    }
+
+    fn do_while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let body = self.loop_body()?;
+        self.consume(TokenType::While, "Expected 'while' after do-while body")?;
+        self.consume(TokenType::LeftParen, "Expected '(' after 'while'")?;
+        let condition = self.expression()?;
+        self.warn_assignment_in_condition(&condition);
+        self.consume(TokenType::RightParen, "Expected ')' after condition")?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expected ';' after do-while condition",
+        )?;
+        Ok(Stmt::DoWhile(Box::new(DoWhileStmt { body, condition })))
+    }
+
+    /// Desugar a C-style for loop into an initializer followed by a while
+    /// loop that runs the increment after each pass through the body.
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'for'")?;
+        if self.is_for_in() {
+            return self.for_in_statement();
+        }
+        let initializer = if self.match_token_types(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token_types(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+        let condition = if self.check(&TokenType::Semicolon) {
+            Expr::Literal(Box::new(LiteralExpr {
+                value: LiteralValue::Bool(true),
+            }))
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after loop condition")?;
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expected ')' after for clauses")?;
+        let body = self.loop_body()?;
+        let mut stmt = Stmt::While(Box::new(WhileStmt {
+            condition,
+            body,
+            increment,
+        }));
+        if let Some(initializer) = initializer {
+            stmt = Stmt::Block(Box::new(BlockStmt {
+                statements: vec![initializer, stmt],
+            }));
+        }
+        Ok(stmt)
+    }
+
+    /// Whether the clauses ahead are `var name in`, or just `name in`, which
+    /// declares the loop variable all the same.
+    fn is_for_in(&mut self) -> bool {
+        self.pull(self.current + 2);
+        let ahead = |distance: usize| {
+            self.tokens
+                .get(self.current + distance)
+                .map(|t| &t.token_type)
+        };
+        let skip = usize::from(ahead(0) == Some(&TokenType::Var));
+        ahead(skip) == Some(&TokenType::Identifier) && ahead(skip + 1) == Some(&TokenType::In)
+    }
+
+    fn for_in_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.match_token_types(&[TokenType::Var]);
+        let variable = self.advance().clone();
+        self.advance();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after for clauses")?;
+        let body = self.loop_body()?;
+        Ok(Stmt::ForIn(Box::new(ForInStmt {
+            variable,
+            iterable,
+            body,
+        })))
+    }
+
+    fn loop_body(&mut self) -> Result<Stmt, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn break_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            return Err(ParseError::new(keyword, "'break' outside loop".to_string()));
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after 'break'")?;
+        Ok(Stmt::Break(Box::new(BreakStmt { keyword })))
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            return Err(ParseError::new(
+                keyword,
+                "'continue' outside loop".to_string(),
+            ));
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after 'continue'")?;
+        Ok(Stmt::Continue(Box::new(ContinueStmt { keyword })))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.function_depth == 0 {
+            return Err(ParseError::new(
+                keyword,
+                "Can't return from top-level code.".to_string(),
+            ));
+        }
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after return value")?;
+        Ok(Stmt::Return(Box::new(ReturnStmt { keyword, value })))
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.peek().clone();
         let expr = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        if let Expr::Literal(literal) = &expr {
+            if let LiteralValue::String(_) = literal.value {
+                self.reporter
+                    .parser_warning(&start, "String literal statement has no effect.");
+            }
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after value")?;
         Ok(Stmt::Expr(Box::new(ExprStmt { expr })))
     }
 
     fn expression(&mut self) -> Result<Expr, ParseError> {
-        self.equality()
+        self.nested(Self::assignment)
     }
 
-    fn equality(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.comparison()?;
-        while self.match_token_types(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator = self.previous().clone();
-            let right = self.comparison()?;
-            expr = Expr::Binary(Box::new(BinaryExpr {
-                left: expr,
-                operator,
-                right,
-            }))
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.conditional()?;
+        if self.match_token_types(&[
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            let equals = self.previous().clone();
+            let value = self.nested(Self::assignment)?;
+            let operator = Self::compound_operator(&equals);
+            match expr {
+                Expr::Variable(variable) => {
+                    // Reading a variable has no side effects, so `a += b`
+                    // can simply become `a = a + b`.
+                    let value = match operator {
+                        Some(operator) => Expr::Binary(Box::new(BinaryExpr {
+                            left: Expr::Variable(variable.clone()),
+                            operator,
+                            right: value,
+                        })),
+                        None => value,
+                    };
+                    return Ok(Expr::Assign(Box::new(AssignExpr {
+                        id: ExprId::fresh(),
+                        name: variable.name,
+                        value,
+                    })));
+                }
+                Expr::Get(get) => {
+                    return Ok(Expr::Set(Box::new(SetExpr {
+                        object: get.object,
+                        name: get.name,
+                        operator,
+                        value,
+                    })))
+                }
+                Expr::Index(index) => {
+                    return Ok(Expr::IndexSet(Box::new(IndexSetExpr {
+                        object: index.object,
+                        bracket: index.bracket,
+                        index: index.index,
+                        operator,
+                        value,
+                    })))
+                }
+                // Report without unwinding: the parser is not confused, so
+                // there is no need to synchronize.
+                _ => self.error(ParseError::new(
+                    equals,
+                    "Invalid assignment target.".to_string(),
+                )),
+            }
         }
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.term()?;
+    /// The binary operator a compound assignment applies, or `None` for a
+    /// plain `=`.
+    fn compound_operator(equals: &Token) -> Option<Token> {
+        let (token_type, lexeme) = match equals.token_type {
+            TokenType::PlusEqual => (TokenType::Plus, "+"),
+            TokenType::MinusEqual => (TokenType::Minus, "-"),
+            TokenType::StarEqual => (TokenType::Star, "*"),
+            TokenType::SlashEqual => (TokenType::Slash, "/"),
+            _ => return None,
+        };
+        Some(Token {
+            token_type,
+            lexeme: lexeme.to_string(),
+            line: equals.line,
+            column: equals.column,
+            span: equals.span.start..equals.span.start + 1,
+            symbol: None,
+        })
+    }
 
-        while self.match_token_types(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let operator = self.previous().clone();
-            let right = self.term()?;
-            expr = Expr::Binary(Box::new(BinaryExpr {
-                left: expr,
-                operator,
-                right,
-            }))
+    fn conditional(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.binary(Precedence::Coalesce)?;
+        if !self.match_token_types(&[TokenType::Question]) {
+            return Ok(condition);
         }
-        Ok(expr)
+        let question = self.previous().clone();
+        let then_branch = self.expression()?;
+        self.consume(
+            TokenType::Colon,
+            &format!("Expected ':' to complete the '?' on line {}", question.line),
+        )?;
+        let else_branch = self.nested(Self::conditional)?;
+        Ok(Expr::Conditional(Box::new(ConditionalExpr {
+            condition,
+            then_branch,
+            else_branch,
+        })))
     }
 
-    fn term(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.factor()?;
-        while self.match_token_types(&[TokenType::Minus, TokenType::Plus]) {
-            let operator = self.previous().clone();
-            let right = self.factor()?;
-            expr = Expr::Binary(Box::new(BinaryExpr {
-                left: expr,
-                operator,
-                right,
-            }))
+    /// The binary operators from `coalesce` to `exponent` in the grammar,
+    /// parsed by precedence climbing: `left` is followed by as many
+    /// operators binding at least as tightly as `min` as there are.
+    fn binary(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let result = self.operator_chain(min);
+        self.depth = depth;
+        result
+    }
+
+    /// `binary` without putting the nesting depth back. Each operator puts
+    /// everything before it one level deeper, and evaluating the tree
+    /// recurses through those levels as it does through parentheses, so a
+    /// long chain is limited like deep nesting rather than left to overflow
+    /// the stack when it runs.
+    fn operator_chain(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        let mut left = self.unary()?;
+        while let Some(operator) = binary_operator(&self.peek().token_type) {
+            if operator.precedence < min {
+                break;
+            }
+            self.deeper()?;
+            let token = self.advance().clone();
+            let right = self.right_operand(&operator)?;
+            if matches!(
+                token.token_type,
+                TokenType::And | TokenType::Or | TokenType::Xor
+            ) {
+                self.warn_contradicting_nil_checks(&left, &right);
+            }
+            left = (operator.build)(left, token, right);
         }
-        Ok(expr)
+        Ok(left)
     }
 
-    fn factor(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.unary()?;
-        while self.match_token_types(&[TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous().clone();
-            let right = self.unary()?;
-            expr = Expr::Binary(Box::new(BinaryExpr {
-                left: expr,
-                operator,
-                right,
-            }));
+    /// The operand to the right of `operator`, which takes in further
+    /// operators of the same precedence only if it is right-associative.
+    fn right_operand(&mut self, operator: &BinaryOperator) -> Result<Expr, ParseError> {
+        match operator.associativity {
+            Associativity::Left => self.binary(operator.precedence.tighter()),
+            Associativity::Right => self.nested(|parser| parser.binary(operator.precedence)),
         }
-        Ok(expr)
     }
 
+    /// Binds looser than `**` on its right, so `-2 ** 2` is -4.
     fn unary(&mut self) -> Result<Expr, ParseError> {
-        if self.match_token_types(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_token_types(&[TokenType::Bang, TokenType::Not, TokenType::Minus]) {
             let operator = self.previous().clone();
-            let right = self.unary()?;
+            let right = self.nested(|parser| parser.binary(Precedence::Exponent))?;
             Ok(Expr::Unary(Box::new(UnaryExpr { operator, right })))
+        } else if self.match_token_types(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            let target = self.call()?;
+            Ok(self.increment(target, operator, true))
         } else {
-            self.primary()
+            self.call()
+        }
+    }
+
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_token_types(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token_types(&[TokenType::Dot]) {
+                let name = self
+                    .consume(TokenType::Identifier, "Expected property name after '.'")?
+                    .clone();
+                expr = Expr::Get(Box::new(GetExpr { object: expr, name }));
+            } else if self.match_token_types(&[TokenType::LeftBracket]) {
+                let bracket = self.previous().clone();
+                let index = self.expression()?;
+                self.consume(TokenType::RightBracket, "Expected ']' after index")?;
+                expr = Expr::Index(Box::new(IndexExpr {
+                    object: expr,
+                    bracket,
+                    index,
+                }));
+            } else {
+                break;
+            }
+        }
+        if self.match_token_types(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            expr = self.increment(expr, operator, false);
+        }
+        Ok(expr)
+    }
+
+    /// `++` or `--` applied to `target`, which must be a variable.
+    fn increment(&mut self, target: Expr, operator: Token, prefix: bool) -> Expr {
+        match target {
+            Expr::Variable(variable) => Expr::Increment(Box::new(IncrementExpr {
+                id: ExprId::fresh(),
+                name: variable.name,
+                operator,
+                prefix,
+            })),
+            // Reported without unwinding, as for an invalid assignment.
+            _ => {
+                self.error(ParseError::new(
+                    operator,
+                    "Invalid increment target.".to_string(),
+                ));
+                target
+            }
+        }
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let mut arguments = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() == MAX_ARGUMENTS {
+                    let token = self.peek().clone();
+                    let message = format!("Can't have more than {} arguments.", MAX_ARGUMENTS);
+                    self.error(ParseError::new(token, message));
+                }
+                let argument = self.expression();
+                arguments.push(
+                    argument.or_else(|error| self.recover_item(error, &[TokenType::RightParen]))?,
+                );
+                if !self.match_token_types(&[TokenType::Comma]) {
+                    break;
+                }
+            }
         }
+        let paren = self
+            .consume(TokenType::RightParen, "Expected ')' after arguments")?
+            .clone();
+        Ok(Expr::Call(Box::new(CallExpr {
+            callee,
+            paren,
+            arguments,
+        })))
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
@@ -246,6 +1315,13 @@ impl<'a> Parser<'a> {
                     value: LiteralValue::Nil,
                 })))
             }
+            TokenType::Int(value) => {
+                let v = *value;
+                self.advance();
+                Ok(Expr::Literal(Box::new(LiteralExpr {
+                    value: LiteralValue::Int(v),
+                })))
+            }
             TokenType::Number(value) => {
                 let v = *value; // copy to make borrow checker happy when calling advance below.
                 self.advance();
@@ -261,59 +1337,1207 @@ impl<'a> Parser<'a> {
                 })))
             }
             TokenType::LeftParen => {
-                self.advance();
+                let paren = self.advance().clone();
                 let expr = self.expression()?;
-                let token = self.advance();
-                match token.token_type {
-                    TokenType::RightParen => Ok(Expr::Grouping(Box::new(GroupingExpr { expr }))),
-                    _ => Err(ParseError::new(
-                        self.peek().clone(),
-                        "Parsing error: expecting ')'".to_string(),
-                    )),
+                // A comma makes a tuple, so `(1,)` has one element while
+                // `(1)` is just a parenthesized 1.
+                if self.match_token_types(&[TokenType::Comma]) {
+                    let mut elements = vec![expr];
+                    while !self.check(&TokenType::RightParen) {
+                        elements.push(self.expression()?);
+                        if !self.match_token_types(&[TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                    self.consume(TokenType::RightParen, "Expected ')' after tuple elements")?;
+                    return Ok(Expr::Tuple(Box::new(TupleExpr { elements })));
+                }
+                if !self.match_token_types(&[TokenType::RightParen]) {
+                    self.unclosed_paren(paren)?;
                 }
+                Ok(Expr::Grouping(Box::new(GroupingExpr { expr })))
             }
             TokenType::Identifier => {
                 let token = self.advance().clone();
-                Ok(Expr::Variable(Box::new(VarExpr { name: token })))
+                Ok(Expr::Variable(Box::new(VarExpr {
+                    id: ExprId::fresh(),
+                    name: token,
+                })))
+            }
+            TokenType::If => {
+                self.advance();
+                self.if_expression()
+            }
+            TokenType::Super => {
+                let keyword = self.advance().clone();
+                self.consume(TokenType::Dot, "Expected '.' after 'super'")?;
+                let method = self
+                    .consume(TokenType::Identifier, "Expected superclass method name")?
+                    .clone();
+                Ok(Expr::Super(Box::new(SuperExpr {
+                    id: ExprId::fresh(),
+                    keyword,
+                    method,
+                })))
+            }
+            TokenType::This => {
+                let keyword = self.advance().clone();
+                Ok(Expr::This(Box::new(ThisExpr {
+                    id: ExprId::fresh(),
+                    keyword,
+                })))
+            }
+            TokenType::LeftBracket => {
+                self.advance();
+                let mut elements = Vec::new();
+                if !self.check(&TokenType::RightBracket) {
+                    loop {
+                        elements.push(self.expression()?);
+                        if !self.match_token_types(&[TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RightBracket, "Expected ']' after list elements")?;
+                Ok(Expr::List(Box::new(ListExpr { elements })))
+            }
+            // Only reached in expression position: a `{` that starts a
+            // statement is a block.
+            TokenType::LeftBrace => self.map_literal(),
+            token_type => match binary_operator(token_type) {
+                Some(operator) if operator.precedence >= Precedence::Equality => {
+                    self.missing_left_operand(&operator)
+                }
+                _ if self.is_at_end() => Err(ParseError::new(
+                    self.peek().clone(),
+                    "Unexpected end of input, expected expression.".to_string(),
+                )),
+                _ => Err(ParseError::expected(
+                    self.peek().clone(),
+                    &[],
+                    "Expected expression",
+                )),
+            },
+        }
+    }
+
+    /// An error production for an equality or tighter binary operator with
+    /// nothing before it, as in `== x`. The right operand is parsed as it
+    /// would be after a left one, and stands in for the whole operation so
+    /// that parsing can go on to find further errors. `-` never gets here,
+    /// as it is also the unary minus.
+    fn missing_left_operand(&mut self, operator: &BinaryOperator) -> Result<Expr, ParseError> {
+        let token = self.advance().clone();
+        let message = format!(
+            "Binary operator '{}' missing left-hand operand.",
+            token.lexeme
+        );
+        self.error(ParseError::new(token, message));
+        self.right_operand(operator)
+    }
+
+    fn map_literal(&mut self) -> Result<Expr, ParseError> {
+        let brace = self.advance().clone();
+        let mut entries = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expected ':' after map key")?;
+            let value = self.expression()?;
+            entries.push((key, value));
+            if !self.match_token_types(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after map entries")?;
+        Ok(Expr::Map(Box::new(MapExpr { brace, entries })))
+    }
+
+    /// Both branches are required since the expression needs a value either
+    /// way. The else branch extends as far to the right as possible, so
+    /// `if (c) 1 else 2 + 3` adds 3 only when `c` is falsey. At the start of
+    /// a statement `if` begins an if statement instead.
+    fn if_expression(&mut self) -> Result<Expr, ParseError> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'if'")?;
+        let condition = self.expression()?;
+        self.warn_assignment_in_condition(&condition);
+        self.consume(TokenType::RightParen, "Expected ')' after if condition")?;
+        let then_branch = self.expression()?;
+        self.consume(TokenType::Else, "Expected 'else' branch in if expression")?;
+        let else_branch = self.expression()?;
+        Ok(Expr::If(Box::new(IfExpr {
+            condition,
+            then_branch,
+            else_branch,
+        })))
+    }
+
+    /// `if (a = b)` is legal but usually meant to be `if (a == b)`. Wrapping
+    /// the assignment in parentheses silences the warning.
+    fn warn_assignment_in_condition(&mut self, condition: &Expr) {
+        if let Expr::Assign(assign) = condition {
+            self.reporter
+                .parser_warning(&assign.name, "Assignment in condition; did you mean '=='?");
+        }
+    }
+
+    /// Warn about `x == nil and x != nil` style conditions, where the two
+    /// operands of a logical operator check the same variable against nil in
+    /// opposite ways.
+    fn warn_contradicting_nil_checks(&mut self, left: &Expr, right: &Expr) {
+        let mut equal_nil = Vec::new();
+        Self::nil_checks(left, &TokenType::EqualEqual, &mut equal_nil);
+        let mut not_equal_nil = Vec::new();
+        Self::nil_checks(right, &TokenType::BangEqual, &mut not_equal_nil);
+        for (name, operator) in not_equal_nil {
+            if equal_nil
+                .iter()
+                .any(|(other, _)| other.lexeme == name.lexeme)
+            {
+                self.reporter.parser_warning(
+                    &operator,
+                    &format!(
+                        "'{}' is compared with both '== nil' and '!= nil'.",
+                        name.lexeme
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Collect `variable <operator> nil` comparisons joined by logical
+    /// operators, as (variable, operator) token pairs.
+    fn nil_checks(expr: &Expr, operator: &TokenType, found: &mut Vec<(Token, Token)>) {
+        match expr {
+            Expr::Logical(logical) => {
+                Self::nil_checks(&logical.left, operator, found);
+                Self::nil_checks(&logical.right, operator, found);
+            }
+            Expr::Grouping(grouping) => Self::nil_checks(&grouping.expr, operator, found),
+            Expr::Binary(binary) if &binary.operator.token_type == operator => {
+                match (&binary.left, &binary.right) {
+                    (Expr::Variable(variable), Expr::Literal(literal))
+                    | (Expr::Literal(literal), Expr::Variable(variable))
+                        if literal.value == LiteralValue::Nil =>
+                    {
+                        found.push((variable.name.clone(), binary.operator.clone()));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Parse the whole input as a single expression, with no trailing `;`.
+    /// Errors go to the reporter.
+    pub fn parse_expression(&mut self) -> Option<Expr> {
+        let result = self.expression().and_then(|expr| {
+            if self.is_at_end() {
+                Ok(expr)
+            } else {
+                Err(ParseError::expected(
+                    self.peek().clone(),
+                    &[TokenType::EndOfFile],
+                    "Expected end of expression",
+                ))
+            }
+        });
+        match result {
+            Ok(expr) => Some(expr),
+            Err(error) => {
+                self.reporter.parser_error(&error.token, &error.message);
+                None
             }
-            _ => Err(ParseError::new(
-                self.peek().clone(),
-                "No other literal token types . Not reachable.".to_string(),
-            )),
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    /// Parse the whole program. Parsing carries on past an error to find
+    /// any others, and all of them are returned if there were any.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration())
+            self.push_declaration(&mut statements);
         }
-        statements
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Recover from a group opened at `paren` that has no `)` where one
+    /// should be, reporting at the `(` rather than at whatever is there.
+    /// The next `)` is taken to close it if there is one before the end of
+    /// the statement. Otherwise this fails, so that any groups around this
+    /// one are not reported too.
+    fn unclosed_paren(&mut self, paren: Token) -> Result<(), ParseError> {
+        let message = format!("Unclosed '(' opened at line {}.", paren.line);
+        while !self.is_at_end()
+            && !self.check(&TokenType::Semicolon)
+            && !Self::starts_statement(&self.peek().token_type)
+        {
+            if self.match_token_types(&[TokenType::RightParen]) {
+                self.error(ParseError::new(paren, message));
+                return Ok(());
+            }
+            self.advance();
+        }
+        let mut error = ParseError::new(paren, message);
+        if self.is_at_end() {
+            error.kind = ParseErrorKind::Incomplete;
+        }
+        Err(error)
+    }
+
+    /// Recover from `error` in an item of a comma-separated list that ends
+    /// at one of `ends`, such as an argument, so that mistakes in the items
+    /// after it are reported too. The rest of the item is skipped and an
+    /// `Expr::Error` put in its place. This fails with `error` instead if
+    /// the list does not go on past it within the statement.
+    fn recover_item(&mut self, error: ParseError, ends: &[TokenType]) -> Result<Expr, ParseError> {
+        let mut depth = 0;
+        while !self.is_at_end() && !Self::starts_statement(&self.peek().token_type) {
+            let token_type = &self.peek().token_type;
+            if depth == 0 && (*token_type == TokenType::Comma || ends.contains(token_type)) {
+                let token = error.token.as_ref().clone();
+                self.error(error);
+                return Ok(Expr::Error(Box::new(ErrorExpr { token })));
+            }
+            match token_type {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace
+                    if depth > 0 =>
+                {
+                    depth -= 1
+                }
+                TokenType::RightParen
+                | TokenType::RightBracket
+                | TokenType::RightBrace
+                | TokenType::Semicolon => break,
+                _ => {}
+            }
+            self.advance();
+        }
+        Err(error)
+    }
+
+    /// Whether a statement begins with a `token_type`, which nothing else
+    /// does.
+    fn starts_statement(token_type: &TokenType) -> bool {
+        matches!(
+            token_type,
+            TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::Import
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Do
+                | TokenType::Print
+                | TokenType::Eprint
+                | TokenType::Return
+                | TokenType::Switch
+                | TokenType::Throw
+                | TokenType::Try
+        )
     }
 
     /// March forward until the beginning of the next statement. Used for
-    /// recovering from an parser error.
+    /// recovering from an parser error. The declaration that failed has
+    /// always consumed its first token, so a statement keyword here is
+    /// already the start of the next one.
     fn synchronize(&mut self) {
+        if Self::starts_statement(&self.peek().token_type) {
+            return;
+        }
         self.advance();
         while !self.is_at_end() {
             if self.previous().token_type == TokenType::Semicolon {
                 break;
             }
-
-            if matches!(
-                self.peek().token_type,
-                TokenType::Class
-                    | TokenType::Fun
-                    | TokenType::Var
-                    | TokenType::For
-                    | TokenType::If
-                    | TokenType::While
-                    | TokenType::Print
-                    | TokenType::Return
-            ) {
+            if Self::starts_statement(&self.peek().token_type) {
                 break;
             }
             self.advance();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_printer::AstPrinter;
+    use crate::reporter::BufferedReporter;
+    use crate::scanner::Scanner;
+    use std::cell::RefCell;
+
+    /// Parse a single expression statement and print its AST.
+    fn parse_expr(source: &str) -> Result<String, Vec<String>> {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new(source, &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let Ok(statements) = Parser::new(tokens, &mut reporter).parse() else {
+            return Err(reporter.messages);
+        };
+        if !reporter.messages.is_empty() {
+            return Err(reporter.messages);
+        }
+        match &statements[..] {
+            [Stmt::Expr(stmt)] => Ok(AstPrinter {}.print(&stmt.expr)),
+            _ => panic!("expected a single expression statement"),
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        assert_eq!(
+            parse_expr("v = if (a) 1 else 2;"),
+            Ok("(= v (if (var Identifier a) 1 2))".to_string())
+        );
+        assert_eq!(
+            parse_expr("v = if (a) 1 else if (b) 2 else 3;"),
+            Ok("(= v (if (var Identifier a) 1 (if (var Identifier b) 2 3)))".to_string())
+        );
+        assert_eq!(
+            parse_expr("[if (a) 1 else 2, 3];"),
+            Ok("(list (if (var Identifier a) 1 2) 3)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_logical_precedence() {
+        assert_eq!(
+            parse_expr("a or b xor c and d;"),
+            Ok("(xor (or (var Identifier a) (var Identifier b)) \
+                (and (var Identifier c) (var Identifier d)))"
+                .to_string())
+        );
+        assert_eq!(
+            parse_expr("not a and !b == c;"),
+            Ok(
+                "(and (not (var Identifier a)) (== (! (var Identifier b)) (var Identifier c)))"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_index_expression() {
+        assert_eq!(
+            parse_expr("-m[0][k];"),
+            Ok("(- (index (index (var Identifier m) 0) (var Identifier k)))".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assignment_is_right_associative() {
+        assert_eq!(parse_expr("a = b = 1;"), Ok("(= a (= b 1))".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        for (source, column) in [("(a) = 1;", 5), ("3 = 1;", 3), ("a + b = 1;", 7)] {
+            assert_eq!(
+                parse_expr(source),
+                Err(vec![format!(
+                    "[line 1, col {}] Error at '=': Invalid assignment target.",
+                    column
+                )])
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_without_unwinding_fails_parse() {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new("a + b = 1; print 2;", &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let errors = Parser::new(tokens, &mut reporter).parse().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Invalid assignment target.");
+    }
+
+    #[test]
+    fn test_unterminated_block() {
+        assert_eq!(
+            parse_errors("{ var a = 1;\nprint a;\n"),
+            ["[line 2, col 9] Error at end: Expected '}' after block, found end of input"]
+        );
+    }
+
+    fn parse_warnings(source: &str) -> Vec<String> {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new(source, &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let _ = Parser::new(tokens, &mut reporter).parse();
+        assert!(reporter.messages.is_empty(), "{:?}", reporter.messages);
+        reporter.warnings
+    }
+
+    #[test]
+    fn test_warn_assignment_in_condition() {
+        assert_eq!(
+            parse_warnings("var a; while (a = nil) print a;"),
+            ["[line 1, col 15] Warning at 'a': Assignment in condition; did you mean '=='?"]
+        );
+        assert_eq!(
+            parse_warnings("var a = if (a = 1) 2 else 3;"),
+            ["[line 1, col 13] Warning at 'a': Assignment in condition; did you mean '=='?"]
+        );
+        assert!(parse_warnings("var a; while (a == nil) a = 1;").is_empty());
+        assert!(parse_warnings("var a; while ((a = nil)) print a;").is_empty());
+    }
+
+    #[test]
+    fn test_warn_string_literal_statement() {
+        assert_eq!(
+            parse_warnings("\"TODO\";"),
+            ["[line 1, col 1] Warning at '\"TODO\"': String literal statement has no effect."]
+        );
+        assert!(parse_warnings("print \"TODO\"; 1; \"a\" + \"b\";").is_empty());
+    }
+
+    #[test]
+    fn test_warn_empty_if_body() {
+        assert_eq!(
+            parse_warnings("var x; if (x == 1); print x;"),
+            ["[line 1, col 19] Warning at ';': Empty body after 'if'; the ';' ends the statement."]
+        );
+        assert_eq!(parse_warnings("var x; if (x); else print x;").len(), 1);
+        assert!(parse_warnings("var x; if (x == 1) {} if (x) print x; else {}").is_empty());
+    }
+
+    #[test]
+    fn test_warn_contradicting_nil_checks() {
+        assert_eq!(
+            parse_warnings("var x; print x == nil and x != nil;"),
+            ["[line 1, col 29] Warning at '!=': 'x' is compared with both '== nil' and '!= nil'."]
+        );
+        assert_eq!(
+            parse_warnings("var x; print (nil == x or true) or (x != nil);").len(),
+            1
+        );
+        assert!(parse_warnings("var x; var y; print x == nil and y != nil;").is_empty());
+        assert!(parse_warnings("var x; print x != nil and x == nil;").is_empty());
+    }
+
+    fn parse_errors(source: &str) -> Vec<String> {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new(source, &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let _ = Parser::new(tokens, &mut reporter).parse();
+        reporter.messages
+    }
+
+    #[test]
+    fn test_parse_collects_every_error() {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new(
+            "var = 1;\nprint 1;\n{ print 1 +; }\nprint 2;",
+            &mut reporter,
+        );
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let errors = Parser::new(tokens, &mut reporter).parse().unwrap_err();
+        let errors: Vec<(&str, usize, &str)> = errors
+            .iter()
+            .map(|e| (e.token.lexeme.as_str(), e.token.line, e.message.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("=", 1, "Expected variable name, found '='"),
+                (";", 3, "Expected expression, found ';'")
+            ]
+        );
+        assert_eq!(reporter.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_expected_and_found() {
+        for (source, expected) in [
+            (
+                "print 1 }",
+                "[line 1, col 9] Error at '}': Expected ';' after value, found '}'",
+            ),
+            (
+                "if (a print a;",
+                "[line 1, col 7] Error at 'print': Expected ')' after if condition, found 'print'",
+            ),
+            (
+                "fun f(a, 1) {}",
+                "[line 1, col 10] Error at '1': Expected parameter name, found '1'",
+            ),
+            (
+                "while (true) {\n  print 1;",
+                "[line 2, col 11] Error at end: Expected '}' after block, found end of input",
+            ),
+            (
+                "var m = {1: 2, 3 4};",
+                "[line 1, col 18] Error at '4': Expected ':' after map key, found '4'",
+            ),
+        ] {
+            assert_eq!(parse_errors(source), [expected], "{}", source);
+        }
+
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new("try {} print 1;", &mut reporter);
+        scanner.scan_tokens();
+        let errors = Parser::new(scanner.into_tokens(), &mut reporter)
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors[0].token.lexeme, "print");
+        assert_eq!(*errors[0].expected, [TokenType::Catch, TokenType::Finally]);
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        for (source, expected) in [
+            ("var", "Expected variable name, found end of input"),
+            ("var x =", "Unexpected end of input, expected expression."),
+            ("print (", "Unexpected end of input, expected expression."),
+            ("print 1 +", "Unexpected end of input, expected expression."),
+        ] {
+            let mut reporter = BufferedReporter::default();
+            let mut scanner = Scanner::new(source, &mut reporter);
+            scanner.scan_tokens();
+            let errors = Parser::new(scanner.into_tokens(), &mut reporter)
+                .parse()
+                .unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", source);
+            assert_eq!(errors[0].message, expected, "{}", source);
+            assert_eq!(errors[0].kind, ParseErrorKind::Incomplete, "{}", source);
+        }
+        assert_eq!(
+            parse_errors("var x ="),
+            ["[line 1, col 8] Error at end: Unexpected end of input, expected expression."]
+        );
+
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new("var x = );", &mut reporter);
+        scanner.scan_tokens();
+        let errors = Parser::new(scanner.into_tokens(), &mut reporter)
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors[0].kind, ParseErrorKind::Syntax);
+    }
+
+    #[test]
+    fn test_unclosed_paren() {
+        assert_eq!(
+            parse_errors("print (1 + 2;\nprint 3;"),
+            ["[line 1, col 7] Error at '(': Unclosed '(' opened at line 1."]
+        );
+        // Only the innermost unclosed group is reported.
+        assert_eq!(
+            parse_errors("print ((1 +\n (2 * 3);\nprint (4;"),
+            [
+                "[line 1, col 8] Error at '(': Unclosed '(' opened at line 1.",
+                "[line 3, col 7] Error at '(': Unclosed '(' opened at line 3."
+            ]
+        );
+        assert_eq!(
+            parse_errors("print ((1 + 2 ;"),
+            ["[line 1, col 8] Error at '(': Unclosed '(' opened at line 1."]
+        );
+        // A later `)` closes the group, so the rest of the statement parses.
+        assert_eq!(
+            parse_errors("print (1 2) + 3;"),
+            ["[line 1, col 7] Error at '(': Unclosed '(' opened at line 1."]
+        );
+        // The statement keyword after the group starts the next statement.
+        assert_eq!(
+            parse_errors("print (1\nprint 2 +;"),
+            [
+                "[line 1, col 7] Error at '(': Unclosed '(' opened at line 1.",
+                "[line 2, col 10] Error at ';': Expected expression, found ';'"
+            ]
+        );
+
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new("var a = (1 +\n(2", &mut reporter);
+        scanner.scan_tokens();
+        let errors = Parser::new(scanner.into_tokens(), &mut reporter)
+            .parse()
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Unclosed '(' opened at line 2.");
+        assert_eq!(errors[0].kind, ParseErrorKind::Incomplete);
+    }
+
+    #[test]
+    fn test_argument_limits() {
+        let names = |count: usize| {
+            (0..count)
+                .map(|i| format!("a{}", i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        assert!(parse_errors(&format!("f({});", names(MAX_ARGUMENTS))).is_empty());
+        assert!(parse_errors(&format!("fun f({}) {{}}", names(MAX_ARGUMENTS))).is_empty());
+
+        // Reported once, at the first one too many, and parsing goes on.
+        let call = format!("f({});\nprint 1 +;", names(MAX_ARGUMENTS + 10));
+        let column = call.find("a255").unwrap() + 1;
+        assert_eq!(
+            parse_errors(&call),
+            [
+                format!(
+                    "[line 1, col {}] Error at 'a255': Can't have more than 255 arguments.",
+                    column
+                ),
+                "[line 2, col 10] Error at ';': Expected expression, found ';'".to_string()
+            ]
+        );
+        let declaration = format!("fun f({}) {{}}\nprint 1 +;", names(MAX_ARGUMENTS + 1));
+        let column = declaration.find("a255").unwrap() + 1;
+        assert_eq!(
+            parse_errors(&declaration),
+            [
+                format!(
+                    "[line 1, col {}] Error at 'a255': Can't have more than 255 parameters.",
+                    column
+                ),
+                "[line 2, col 10] Error at ';': Expected expression, found ';'".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skips_trivia() {
+        let source =
+            "// a counter\nvar i = 0; // start\nwhile (i < 3) { // loop\n  i = i + 1;\n}\n//";
+        let parse = |trivia: bool| {
+            let mut reporter = BufferedReporter::default();
+            let mut scanner = Scanner::new(source, &mut reporter).with_trivia(trivia);
+            scanner.scan_tokens();
+            let tokens = scanner.into_tokens();
+            let tree = format!("{:?}", Parser::new(tokens, &mut reporter).parse().unwrap());
+            // Expression ids are unique across parses, so leave them out.
+            let mut parts = tree.split("ExprId(");
+            let mut tree = parts.next().unwrap().to_string();
+            for part in parts {
+                tree.push_str(part.trim_start_matches(|c: char| c.is_ascii_digit()));
+            }
+            tree
+        };
+        assert_eq!(parse(true), parse(false));
+    }
+
+    #[test]
+    fn test_errors_within_statement() {
+        assert_eq!(
+            parse_errors("print f(1 +, g(2, 3 *), 4);"),
+            [
+                "[line 1, col 12] Error at ',': Expected expression, found ','",
+                "[line 1, col 22] Error at ')': Expected expression, found ')'"
+            ]
+        );
+        assert_eq!(
+            parse_errors("var a = 1 b = 2 +, c = -;"),
+            [
+                "[line 1, col 11] Error at 'b': Expected ',' or ';' after variable declaration, found 'b'",
+                "[line 1, col 18] Error at ',': Expected expression, found ','",
+                "[line 1, col 25] Error at ';': Expected expression, found ';'"
+            ]
+        );
+        // A list that does not go on is reported once, as before.
+        assert_eq!(
+            parse_errors("print f(1 +;\nprint g(2 +);"),
+            [
+                "[line 1, col 12] Error at ';': Expected expression, found ';'",
+                "[line 2, col 12] Error at ')': Expected expression, found ')'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let reporter = RefCell::new(BufferedReporter::default());
+        let (mut scanning, mut parsing) = (&reporter, &reporter);
+        let mut scanner = Scanner::new("", &mut scanning);
+        let mut parser = Parser::new(Vec::new(), &mut parsing);
+        for (source, counts) in [
+            ("while (true) {\n", Err(1)),
+            ("break;\n", Err(1)),
+            ("1; 2;\n", Ok(2)),
+        ] {
+            scanner.reset(source, true);
+            scanner.scan_tokens();
+            parser.reset(std::mem::take(&mut scanner.tokens));
+            let parsed = parser.parse();
+            assert_eq!(parsed.map(|s| s.len()).map_err(|e| e.len()), counts);
+        }
+        assert_eq!(
+            reporter.borrow().messages,
+            [
+                "[line 1, col 15] Error at end: Expected '}' after block, found end of input",
+                "[line 2, col 1] Error at 'break': 'break' outside loop"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_from_scanner() {
+        let source = "var a = ;\nvar b = 1 @ 2;\nprint a;";
+        let reporter = RefCell::new(BufferedReporter::default());
+        let (mut scanning, mut parsing) = (&reporter, &reporter);
+        let scanner = Scanner::new(source, &mut scanning);
+        let statements = Parser::new(scanner, &mut parsing).parse();
+        assert_eq!(statements.unwrap_err().len(), 2);
+        // Pulled lazily, the scanner's error comes between the parser's.
+        assert_eq!(
+            reporter.into_inner().messages,
+            [
+                "[line 1, col 9] Error at ';': Expected expression, found ';'",
+                "[line 2, col 11] Error : encountered unexpected character: @",
+                "[line 2, col 13] Error at '2': Expected ';' after variable declaration, found '2'"
+            ]
+        );
+        assert_eq!(parse_errors(source).len(), 3);
+    }
+
+    #[test]
+    fn test_if_statement_parentheses() {
+        assert_eq!(
+            parse_errors("if true) print 1;"),
+            ["[line 1, col 4] Error at 'true': Expected '(' after 'if', found 'true'"]
+        );
+        assert_eq!(
+            parse_errors("if (true print 1;"),
+            ["[line 1, col 10] Error at 'print': Expected ')' after if condition, found 'print'"]
+        );
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        assert_eq!(
+            parse_errors("break;"),
+            ["[line 1, col 1] Error at 'break': 'break' outside loop"]
+        );
+        assert_eq!(
+            parse_errors("while (true) {}\n{ break; }"),
+            ["[line 2, col 3] Error at 'break': 'break' outside loop"]
+        );
+        assert!(parse_errors("while (true) { if (true) { break; } }").is_empty());
+        assert!(parse_errors("for (;;) break;").is_empty());
+    }
+
+    #[test]
+    fn test_continue_outside_loop() {
+        assert_eq!(
+            parse_errors("print 1;\n\ncontinue;"),
+            ["[line 3, col 1] Error at 'continue': 'continue' outside loop"]
+        );
+        assert!(parse_errors("for (;;) { continue; }").is_empty());
+    }
+
+    #[test]
+    fn test_call() {
+        assert_eq!(parse_expr("f();").unwrap(), "(call (var Identifier f))");
+        assert_eq!(
+            parse_expr("f(1, g(2))[0](3);").unwrap(),
+            "(call (index (call (var Identifier f) 1 (call (var Identifier g) 2)) 0) 3)"
+        );
+    }
+
+    #[test]
+    fn test_class_declaration() {
+        assert!(parse_errors("class A {}\nclass B { get() { return 1; } set(v) {} }").is_empty());
+        assert_eq!(
+            parse_errors("class A { 1; }")[0],
+            "[line 1, col 11] Error at '1': Expected method or field declaration in class body, found '1'"
+        );
+        assert!(parse_errors("class B < A {}").is_empty());
+        assert!(parse_errors("class C { area { return 1; } }").is_empty());
+        assert_eq!(
+            parse_errors("fun area { return 1; }")[0],
+            "[line 1, col 10] Error at '{': Expected '(' after function name, found '{'"
+        );
+        assert_eq!(
+            parse_errors("class B < {}")[0],
+            "[line 1, col 11] Error at '{': Expected superclass name, found '{'"
+        );
+        assert_eq!(
+            parse_errors("class A { f() {}"),
+            ["[line 1, col 17] Error at end: Expected '}' after class body, found end of input"]
+        );
+    }
+
+    #[test]
+    fn test_properties() {
+        assert_eq!(
+            parse_expr("a.b.c;").unwrap(),
+            "(. c (. b (var Identifier a)))"
+        );
+        assert_eq!(
+            parse_expr("a.b(1).c = this.d;").unwrap(),
+            "(.= c (call (. b (var Identifier a)) 1) (. d this))"
+        );
+        assert_eq!(
+            parse_errors("a.1;"),
+            ["[line 1, col 3] Error at '1': Expected property name after '.', found '1'"]
+        );
+    }
+
+    #[test]
+    fn test_super() {
+        assert_eq!(
+            parse_expr("super.cook(1);").unwrap(),
+            "(call (super cook) 1)"
+        );
+        assert_eq!(
+            parse_errors("super;"),
+            ["[line 1, col 6] Error at ';': Expected '.' after 'super', found ';'"]
+        );
+    }
+
+    #[test]
+    fn test_conditional() {
+        assert_eq!(
+            parse_expr("a ? b : c ? d : e;").unwrap(),
+            "(?: (var Identifier a) (var Identifier b) (?: (var Identifier c) (var Identifier d) (var Identifier e)))"
+        );
+        assert_eq!(
+            parse_expr("x = a or b ? 1 : 2;").unwrap(),
+            "(= x (?: (or (var Identifier a) (var Identifier b)) 1 2))"
+        );
+        assert_eq!(
+            parse_errors("a ?\n b;"),
+            ["[line 2, col 3] Error at ';': Expected ':' to complete the '?' on line 1, found ';'"]
+        );
+    }
+
+    #[test]
+    fn test_missing_left_operand() {
+        for (source, operator) in [
+            ("== x;", "=="),
+            ("!= x;", "!="),
+            ("> 1;", ">"),
+            ("<= 1;", "<="),
+            ("+ 3;", "+"),
+            ("* 2;", "*"),
+            ("/ 2;", "/"),
+        ] {
+            assert_eq!(
+                parse_errors(&format!("{}\nprint 1;", source)),
+                [format!(
+                    "[line 1, col 1] Error at '{}': Binary operator '{}' missing left-hand operand.",
+                    operator, operator
+                )]
+            );
+        }
+        // The right operand is parsed at the operator's precedence, so the
+        // rest of the statement is still checked.
+        assert_eq!(
+            parse_errors("== 1 < 2 == 3 print 1;"),
+            [
+                "[line 1, col 1] Error at '==': Binary operator '==' missing left-hand operand.",
+                "[line 1, col 15] Error at 'print': Expected ';' after value, found 'print'"
+            ]
+        );
+        assert_eq!(
+            parse_errors("+ 1;\nprint 2 +;"),
+            [
+                "[line 1, col 1] Error at '+': Binary operator '+' missing left-hand operand.",
+                "[line 2, col 10] Error at ';': Expected expression, found ';'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // Unoptimized builds use far more stack per level than the default
+        // limit is sized for.
+        let deep = std::thread::Builder::new().stack_size(64 << 20);
+        deep.spawn(|| {
+            let nested = |depth: usize| format!("{}1{};", "(".repeat(depth), ")".repeat(depth));
+            assert!(parse_expr(&nested(DEFAULT_MAX_DEPTH - 1)).is_ok());
+            assert_eq!(
+                parse_errors(&nested(50_000)),
+                [format!(
+                    "[line 1, col {}] Error at '(': Expression too deeply nested.",
+                    DEFAULT_MAX_DEPTH + 1
+                )]
+            );
+            assert_eq!(
+                parse_errors(&format!("{}1;\nprint 2;", "!".repeat(50_000))),
+                [format!(
+                    "[line 1, col {}] Error at '!': Expression too deeply nested.",
+                    DEFAULT_MAX_DEPTH + 1
+                )]
+            );
+            assert_eq!(parse_errors(&"a = ".repeat(50_000)).len(), 1);
+
+            let chain = |terms: usize| format!("1{};", " + 1".repeat(terms - 1));
+            assert!(parse_expr(&chain(DEFAULT_MAX_DEPTH)).is_ok());
+            assert_eq!(
+                parse_errors(&chain(10_000)),
+                [format!(
+                    "[line 1, col {}] Error at '+': Expression too deeply nested.",
+                    4 * DEFAULT_MAX_DEPTH - 1
+                )]
+            );
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new("((1));", &mut reporter);
+        scanner.scan_tokens();
+        let mut parser = Parser::new(scanner.into_tokens(), &mut reporter);
+        parser.set_max_depth(2);
+        let errors = parser.parse().unwrap_err();
+        assert_eq!(errors[0].message, "Expression too deeply nested.");
+    }
+
+    #[test]
+    fn test_associativity() {
+        for (source, expected) in [
+            ("1 ?? 2 ?? 3;", "(?? (?? 1 2) 3)"),
+            ("1 or 2 xor 3;", "(xor (or 1 2) 3)"),
+            ("1 and 2 and 3;", "(and (and 1 2) 3)"),
+            ("1 == 2 != 3;", "(!= (== 1 2) 3)"),
+            ("1 < 2 >= 3;", "(>= (< 1 2) 3)"),
+            ("1 - 2 + 3;", "(+ (- 1 2) 3)"),
+            ("1 / 2 * 3;", "(* (/ 1 2) 3)"),
+            ("1 ** 2 ** 3;", "(** 1 (** 2 3))"),
+        ] {
+            assert_eq!(parse_expr(source).unwrap(), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_precedence_levels() {
+        assert_eq!(
+            parse_expr("1 ?? 2 or 3 and 4 == 5 < 6 + 7 * 8 ** 9;").unwrap(),
+            "(?? 1 (or 2 (and 3 (== 4 (< 5 (+ 6 (* 7 (** 8 9))))))))"
+        );
+        assert_eq!(
+            parse_expr("1 ** 2 * 3 + 4 < 5 == 6 and 7 or 8 ?? 9;").unwrap(),
+            "(?? (or (and (== (< (+ (* (** 1 2) 3) 4) 5) 6) 7) 8) 9)"
+        );
+        assert_eq!(parse_expr("-1 * !2;").unwrap(), "(* (- 1) (! 2))");
+    }
+
+    #[test]
+    fn test_exponent() {
+        assert_eq!(parse_expr("2 ** 3 ** 2;").unwrap(), "(** 2 (** 3 2))");
+        assert_eq!(parse_expr("-2 ** 2;").unwrap(), "(- (** 2 2))");
+        assert_eq!(parse_expr("2 ** -1 * 3;").unwrap(), "(* (** 2 (- 1)) 3)");
+        assert_eq!(
+            parse_errors("a * *b;"),
+            ["[line 1, col 5] Error at '*': Binary operator '*' missing left-hand operand."]
+        );
+    }
+
+    #[test]
+    fn test_coalesce() {
+        assert_eq!(
+            parse_expr("a ?? b or c ?? d;").unwrap(),
+            "(?? (?? (var Identifier a) (or (var Identifier b) (var Identifier c))) (var Identifier d))"
+        );
+        assert_eq!(
+            parse_expr("a ?? b ? 1 : 2;").unwrap(),
+            "(?: (?? (var Identifier a) (var Identifier b)) 1 2)"
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        assert_eq!(
+            parse_expr("a -= b *= 2;").unwrap(),
+            "(= a (- (var Identifier a) (= b (* (var Identifier b) 2))))"
+        );
+        assert_eq!(
+            parse_expr("a.b /= 2;").unwrap(),
+            "(./= b (var Identifier a) 2)"
+        );
+        assert_eq!(
+            parse_errors("3 += 1;"),
+            ["[line 1, col 3] Error at '+=': Invalid assignment target."]
+        );
+    }
+
+    #[test]
+    fn test_increment() {
+        assert_eq!(parse_expr("i++ + ++j;").unwrap(), "(+ (i++) (++j))");
+        assert_eq!(parse_expr("-i--;").unwrap(), "(- (i--))");
+        assert_eq!(
+            parse_errors("5++;"),
+            ["[line 1, col 2] Error at '++': Invalid increment target."]
+        );
+        assert_eq!(
+            parse_errors("--a.b;"),
+            ["[line 1, col 1] Error at '--': Invalid increment target."]
+        );
+    }
+
+    #[test]
+    fn test_class_fields() {
+        assert!(parse_errors("class P { var x = 0, y; var items = []; init() {} }").is_empty());
+        assert_eq!(
+            parse_errors("class P { print 1; }")[0],
+            "[line 1, col 11] Error at 'print': Expected method or field declaration in class body, found 'print'"
+        );
+        assert_eq!(
+            parse_errors("class P { var = 1; }")[0],
+            "[line 1, col 15] Error at '=': Expected field name, found '='"
+        );
+        assert_eq!(
+            parse_errors("class P { var x = 1 }"),
+            ["[line 1, col 21] Error at '}': Expected ';' after field declaration, found '}'"]
+        );
+    }
+
+    #[test]
+    fn test_print_list() {
+        assert!(parse_errors("print; print 1; print 1, \"a\", x;").is_empty());
+        assert_eq!(
+            parse_errors("print 1, 2,;"),
+            ["[line 1, col 12] Error at ';': Expected expression after ',', found ';'"]
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        assert!(parse_errors("import \"m.lux\"; import \"lib/m.lux\" as m;").is_empty());
+        assert_eq!(
+            parse_errors("import m;"),
+            ["[line 1, col 8] Error at 'm': Expected module path after 'import', found 'm'"]
+        );
+        assert_eq!(
+            parse_errors("import \"m.lux\" as;"),
+            ["[line 1, col 18] Error at ';': Expected module name after 'as', found ';'"]
+        );
+    }
+
+    #[test]
+    fn test_tuple_literal() {
+        assert_eq!(parse_expr("(1);").unwrap(), "(group 1)");
+        assert_eq!(parse_expr("(1,);").unwrap(), "(tuple 1)");
+        assert_eq!(parse_expr("(1, 2 + 3,);").unwrap(), "(tuple 1 (+ 2 3))");
+        assert!(parse_errors("var (q, r) = pair;").is_empty());
+        assert_eq!(
+            parse_errors("var (q, r);"),
+            ["[line 1, col 11] Error at ';': Expected '=' after destructuring pattern, found ';'"]
+        );
+    }
+
+    #[test]
+    fn test_map_literal() {
+        assert_eq!(
+            parse_expr("x = {\"a\": 1, 2: b};").unwrap(),
+            "(= x (map a 1 2 (var Identifier b)))"
+        );
+        assert_eq!(
+            parse_errors("x = {\"a\" 1};"),
+            ["[line 1, col 10] Error at '1': Expected ':' after map key, found '1'"]
+        );
+    }
+
+    #[test]
+    fn test_index_assignment() {
+        assert_eq!(
+            parse_expr("xs[0] = 1;").unwrap(),
+            "(index= (var Identifier xs) 0 1)"
+        );
+        assert_eq!(
+            parse_expr("xs[i] += 1;").unwrap(),
+            "(index+= (var Identifier xs) (var Identifier i) 1)"
+        );
+    }
+
+    #[test]
+    fn test_multiple_declarators() {
+        assert!(parse_errors("var a = 1, b, c = a + 2;").is_empty());
+        assert_eq!(
+            parse_errors("var a = 1, ; var b = 2, 3; print c;"),
+            [
+                "[line 1, col 12] Error at ';': Expected variable name after ',', found ';'",
+                "[line 1, col 25] Error at '3': Expected variable name after ',', found '3'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_switch_statement() {
+        assert!(
+            parse_errors("switch (x) { case 1: print 1; case 2: default: print 3; }").is_empty()
+        );
+        assert_eq!(
+            parse_warnings("switch (x) { case 1: case \"a\": case 1.0: }"),
+            ["[line 1, col 37] Warning at '1.0': Duplicate case value; it can never match."]
+        );
+        assert_eq!(
+            parse_errors("switch (x) { default: case 1: }"),
+            ["[line 1, col 23] Error at 'case': Expected '}' after the 'default' case, which must be the last, found 'case'"]
+        );
+    }
+
+    #[test]
+    fn test_try_statement() {
+        assert!(parse_errors("try { throw 1; } catch (e) { print e; } finally {}").is_empty());
+        assert!(parse_errors("try {} finally {}").is_empty());
+        assert_eq!(
+            parse_errors("try {} print 1;"),
+            ["[line 1, col 8] Error at 'print': Expected 'catch' or 'finally' after try block, found 'print'"]
+        );
+    }
+
+    #[test]
+    fn test_variadic_parameters() {
+        assert!(parse_errors("fun f(a, ...rest) {} fun g(...all) {}").is_empty());
+        assert_eq!(
+            parse_errors("fun f(...rest, a) {}"),
+            ["[line 1, col 14] Error at ',': Variadic parameter must be the last one."]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        assert_eq!(
+            parse_warnings("fun f() {\n  return 1;\n  print 2;\n  return 3;\n  print 4;\n}"),
+            ["[line 3, col 3] Warning at 'print': Unreachable code."]
+        );
+        assert_eq!(
+            parse_warnings("while (true) { if (true) { break; } else { continue; } throw 1; 2; }"),
+            ["[line 1, col 65] Warning at '2': Unreachable code."]
+        );
+        assert!(parse_warnings("fun f(x) { if (x) return 1; print 2; return 3; }").is_empty());
+        assert!(
+            parse_warnings("while (true) switch (1) { case 1: break; case 2: print 2; }")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_return_outside_function() {
+        assert_eq!(
+            parse_errors("return 1;"),
+            ["[line 1, col 1] Error at 'return': Can't return from top-level code."]
+        );
+        assert!(parse_errors("fun f() { while (true) { return; } }").is_empty());
+        assert_eq!(
+            parse_errors("while (true) { fun f() { break; } }"),
+            ["[line 1, col 26] Error at 'break': 'break' outside loop"]
+        );
+    }
+
+    #[test]
+    fn test_if_expression_requires_else() {
+        assert_eq!(
+            parse_expr("v = if (a) 1;"),
+            Err(vec![
+                "[line 1, col 13] Error at ';': Expected 'else' branch in if expression, found ';'"
+                    .to_string()
+            ])
+        );
+    }
+}
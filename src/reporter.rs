@@ -1,29 +1,173 @@
+use std::cell::RefCell;
+
+use crate::interpreter::RuntimeError;
 use crate::token::{Token, TokenType};
 
 pub trait Reporter {
-    fn scanner_error(&mut self, line: usize, message: &str);
+    fn scanner_error(&mut self, line: usize, column: usize, message: &str) {
+        self.report(line, column, "", message);
+    }
     #[allow(dead_code)]
-    fn parser_error(&mut self, token: &Token, message: &str);
-    fn report(&mut self, line: usize, error_where: &str, message: &str);
+    fn parser_error(&mut self, token: &Token, message: &str) {
+        self.report(token.line, token.column, &location(token), message);
+    }
+    /// Errors the resolver finds in a program that parsed successfully.
+    fn resolver_error(&mut self, token: &Token, message: &str) {
+        self.report(token.line, token.column, &location(token), message);
+    }
+    /// Flag code that is legal but almost certainly a mistake.
+    fn parser_warning(&mut self, token: &Token, message: &str) {
+        self.warning(token.line, token.column, &location(token), message);
+    }
+    fn resolver_warning(&mut self, token: &Token, message: &str) {
+        self.warning(token.line, token.column, &location(token), message);
+    }
+    fn report(&mut self, line: usize, column: usize, error_where: &str, message: &str);
+    /// Warnings are informational and never count as errors.
+    fn warning(&mut self, line: usize, column: usize, warning_where: &str, message: &str);
+    /// An error that stopped a program while it was running.
+    fn runtime_error(&mut self, error: &RuntimeError);
 }
 
+fn location(token: &Token) -> String {
+    match token.token_type {
+        TokenType::EndOfFile => "at end".to_string(),
+        _ => format!("at '{}'", token.lexeme),
+    }
+}
+
+/// Where a diagnostic points: `line 3, col 17`, or `lib.lux line 3, col 17`
+/// for code from a named file.
+fn position(file: &Option<String>, line: usize, column: usize) -> String {
+    match file {
+        Some(file) => format!("{} line {}, col {}", file, line, column),
+        None => format!("line {}, col {}", line, column),
+    }
+}
+
+/// How a runtime error is shown: `[line 3] runtime error: division by
+/// zero`, or without the line when it is not known. An error raised inside
+/// calls is followed by their trace on the next line, innermost first, e.g.
+/// `in fib (line 12) / in main (line 30)`, where each line is that of the
+/// call. A run of the same call, as recursion makes, is shown once with a
+/// count.
+pub(crate) fn runtime_message(file: &Option<String>, error: &RuntimeError) -> String {
+    let mut message = match (file, error.line()) {
+        (Some(file), Some(line)) => format!("[{} line {}] runtime error: {}", file, line, error),
+        (None, Some(line)) => format!("[line {}] runtime error: {}", line, error),
+        _ => format!("runtime error: {}", error),
+    };
+    let mut frames = Vec::new();
+    for run in error.trace().chunk_by(|a, b| a == b) {
+        match run.len() {
+            1 => frames.push(run[0].to_string()),
+            count => frames.push(format!("{}, {} times", run[0], count)),
+        }
+    }
+    if !frames.is_empty() {
+        message.push_str("\n  ");
+        message.push_str(&frames.join(" / "));
+    }
+    message
+}
+
+/// Reports to the terminal while a program prints to stdout. Diagnostics
+/// go to stderr, so they don't mix with the program's own output.
 #[derive(Default)]
 pub struct StdoutReporter {
     had_error: bool,
+    had_runtime_error: bool,
+    /// Drop warnings instead of printing them (`--no-warn`).
+    pub no_warn: bool,
+    /// The file being reported on, shown before the line when set.
+    pub file: Option<String>,
+}
+
+impl StdoutReporter {
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+
+    pub fn had_runtime_error(&self) -> bool {
+        self.had_runtime_error
+    }
+
+    /// Forget earlier errors, as before each entry in the REPL.
+    pub fn reset(&mut self) {
+        self.had_error = false;
+        self.had_runtime_error = false;
+    }
 }
 
 impl Reporter for StdoutReporter {
-    fn scanner_error(&mut self, line: usize, message: &str) {
-        self.report(line, "", message);
+    fn report(&mut self, line: usize, column: usize, error_where: &str, message: &str) {
+        eprintln!(
+            "[{}] Error {}: {}",
+            position(&self.file, line, column),
+            error_where,
+            message
+        );
+        self.had_error = true;
     }
-    fn parser_error(&mut self, token: &Token, message: &str) {
-        match token.token_type {
-            TokenType::EndOfFile => self.report(token.line, " at end", message),
-            _ => self.report(token.line, &format!("at '{}'", token.lexeme), message),
+    fn warning(&mut self, line: usize, column: usize, warning_where: &str, message: &str) {
+        if !self.no_warn {
+            eprintln!(
+                "[{}] Warning {}: {}",
+                position(&self.file, line, column),
+                warning_where,
+                message
+            );
         }
     }
-    fn report(&mut self, line: usize, error_where: &str, message: &str) {
-        println!("[line {}] Error {}: {}", line, error_where, message);
-        self.had_error = true;
+    fn runtime_error(&mut self, error: &RuntimeError) {
+        eprintln!("{}", runtime_message(&self.file, error));
+        self.had_runtime_error = true;
+    }
+}
+
+/// Lets a lazy `Scanner` and the `Parser` pulling tokens from it report to
+/// the same reporter, each through its own reference to the cell.
+impl<R: Reporter> Reporter for &RefCell<R> {
+    fn report(&mut self, line: usize, column: usize, error_where: &str, message: &str) {
+        self.borrow_mut().report(line, column, error_where, message);
+    }
+    fn warning(&mut self, line: usize, column: usize, warning_where: &str, message: &str) {
+        self.borrow_mut()
+            .warning(line, column, warning_where, message);
+    }
+    fn runtime_error(&mut self, error: &RuntimeError) {
+        self.borrow_mut().runtime_error(error);
+    }
+}
+
+/// Keeps diagnostics in memory instead of printing them, for callers that
+/// surface errors themselves.
+#[derive(Default)]
+pub struct BufferedReporter {
+    pub messages: Vec<String>,
+    pub warnings: Vec<String>,
+    /// The file being reported on, shown before the line when set.
+    pub file: Option<String>,
+}
+
+impl Reporter for BufferedReporter {
+    fn report(&mut self, line: usize, column: usize, error_where: &str, message: &str) {
+        self.messages.push(format!(
+            "[{}] Error {}: {}",
+            position(&self.file, line, column),
+            error_where,
+            message
+        ));
+    }
+    fn warning(&mut self, line: usize, column: usize, warning_where: &str, message: &str) {
+        self.warnings.push(format!(
+            "[{}] Warning {}: {}",
+            position(&self.file, line, column),
+            warning_where,
+            message
+        ));
+    }
+    fn runtime_error(&mut self, error: &RuntimeError) {
+        self.messages.push(runtime_message(&self.file, error));
     }
 }
@@ -0,0 +1,638 @@
+use std::collections::HashMap;
+
+use crate::expressions::{
+    Accept as ExprAccept, AssignExpr, BinaryExpr, CallExpr, ConditionalExpr, ErrorExpr, Expr,
+    ExprId, ExprVisitor, GetExpr, GroupingExpr, IfExpr, IncrementExpr, IndexExpr, IndexSetExpr,
+    ListExpr, LiteralExpr, LogicalExpr, MapExpr, SetExpr, SuperExpr, ThisExpr, TupleExpr,
+    UnaryExpr, VarExpr,
+};
+use crate::interpreter::Interpreter;
+use crate::reporter::Reporter;
+use crate::statements::{
+    Accept as StmtAccept, BlockStmt, BreakStmt, ClassStmt, ContinueStmt, DestructureStmt,
+    DoWhileStmt, EprintStmt, ExprStmt, ForInStmt, FunctionStmt, IfStmt, ImportStmt, PrintStmt,
+    ReturnStmt, Stmt, StmtVisitor, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use crate::token::Token;
+
+/// The kind of class declaration being resolved, if any.
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// The kind of function body being resolved, if any.
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// A variable declared in a local scope.
+struct Local {
+    /// Where it is stored in its scope at runtime. Variables are numbered
+    /// in the order they are declared, which is the order the interpreter
+    /// defines them in.
+    slot: usize,
+    /// Whether its initializer has finished.
+    defined: bool,
+    /// The line it was declared on, or `None` for the implicit `this` and
+    /// `super`.
+    line: Option<usize>,
+    /// The declaration, for a variable that has not been read yet and
+    /// should be reported if it never is.
+    unread: Option<Token>,
+}
+
+impl Local {
+    /// The implicit `this` or `super`, alone in its scope.
+    fn implicit() -> Self {
+        Self {
+            slot: 0,
+            defined: true,
+            line: None,
+            unread: None,
+        }
+    }
+}
+
+/// Works out, between parsing and running a program, which declaration each
+/// variable reference means, and tells the interpreter how many scopes out
+/// from the reference that declaration lives and in which slot of that
+/// scope. Resolving statically pins
+/// closures to the variables that were in scope where they were declared,
+/// even if a later declaration in the same block shadows them.
+///
+/// Run it once over every program before interpreting it; errors go to the
+/// reporter and the program should not be run if there were any.
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    reporter: &'a mut dyn Reporter,
+    /// One map per block or function body being resolved, from each name
+    /// declared in it to what is known about it. Globals are not tracked.
+    scopes: Vec<HashMap<String, Local>>,
+    /// The line of each global declared so far, for shadowing warnings.
+    globals: HashMap<String, usize>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    warn_shadow: bool,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter, reporter: &'a mut dyn Reporter) -> Self {
+        Self {
+            interpreter,
+            reporter,
+            scopes: Vec::new(),
+            globals: HashMap::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            warn_shadow: false,
+        }
+    }
+
+    /// Warn when a local declaration hides a variable of an enclosing scope
+    /// declared earlier in the same program. Off by default.
+    pub fn set_warn_shadow(&mut self, warn_shadow: bool) {
+        self.warn_shadow = warn_shadow;
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            stmt.accept(self);
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let mut unread: Vec<Token> = scope
+            .into_values()
+            .filter_map(|local| local.unread)
+            .collect();
+        unread.sort_by_key(|name| name.line);
+        for name in unread {
+            let message = format!("Local variable '{}' is never used.", name.lexeme);
+            self.reporter.resolver_warning(&name, &message);
+        }
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if self.warn_shadow {
+            self.check_shadowing(name);
+        }
+        let Some(scope) = self.scopes.last_mut() else {
+            self.globals.insert(name.lexeme.clone(), name.line);
+            return;
+        };
+        let local = Local {
+            slot: scope.len(),
+            defined: false,
+            line: Some(name.line),
+            unread: None,
+        };
+        if scope.insert(name.lexeme.clone(), local).is_some() {
+            self.reporter
+                .resolver_error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn check_shadowing(&mut self, name: &Token) {
+        let Some((_, enclosing)) = self.scopes.split_last() else {
+            return;
+        };
+        let shadowed = enclosing
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))
+            .map(|local| local.line)
+            .unwrap_or_else(|| self.globals.get(&name.lexeme).copied());
+        if let Some(line) = shadowed {
+            let message = format!(
+                "Declaration of '{}' shadows the one on line {}.",
+                name.lexeme, line
+            );
+            self.reporter.resolver_warning(name, &message);
+        }
+    }
+
+    /// Declare a variable made by `var`, which is reported when its scope
+    /// ends if it was never read. Names starting with `_` are exempt.
+    fn declare_variable(&mut self, name: &Token) {
+        self.declare(name);
+        if name.lexeme.starts_with('_') {
+            return;
+        }
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.unread = Some(name.clone());
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
+        }
+    }
+
+    fn resolve_local(&mut self, id: ExprId, name: &Token) {
+        let slot = self
+            .scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| Some((depth, scope.get(&name.lexeme)?.slot)));
+        self.interpreter.resolve(id, slot);
+    }
+
+    fn resolve_function(&mut self, function: &FunctionStmt, function_type: FunctionType) {
+        let enclosing_function = std::mem::replace(&mut self.current_function, function_type);
+        self.begin_scope();
+        for param in function.params.iter().chain(&function.rest) {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+        self.current_function = enclosing_function;
+    }
+}
+
+impl StmtVisitor<()> for Resolver<'_> {
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        for expr in &stmt.expressions {
+            self.resolve_expr(expr);
+        }
+    }
+    fn visit_eprint_stmt(&mut self, stmt: &EprintStmt) {
+        self.resolve_expr(&stmt.expr);
+    }
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) {
+        self.resolve_expr(&stmt.expr);
+    }
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) {
+        for (name, initializer) in &stmt.declarators {
+            self.declare_variable(name);
+            if let Some(initializer) = initializer {
+                self.resolve_expr(initializer);
+            }
+            self.define(name);
+        }
+    }
+    fn visit_import_stmt(&mut self, stmt: &ImportStmt) {
+        // The names an import brings in are only known once the module has
+        // run, so they can only be globals.
+        if !self.scopes.is_empty() {
+            self.reporter
+                .resolver_error(&stmt.keyword, "Can only import at the top level.");
+        }
+        if let Some(alias) = &stmt.alias {
+            self.declare(alias);
+            self.define(alias);
+        }
+    }
+    fn visit_destructure_stmt(&mut self, stmt: &DestructureStmt) {
+        for name in &stmt.names {
+            self.declare_variable(name);
+        }
+        self.resolve_expr(&stmt.initializer);
+        for name in &stmt.names {
+            self.define(name);
+        }
+    }
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) {
+        self.resolve_expr(&stmt.condition);
+        stmt.body.accept(self);
+        if let Some(increment) = &stmt.increment {
+            self.resolve_expr(increment);
+        }
+    }
+    fn visit_do_while_stmt(&mut self, stmt: &DoWhileStmt) {
+        stmt.body.accept(self);
+        self.resolve_expr(&stmt.condition);
+    }
+    fn visit_for_in_stmt(&mut self, stmt: &ForInStmt) {
+        self.resolve_expr(&stmt.iterable);
+        self.begin_scope();
+        self.declare(&stmt.variable);
+        self.define(&stmt.variable);
+        stmt.body.accept(self);
+        self.end_scope();
+    }
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) {
+        self.begin_scope();
+        self.resolve(&stmt.statements);
+        self.end_scope();
+    }
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) {
+        self.resolve_expr(&stmt.condition);
+        stmt.then_branch.accept(self);
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+    fn visit_break_stmt(&mut self, _stmt: &BreakStmt) {}
+    fn visit_continue_stmt(&mut self, _stmt: &ContinueStmt) {}
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) {
+        // Defined before the body is resolved so that it can call itself.
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionType::Function);
+    }
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) {
+        if let Some(value) = &stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                self.reporter
+                    .resolver_error(&stmt.keyword, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(value);
+        }
+    }
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) {
+        let enclosing_class = std::mem::replace(&mut self.current_class, ClassType::Class);
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        if let Some(superclass) = &stmt.superclass {
+            self.current_class = ClassType::Subclass;
+            if superclass.name.lexeme == stmt.name.lexeme {
+                self.reporter
+                    .resolver_error(&superclass.name, "A class can't inherit from itself.");
+            }
+            self.visit_var_expr(superclass);
+            // A subclass's methods also close over a scope holding `super`.
+            self.scopes
+                .push(HashMap::from([("super".to_string(), Local::implicit())]));
+        }
+        // Methods close over a scope holding `this`, as set up by binding.
+        self.scopes
+            .push(HashMap::from([("this".to_string(), Local::implicit())]));
+        if let Some(initializer) = &stmt.field_initializer {
+            self.resolve_function(initializer, FunctionType::Method);
+        }
+        for method in &stmt.methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+    }
+    fn visit_throw_stmt(&mut self, stmt: &ThrowStmt) {
+        self.resolve_expr(&stmt.value);
+    }
+    fn visit_switch_stmt(&mut self, stmt: &SwitchStmt) {
+        self.resolve_expr(&stmt.subject);
+        for (value, body) in &stmt.cases {
+            self.resolve_expr(value);
+            self.begin_scope();
+            self.resolve(body);
+            self.end_scope();
+        }
+        if let Some(default) = &stmt.default {
+            self.begin_scope();
+            self.resolve(default);
+            self.end_scope();
+        }
+    }
+    fn visit_try_stmt(&mut self, stmt: &TryStmt) {
+        self.begin_scope();
+        self.resolve(&stmt.body);
+        self.end_scope();
+        if let Some((name, body)) = &stmt.catch {
+            self.begin_scope();
+            self.declare(name);
+            self.define(name);
+            self.resolve(body);
+            self.end_scope();
+        }
+        if let Some(finally) = &stmt.finally {
+            self.begin_scope();
+            self.resolve(finally);
+            self.end_scope();
+        }
+    }
+}
+
+impl ExprVisitor<()> for Resolver<'_> {
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) {
+        self.resolve_expr(&expr.right);
+    }
+    fn visit_literal_expr(&mut self, _expr: &LiteralExpr) {}
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) {
+        self.resolve_expr(&expr.expr);
+    }
+    fn visit_var_expr(&mut self, expr: &VarExpr) {
+        let declared = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(&expr.name.lexeme));
+        if declared.is_some_and(|local| !local.defined) {
+            self.reporter.resolver_error(
+                &expr.name,
+                "Can't read local variable in its own initializer.",
+            );
+        }
+        self.resolve_local(expr.id, &expr.name);
+        // Only reads count as uses; assigning to a variable does not.
+        if let Some(local) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&expr.name.lexeme))
+        {
+            local.unread = None;
+        }
+    }
+    fn visit_list_expr(&mut self, expr: &ListExpr) {
+        for element in &expr.elements {
+            self.resolve_expr(element);
+        }
+    }
+    fn visit_tuple_expr(&mut self, expr: &TupleExpr) {
+        for element in &expr.elements {
+            self.resolve_expr(element);
+        }
+    }
+    fn visit_map_expr(&mut self, expr: &MapExpr) {
+        for (key, value) in &expr.entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+    fn visit_if_expr(&mut self, expr: &IfExpr) {
+        self.resolve_expr(&expr.condition);
+        self.resolve_expr(&expr.then_branch);
+        self.resolve_expr(&expr.else_branch);
+    }
+    fn visit_conditional_expr(&mut self, expr: &ConditionalExpr) {
+        self.resolve_expr(&expr.condition);
+        self.resolve_expr(&expr.then_branch);
+        self.resolve_expr(&expr.else_branch);
+    }
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+    fn visit_index_expr(&mut self, expr: &IndexExpr) {
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+    fn visit_index_set_expr(&mut self, expr: &IndexSetExpr) {
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+        self.resolve_expr(&expr.value);
+    }
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) {
+        self.resolve_expr(&expr.value);
+        self.resolve_local(expr.id, &expr.name);
+    }
+    fn visit_increment_expr(&mut self, expr: &IncrementExpr) {
+        // The old value is read, so this is checked like a read.
+        self.visit_var_expr(&VarExpr {
+            id: expr.id,
+            name: expr.name.clone(),
+        });
+    }
+    fn visit_get_expr(&mut self, expr: &GetExpr) {
+        self.resolve_expr(&expr.object);
+    }
+    fn visit_set_expr(&mut self, expr: &SetExpr) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+    }
+    fn visit_this_expr(&mut self, expr: &ThisExpr) {
+        if self.current_class == ClassType::None {
+            self.reporter
+                .resolver_error(&expr.keyword, "Can't use 'this' outside of a class.");
+            return;
+        }
+        self.resolve_local(expr.id, &expr.keyword);
+    }
+    fn visit_super_expr(&mut self, expr: &SuperExpr) {
+        match self.current_class {
+            ClassType::None => self
+                .reporter
+                .resolver_error(&expr.keyword, "Can't use 'super' outside of a class."),
+            ClassType::Class => self.reporter.resolver_error(
+                &expr.keyword,
+                "Can't use 'super' in a class with no superclass.",
+            ),
+            ClassType::Subclass => self.resolve_local(expr.id, &expr.keyword),
+        }
+    }
+    fn visit_call_expr(&mut self, expr: &CallExpr) {
+        self.resolve_expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.resolve_expr(argument);
+        }
+    }
+    // The parser has already reported the mistake.
+    fn visit_error_expr(&mut self, _expr: &ErrorExpr) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::reporter::BufferedReporter;
+    use crate::scanner::Scanner;
+
+    fn resolve_source(source: &str, warn_shadow: bool) -> BufferedReporter {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new(source, &mut reporter);
+        scanner.scan_tokens();
+        let tokens = scanner.into_tokens();
+        let Ok(statements) = Parser::new(tokens, &mut reporter).parse() else {
+            return reporter;
+        };
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter, &mut reporter);
+        resolver.set_warn_shadow(warn_shadow);
+        resolver.resolve(&statements);
+        reporter
+    }
+
+    fn resolve_errors(source: &str) -> Vec<String> {
+        resolve_source(source, false).messages
+    }
+
+    fn resolve_warnings(source: &str) -> Vec<String> {
+        let reporter = resolve_source(source, false);
+        assert!(reporter.messages.is_empty());
+        reporter.warnings
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let source =
+            "var count = 0;\nfun add(count) {\n  {\n    var count = 1;\n    print count;\n  }\n}";
+        assert!(resolve_warnings(source).is_empty());
+        assert_eq!(
+            resolve_source(source, true).warnings,
+            [
+                "[line 2, col 9] Warning at 'count': Declaration of 'count' shadows the one on line 1.",
+                "[line 4, col 9] Warning at 'count': Declaration of 'count' shadows the one on line 2."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_locals() {
+        assert!(resolve_warnings("var a = 1; { var b = a; print b; }").is_empty());
+        assert_eq!(
+            resolve_warnings("{\n  var a = 1;\n  var (b, _c) = (2, 3);\n  b = a;\n}"),
+            ["[line 3, col 8] Warning at 'b': Local variable 'b' is never used."]
+        );
+        assert_eq!(
+            resolve_warnings("{ var a = 1; { var a = 2; } print a; }"),
+            ["[line 1, col 20] Warning at 'a': Local variable 'a' is never used."]
+        );
+        assert!(resolve_warnings("{ var a = 1; { var a = 2; print a; } print a; }").is_empty());
+        assert!(resolve_warnings("fun f(x, ...rest) { var _unused; }").is_empty());
+    }
+
+    #[test]
+    fn test_own_initializer() {
+        assert_eq!(
+            resolve_errors("var a = 1;\n{ var a = a + 1; }"),
+            ["[line 2, col 11] Error at 'a': Can't read local variable in its own initializer."]
+        );
+        assert!(resolve_errors("var a = 1; var a = a + 1;").is_empty());
+    }
+
+    #[test]
+    fn test_this_outside_class() {
+        assert_eq!(
+            resolve_errors("print this;\nfun f() { return this; }"),
+            [
+                "[line 1, col 7] Error at 'this': Can't use 'this' outside of a class.",
+                "[line 2, col 18] Error at 'this': Can't use 'this' outside of a class."
+            ]
+        );
+        assert!(resolve_errors("class A { f() { fun g() { return this; } } }").is_empty());
+    }
+
+    #[test]
+    fn test_return_value_from_init() {
+        assert_eq!(
+            resolve_errors("class A {\n  init() { return 1; }\n}"),
+            ["[line 2, col 12] Error at 'return': Can't return a value from an initializer."]
+        );
+        assert!(resolve_errors("class A { init() { return; } f() { return 1; } }").is_empty());
+        assert!(resolve_errors("class A { init() { fun f() { return 1; } } }").is_empty());
+    }
+
+    #[test]
+    fn test_inherit_from_itself() {
+        assert_eq!(
+            resolve_errors("class A < A {}\nprint this;"),
+            [
+                "[line 1, col 11] Error at 'A': A class can't inherit from itself.",
+                "[line 2, col 7] Error at 'this': Can't use 'this' outside of a class."
+            ]
+        );
+        assert!(resolve_errors("class A {} class B < A {}").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_super() {
+        assert_eq!(
+            resolve_errors("super.f();\nclass A { f() { super.f(); } }"),
+            [
+                "[line 1, col 1] Error at 'super': Can't use 'super' outside of a class.",
+                "[line 2, col 17] Error at 'super': Can't use 'super' in a class with no superclass."
+            ]
+        );
+        assert!(resolve_errors(
+            "class A { f() {} } class B < A { f() { fun g() { super.f(); } g(); } }"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_local() {
+        assert_eq!(
+            resolve_errors("fun f(a) { var a; }"),
+            ["[line 1, col 16] Error at 'a': Already a variable with this name in this scope."]
+        );
+        assert_eq!(
+            resolve_errors("{\n  var a = 1;\n  var a = 2;\n}"),
+            ["[line 3, col 7] Error at 'a': Already a variable with this name in this scope."]
+        );
+        assert_eq!(
+            resolve_errors("fun f(a, b,\n  a) {}"),
+            ["[line 2, col 3] Error at 'a': Already a variable with this name in this scope."]
+        );
+        assert!(resolve_errors("var a; var a; { var a; { var a; } }").is_empty());
+    }
+}
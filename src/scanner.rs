@@ -1,127 +1,399 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+use std::ops::Range;
+
 use crate::reporter::Reporter;
+use crate::symbol::Symbol;
 use crate::token::{Token, TokenType};
 
+/// How many bytes a streaming scanner takes from its reader at a time.
+const CHUNK: usize = 4096;
+
 pub struct Scanner<'a> {
-    pub source: &'a str,
+    /// The source from byte `offset` on. When streaming from a reader this
+    /// holds a bounded window: from at most a chunk or so before the token
+    /// being scanned to a little way past it.
+    pub source: Cow<'a, str>,
+    pub offset: usize,
+    reader: Option<Box<dyn BufRead + 'a>>,
+    /// Bytes read that end partway through a character, waiting for the
+    /// rest of it.
+    undecoded: Vec<u8>,
     pub tokens: Vec<Token>,
     pub start: usize,
     pub current: usize,
     pub line: usize,
+    /// The byte offset at which the current line begins.
+    pub line_offset: usize,
+    /// Where the token being scanned starts, as a line and a column.
+    pub start_line: usize,
+    pub start_column: usize,
     pub reporter: &'a mut dyn Reporter,
+    /// The token `scan_token` just produced, if any, waiting to be yielded.
+    pending: Option<Token>,
+    /// Whether the EndOfFile token has been yielded.
+    finished: bool,
+    /// Where the last token ended, as a byte offset, line and column. The
+    /// EndOfFile token goes here rather than after any trailing newlines.
+    end: (usize, usize, usize),
+    /// Whether comments are kept as tokens.
+    trivia: bool,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str, reporter: &'a mut dyn Reporter) -> Self {
+        let mut scanner = Self::with_source(Cow::Borrowed(source), None, reporter);
+        scanner.skip_shebang();
+        scanner
+    }
+
+    /// A scanner that reads its source from `reader` a chunk at a time as
+    /// tokens are pulled, rather than needing it all in memory, even when
+    /// it is all on one line.
+    pub fn from_reader(reader: impl BufRead + 'a, reporter: &'a mut dyn Reporter) -> Self {
+        let reader: Box<dyn BufRead + 'a> = Box::new(reader);
+        let mut scanner = Self::with_source(Cow::Owned(String::new()), Some(reader), reporter);
+        scanner.skip_shebang();
+        scanner
+    }
+
+    fn with_source(
+        source: Cow<'a, str>,
+        reader: Option<Box<dyn BufRead + 'a>>,
+        reporter: &'a mut dyn Reporter,
+    ) -> Self {
         Scanner {
             source,
+            offset: 0,
+            reader,
+            undecoded: Vec::new(),
             tokens: vec![],
             start: 0,
             current: 0,
-            line: 0,
+            line: 1,
+            line_offset: 0,
+            start_line: 1,
+            start_column: 1,
             reporter,
+            pending: None,
+            finished: false,
+            end: (0, 0, 1),
+            trivia: false,
+        }
+    }
+
+    /// With `trivia`, yield each `//` comment as a `TokenType::Comment`
+    /// token rather than skipping it, for tools that need the source's
+    /// comments. The parser ignores them.
+    pub fn with_trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    /// Start over on a new `source`, reusing the buffers. With `keep_line`
+    /// the new source is numbered as if it followed the old one, so a REPL
+    /// can number its entries as one long program.
+    pub fn reset(&mut self, source: &str, keep_line: bool) {
+        match &mut self.source {
+            Cow::Owned(buffer) => {
+                buffer.clear();
+                buffer.push_str(source);
+            }
+            Cow::Borrowed(_) => self.source = Cow::Owned(source.to_string()),
+        }
+        self.offset = 0;
+        self.reader = None;
+        self.undecoded.clear();
+        self.tokens.clear();
+        self.start = 0;
+        self.current = 0;
+        if !keep_line {
+            self.line = 1;
+        }
+        self.line_offset = 0;
+        self.start_line = self.line;
+        self.start_column = 1;
+        self.pending = None;
+        self.finished = false;
+        self.end = (0, self.line, 1);
+    }
+
+    /// Skip a `#!` line that lets a script run as an executable, leaving its
+    /// newline to be counted.
+    fn skip_shebang(&mut self) {
+        if self.looking_at("#!") {
+            while self.peek().is_some_and(|c| c != '\r' && c != '\n') {
+                self.advance();
+            }
         }
     }
 
-    fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
+    /// When streaming, read chunks until at least `bytes` bytes past
+    /// `current` are buffered or the source runs out.
+    fn fill(&mut self, bytes: usize) {
+        while self.source.len() - (self.current - self.offset) < bytes {
+            let Some(reader) = &mut self.reader else {
+                return;
+            };
+            let chunk = match reader.fill_buf() {
+                Ok(chunk) => chunk,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return self.read_failed(&error.to_string()),
+            };
+            if chunk.is_empty() {
+                self.reader = None;
+                if !self.undecoded.is_empty() {
+                    self.read_failed("stream did not contain valid UTF-8");
+                }
+                return;
+            }
+            let count = chunk.len().min(CHUNK);
+            self.undecoded.extend_from_slice(&chunk[..count]);
+            reader.consume(count);
+            let valid = match std::str::from_utf8(&self.undecoded) {
+                Ok(text) => text.len(),
+                // Only a character cut off at the end of the chunk may be
+                // completed by the next one.
+                Err(error) if error.error_len().is_none() => error.valid_up_to(),
+                Err(_) => return self.read_failed("stream did not contain valid UTF-8"),
+            };
+            let text = std::str::from_utf8(&self.undecoded[..valid]).expect("checked above");
+            self.source.to_mut().push_str(text);
+            self.undecoded.drain(..valid);
+        }
     }
 
+    /// Stop reading after `reader` fails, reporting why.
+    fn read_failed(&mut self, error: &str) {
+        self.reader = None;
+        let message = format!("can't read source: {}", error);
+        self.reporter.scanner_error(self.line, 1, &message);
+    }
+
+    /// The source text in `range`, which must not have been discarded.
+    fn text(&self, range: Range<usize>) -> &str {
+        &self.source[range.start - self.offset..range.end - self.offset]
+    }
+
+    /// The source text that has not been scanned yet, to the end of what
+    /// is buffered.
+    fn rest(&self) -> &str {
+        &self.source[self.current - self.offset..]
+    }
+
+    /// Whether the source goes on with `text`.
+    fn looking_at(&mut self, text: &str) -> bool {
+        self.fill(text.len());
+        self.rest().starts_with(text)
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        self.fill(1);
+        self.rest().is_empty()
+    }
+
+    // `current` is a byte offset that always sits on a char boundary, so
+    // these step over whole characters rather than bytes.
     fn advance(&mut self) -> char {
-        let c = self.source.as_bytes()[self.current] as char;
-        self.current += 1;
+        let c = self.peek().expect("advanced past the end of the source");
+        self.current += c.len_utf8();
         c
     }
 
     fn match_char(&mut self, c: char) -> bool {
-        let matched = !self.is_at_end() && self.source.as_bytes()[self.current] as char == c;
+        let matched = self.peek() == Some(c);
         if matched {
-            self.current += 1;
+            self.current += c.len_utf8();
         }
         matched
     }
 
-    fn peek(&self) -> Option<char> {
-        (!self.is_at_end()).then(|| self.source.as_bytes()[self.current] as char)
+    // A char is at most 4 bytes, so that much buffered is enough to see
+    // the next one, and twice that the one after.
+    fn peek(&mut self) -> Option<char> {
+        self.fill(4);
+        self.rest().chars().next()
+    }
+
+    fn peek_next(&mut self) -> Option<char> {
+        self.fill(8);
+        self.rest().chars().nth(1)
+    }
+
+    /// Count a line break if `c`, just consumed, ends a line. That is a
+    /// `\n`, or a `\r` not followed by one, so a `\r\n` counts once.
+    fn line_break(&mut self, c: char) {
+        if c == '\n' || (c == '\r' && self.peek() != Some('\n')) {
+            self.newline();
+        }
     }
 
-    fn peek_next(&self) -> Option<char> {
-        (self.current + 1 < self.source.len())
-            .then(|| self.source.as_bytes()[self.current + 1] as char)
+    /// Note that a line break was just consumed.
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_offset = self.current;
+    }
+
+    /// Report an error at the start of the token being scanned.
+    fn error(&mut self, message: &str) {
+        self.reporter
+            .scanner_error(self.start_line, self.start_column, message);
     }
 
     fn string(&mut self) {
         while let Some(c) = self.peek() {
-            match c {
-                '"' => break,
-                'n' => {
-                    self.line += 1;
-                    self.advance();
-                }
-                _ => {
-                    self.advance();
-                }
+            if c == '"' {
+                break;
             }
+            self.advance();
+            self.line_break(c);
         }
         if self.is_at_end() {
-            self.reporter.scanner_error(self.line, "string not closed");
+            self.error("string not closed");
+            return;
         }
         self.advance();
-        let string_literal = self.source[self.start + 1..self.current - 1].to_string();
+        let string_literal = self.text(self.start + 1..self.current - 1).to_string();
         self.add_token(TokenType::String(string_literal))
     }
 
+    /// A triple-quoted string, which may contain quotes, backslashes and
+    /// newlines exactly as written.
+    fn raw_string(&mut self) {
+        while !self.looking_at("\"\"\"") {
+            if self.is_at_end() {
+                self.error("raw string not closed");
+                return;
+            }
+            let c = self.advance();
+            self.line_break(c);
+        }
+        self.current += 3;
+        let string_literal = self.text(self.start + 3..self.current - 3).to_string();
+        self.add_token(TokenType::String(string_literal))
+    }
+
+    /// Whether `c` can begin a token or is whitespace between tokens.
+    fn starts_token(c: char) -> bool {
+        Self::is_alphanumeric(c) || "(){}[],.-+?:;*!=<>/\" \t\r\n".contains(c)
+    }
+
     fn is_alphanumeric(c: char) -> bool {
         c.is_ascii_digit() || c.is_ascii_alphabetic() || c == '_'
     }
 
     fn number(&mut self) {
-        while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+        if self.text(self.start..self.current) == "0" {
+            let radix = match self.peek() {
+                Some('x') => Some((16, "hexadecimal")),
+                Some('b') => Some((2, "binary")),
+                Some('o') => Some((8, "octal")),
+                _ => None,
+            };
+            if let Some((radix, base)) = radix {
+                self.advance();
+                return self.radix_number(radix, base);
+            }
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
             self.advance();
         }
-        if self.peek() == Some('.') && self.peek_next().map_or(false, |c| c.is_ascii_digit()) {
+        if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
             self.advance();
-            while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
                 self.advance();
             }
         }
-        let digits = &self.source[self.start..self.current];
-        self.add_token(TokenType::Number(
-            digits.parse::<f64>().expect("failed to parse float"),
-        ))
+        let digits = self.text(self.start..self.current);
+        // Literals without a fractional part are ints, unless too large for
+        // an i64.
+        let token_type = match digits.parse::<i64>() {
+            Ok(value) => TokenType::Int(value),
+            Err(_) => TokenType::Number(digits.parse::<f64>().expect("failed to parse float")),
+        };
+        self.add_token(token_type)
+    }
+
+    /// The digits of a `0x`, `0b` or `0o` literal, an int unless it is too
+    /// large for an i64. Larger values are rounded to the nearest f64.
+    fn radix_number(&mut self, radix: u32, base: &str) {
+        let digits_start = self.current;
+        while self.peek().is_some_and(Self::is_alphanumeric) {
+            self.advance();
+        }
+        let digits = self.text(digits_start..self.current);
+        if digits.is_empty() {
+            self.error(&format!("{} literal has no digits", base));
+            return;
+        }
+        let mut value = 0.0;
+        for c in digits.chars() {
+            let Some(digit) = c.to_digit(radix) else {
+                self.error(&format!("invalid digit '{}' in {} literal", c, base));
+                return;
+            };
+            value = value * radix as f64 + digit as f64;
+        }
+        let token_type = match i64::from_str_radix(digits, radix) {
+            Ok(value) => TokenType::Int(value),
+            Err(_) => TokenType::Number(value),
+        };
+        self.add_token(token_type)
     }
 
     fn identifier(&mut self) {
-        while self.peek().map_or(false, Self::is_alphanumeric) {
+        while self.peek().is_some_and(Self::is_alphanumeric) {
             self.advance();
         }
-        let token_type = match &self.source[self.start..self.current] {
+        let token_type = match self.text(self.start..self.current) {
             "and" => TokenType::And,
+            "as" => TokenType::As,
+            "break" => TokenType::Break,
+            "case" => TokenType::Case,
+            "catch" => TokenType::Catch,
             "class" => TokenType::Class,
+            "continue" => TokenType::Continue,
+            "default" => TokenType::Default,
+            "do" => TokenType::Do,
             "else" => TokenType::Else,
             "false" => TokenType::False,
+            "finally" => TokenType::Finally,
             "for" => TokenType::For,
             "fun" => TokenType::Fun,
             "if" => TokenType::If,
+            "import" => TokenType::Import,
+            "in" => TokenType::In,
             "nil" => TokenType::Nil,
             "or" => TokenType::Or,
+            "xor" => TokenType::Xor,
+            "not" => TokenType::Not,
             "print" => TokenType::Print,
+            "eprint" => TokenType::Eprint,
             "return" => TokenType::Return,
             "super" => TokenType::Super,
+            "switch" => TokenType::Switch,
             "this" => TokenType::This,
+            "throw" => TokenType::Throw,
             "true" => TokenType::True,
+            "try" => TokenType::Try,
             "var" => TokenType::Var,
             "while" => TokenType::While,
             _ => TokenType::Identifier,
         };
-        self.add_token(token_type)
+        self.add_token(token_type);
+        let token = self.pending.as_mut().expect("token was just added");
+        token.symbol = Some(Symbol::intern(&token.lexeme));
     }
 
     fn add_token(&mut self, token_type: TokenType) {
-        let lexeme = self.source[self.start..self.current].to_string();
-        self.tokens.push(Token {
+        let lexeme = self.text(self.start..self.current).to_string();
+        self.pending = Some(Token {
             token_type,
             lexeme,
-            line: self.line,
+            line: self.start_line,
+            column: self.start_column,
+            span: self.start..self.current,
+            symbol: None,
         });
     }
 
@@ -132,12 +404,53 @@ impl<'a> Scanner<'a> {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
+            '.' => {
+                if self.looking_at("..") {
+                    self.current += 2;
+                    self.add_token(TokenType::DotDotDot)
+                } else {
+                    self.add_token(TokenType::Dot)
+                }
+            }
+            '-' => {
+                if self.match_char('-') {
+                    self.add_token(TokenType::MinusMinus)
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::MinusEqual)
+                } else {
+                    self.add_token(TokenType::Minus)
+                }
+            }
+            '+' => {
+                if self.match_char('+') {
+                    self.add_token(TokenType::PlusPlus)
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::PlusEqual)
+                } else {
+                    self.add_token(TokenType::Plus)
+                }
+            }
+            '?' => {
+                if self.match_char('?') {
+                    self.add_token(TokenType::QuestionQuestion)
+                } else {
+                    self.add_token(TokenType::Question)
+                }
+            }
+            ':' => self.add_token(TokenType::Colon),
             ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+            '*' => {
+                if self.match_char('*') {
+                    self.add_token(TokenType::StarStar)
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::StarEqual)
+                } else {
+                    self.add_token(TokenType::Star)
+                }
+            }
             '!' => {
                 if self.match_char('=') {
                     self.add_token(TokenType::BangEqual)
@@ -169,37 +482,80 @@ impl<'a> Scanner<'a> {
             '/' => {
                 if self.match_char('/') {
                     while let Some(c) = self.peek() {
-                        if c == '\n' {
+                        if c == '\n' || c == '\r' {
                             break;
                         }
                         self.advance();
                     }
+                    if self.trivia {
+                        let text = self.text(self.start + 2..self.current).to_string();
+                        self.add_token(TokenType::Comment(text))
+                    }
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::SlashEqual)
                 } else {
                     self.add_token(TokenType::Slash)
                 }
             }
-            '"' => self.string(),
-            ' ' | '\t' | '\r' => {}
-            '\n' => self.line += 1,
+            '"' => {
+                if self.looking_at("\"\"") {
+                    self.current += 2;
+                    self.raw_string()
+                } else {
+                    self.string()
+                }
+            }
+            ' ' | '\t' => {}
+            '\n' | '\r' => self.line_break(c),
             c if c.is_ascii_digit() => self.number(),
             c if c.is_ascii_alphabetic() || c == '_' => self.identifier(),
             _ => {
-                let message = format!("encountered unexpected character: {}", c);
-                self.reporter.scanner_error(self.line, &message)
+                // Report a run of stray characters, such as pasted binary,
+                // once rather than once per character.
+                while self.peek().is_some_and(|c| !Self::starts_token(c)) {
+                    self.advance();
+                }
+                let run = self.text(self.start..self.current);
+                let message = if run.chars().count() == 1 {
+                    format!("encountered unexpected character: {}", run)
+                } else {
+                    format!("encountered unexpected characters: {}", run)
+                };
+                self.error(&message)
             }
         };
     }
 
-    pub fn scan_tokens(&mut self) {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+    /// The column `current` is at. This counts on from the previous token's
+    /// column where possible, so that the start of the line need not be kept.
+    fn column(&self) -> usize {
+        if self.line == self.start_line {
+            self.start_column + self.text(self.start..self.current).chars().count()
+        } else {
+            self.text(self.line_offset..self.current).chars().count() + 1
         }
-        self.tokens.push(Token {
-            token_type: TokenType::EndOfFile,
-            lexeme: "".to_string(),
-            line: self.line,
-        });
+    }
+
+    fn mark_start(&mut self) {
+        self.start_column = self.column();
+        self.start = self.current;
+        self.start_line = self.line;
+        // Text before the token is no longer needed. Dropping it only once
+        // it outweighs the rest of the buffer keeps the buffer bounded
+        // without moving what's left on every token.
+        if let Cow::Owned(buffer) = &mut self.source {
+            let consumed = self.current - self.offset;
+            if consumed >= CHUNK && consumed * 2 >= buffer.len() {
+                buffer.drain(..consumed);
+                self.offset = self.current;
+            }
+        }
+    }
+
+    /// Scan all the remaining tokens into `tokens`.
+    pub fn scan_tokens(&mut self) {
+        let tokens: Vec<Token> = self.by_ref().collect();
+        self.tokens.extend(tokens);
     }
 
     /// Obtain the scanned tokens and consume the scanner when scanning is finished
@@ -211,10 +567,47 @@ impl<'a> Scanner<'a> {
     }
 }
 
+/// Scans lazily, one token per call, so that a parser can pull tokens as it
+/// needs them. Errors are reported as the characters causing them are
+/// reached. The last token is always a single EndOfFile.
+impl Iterator for Scanner<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        while !self.is_at_end() {
+            self.mark_start();
+            self.scan_token();
+            if let Some(token) = self.pending.take() {
+                if !token.token_type.is_trivia() {
+                    self.end = (self.current, self.line, self.column());
+                }
+                return Some(token);
+            }
+        }
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        let (offset, line, column) = self.end;
+        Some(Token {
+            token_type: TokenType::EndOfFile,
+            lexeme: "".to_string(),
+            line,
+            column,
+            span: offset..offset,
+            symbol: None,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::reporter::StdoutReporter;
+    use crate::reporter::{BufferedReporter, StdoutReporter};
+    use crate::token::{attach_comments, line_and_column};
+    use std::cell::Cell;
+    use std::io;
+    use std::rc::Rc;
 
     #[test]
     fn test_scanner() {
@@ -229,34 +622,557 @@ mod tests {
                 Token {
                     token_type: TokenType::Var,
                     lexeme: "var".to_string(),
-                    line: 0
+                    line: 1,
+                    column: 1,
+                    span: 0..3,
+                    symbol: Some(Symbol::intern("var")),
                 },
                 Token {
                     token_type: TokenType::Identifier,
                     lexeme: "a".to_string(),
-                    line: 0
+                    line: 1,
+                    column: 5,
+                    span: 4..5,
+                    symbol: Some(Symbol::intern("a")),
                 },
                 Token {
                     token_type: TokenType::Equal,
                     lexeme: "=".to_string(),
-                    line: 0
+                    line: 1,
+                    column: 7,
+                    span: 6..7,
+                    symbol: None,
                 },
                 Token {
                     token_type: TokenType::Number(3.1),
                     lexeme: "3.1".to_string(),
-                    line: 0
+                    line: 1,
+                    column: 9,
+                    span: 8..11,
+                    symbol: None,
                 },
                 Token {
                     token_type: TokenType::Semicolon,
                     lexeme: ";".to_string(),
-                    line: 0
+                    line: 1,
+                    column: 12,
+                    span: 11..12,
+                    symbol: None,
                 },
                 Token {
                     token_type: TokenType::EndOfFile,
                     lexeme: "".to_string(),
-                    line: 0
+                    line: 1,
+                    column: 13,
+                    span: 12..12,
+                    symbol: None,
                 }
             ]
         );
     }
+
+    #[test]
+    fn test_increment_operators() {
+        let mut reporter = StdoutReporter::default();
+        let mut scanner = Scanner::new("a+++b - -c--=", &mut reporter);
+        scanner.scan_tokens();
+        let types: Vec<TokenType> = scanner
+            .into_tokens()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Identifier,
+                TokenType::PlusPlus,
+                TokenType::Plus,
+                TokenType::Identifier,
+                TokenType::Minus,
+                TokenType::Minus,
+                TokenType::Identifier,
+                TokenType::MinusMinus,
+                TokenType::Equal,
+                TokenType::EndOfFile,
+            ]
+        );
+    }
+
+    fn scan(source: &str) -> (Vec<Token>, Vec<String>) {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new(source, &mut reporter);
+        scanner.scan_tokens();
+        (scanner.into_tokens(), reporter.messages)
+    }
+
+    #[test]
+    fn test_radix_numbers() {
+        let (tokens, errors) = scan("0xFF 0b1010 0o755 0x7fffffffffffffff 0x8000000000000000 0");
+        assert!(errors.is_empty());
+        let values: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            values,
+            [
+                TokenType::Int(255),
+                TokenType::Int(10),
+                TokenType::Int(493),
+                TokenType::Int(i64::MAX),
+                TokenType::Number(9223372036854775808.0),
+                TokenType::Int(0),
+                TokenType::EndOfFile,
+            ]
+        );
+        assert_eq!(
+            scan("0b102").1,
+            ["[line 1, col 1] Error : invalid digit '2' in binary literal"]
+        );
+        assert_eq!(
+            scan("0x;").1,
+            ["[line 1, col 1] Error : hexadecimal literal has no digits"]
+        );
+        assert_eq!(
+            scan("0o8").1,
+            ["[line 1, col 1] Error : invalid digit '8' in octal literal"]
+        );
+    }
+
+    #[test]
+    fn test_int_and_float_literals() {
+        let (tokens, _) = scan("3 3.0 99999999999999999999");
+        let values: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            values,
+            [
+                TokenType::Int(3),
+                TokenType::Number(3.0),
+                TokenType::Number(1e20),
+                TokenType::EndOfFile,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_string() {
+        let (tokens, errors) = scan("x = \"\"\"C:\\dir\\ say \"hi\"\n\\d+\"\"\" + 1;");
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens[2].token_type,
+            TokenType::String("C:\\dir\\ say \"hi\"\n\\d+".to_string())
+        );
+        assert_eq!(tokens[3].line, 2);
+        let (tokens, _) = scan("\"\"\"\"\"\"");
+        assert_eq!(tokens[0].token_type, TokenType::String(String::new()));
+    }
+
+    #[test]
+    fn test_string() {
+        let (tokens, errors) = scan("\"no newline\" \"two\nlines\" end");
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::String("no newline".to_string())
+        );
+        assert_eq!(tokens[0].line, 1);
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::String("two\nlines".to_string())
+        );
+        assert_eq!(tokens[2].line, 2);
+    }
+
+    #[test]
+    fn test_columns() {
+        let (tokens, _) = scan("a\n\tb +\n  \"x\ny\" é");
+        let positions: Vec<(&str, usize, usize)> = tokens
+            .iter()
+            .map(|t| (t.lexeme.as_str(), t.line, t.column))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                ("a", 1, 1),
+                ("b", 2, 2),
+                ("+", 2, 4),
+                ("\"x\ny\"", 3, 3),
+                ("", 4, 3)
+            ]
+        );
+        assert_eq!(
+            scan("é\n  ✓").1,
+            [
+                "[line 1, col 1] Error : encountered unexpected character: é",
+                "[line 2, col 3] Error : encountered unexpected character: ✓"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unexpected_character_runs() {
+        let (tokens, errors) = scan("var x = 1; @@@ var y = 2;#");
+        assert_eq!(
+            errors,
+            [
+                "[line 1, col 12] Error : encountered unexpected characters: @@@",
+                "[line 1, col 26] Error : encountered unexpected character: #"
+            ]
+        );
+        let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme.as_str()).collect();
+        assert_eq!(
+            lexemes,
+            ["var", "x", "=", "1", ";", "var", "y", "=", "2", ";", ""]
+        );
+        assert_eq!(
+            scan("a@#b").1,
+            ["[line 1, col 2] Error : encountered unexpected characters: @#"]
+        );
+    }
+
+    #[test]
+    fn test_shebang() {
+        let (tokens, errors) = scan("#!/usr/bin/env lux\nx #");
+        assert_eq!(tokens[0].lexeme, "x");
+        assert_eq!(tokens[0].line, 2);
+        assert_eq!(
+            errors,
+            ["[line 2, col 3] Error : encountered unexpected character: #"]
+        );
+        assert_eq!(scan("#!lux").0.len(), 1);
+        assert_eq!(scan(" #!lux").1.len(), 1);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let source = "var a = 1; // one\n\nprint \"x\ny\";\n\"\"\"raw\nstring\"\"\" @\nb\n";
+        let lines = |source: &str| {
+            let (tokens, errors) = scan(source);
+            let lines: Vec<(String, usize, usize)> = tokens
+                .iter()
+                .map(|t| {
+                    (
+                        t.lexeme.replace("\r\n", "\n").replace('\r', "\n"),
+                        t.line,
+                        t.column,
+                    )
+                })
+                .collect();
+            (lines, errors)
+        };
+        let expected = lines(source);
+        assert_eq!(expected.0.last().unwrap(), &("".to_string(), 7, 2));
+        assert_eq!(
+            expected.1,
+            ["[line 6, col 11] Error : encountered unexpected character: @"]
+        );
+        assert_eq!(lines(&source.replace('\n', "\r\n")), expected);
+        assert_eq!(lines(&source.replace('\n', "\r")), expected);
+    }
+
+    #[test]
+    fn test_error_on_last_line() {
+        let (tokens, errors) = scan("a\nb @");
+        assert_eq!(
+            errors,
+            ["[line 2, col 3] Error : encountered unexpected character: @"]
+        );
+        let end = tokens.last().unwrap();
+        assert_eq!((end.line, end.column), (2, 2));
+    }
+
+    #[test]
+    fn test_trivia() {
+        let source = "// leading\nvar a = 1; // trailing\r\n\n//\nprint a;\n// last";
+        let mut reporter = StdoutReporter::default();
+        let tokens: Vec<Token> = Scanner::new(source, &mut reporter)
+            .with_trivia(true)
+            .collect();
+        let comments: Vec<(&Token, &str)> = tokens
+            .iter()
+            .filter_map(|token| match &token.token_type {
+                TokenType::Comment(text) => Some((token, text.as_str())),
+                _ => None,
+            })
+            .collect();
+        let positions: Vec<(&str, usize, usize)> = comments
+            .iter()
+            .map(|(token, text)| (*text, token.line, token.column))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                (" leading", 1, 1),
+                (" trailing", 2, 12),
+                ("", 4, 1),
+                (" last", 6, 1)
+            ]
+        );
+        for (token, _) in &comments {
+            assert_eq!(&source[token.span.clone()], token.lexeme);
+        }
+
+        // Without them the tokens are the same as in the default mode.
+        let code: Vec<Token> = tokens
+            .iter()
+            .filter(|token| !token.token_type.is_trivia())
+            .cloned()
+            .collect();
+        let (default, _) = scan(source);
+        assert_eq!(code, default);
+
+        let attached: Vec<(&str, Vec<&str>)> = attach_comments(&tokens)
+            .into_iter()
+            .filter(|(_, comments)| !comments.is_empty())
+            .map(|(token, comments)| {
+                let comments = comments.iter().map(|c| c.lexeme.as_str()).collect();
+                (token.lexeme.as_str(), comments)
+            })
+            .collect();
+        assert_eq!(
+            attached,
+            [
+                ("var", vec!["// leading"]),
+                ("print", vec!["// trailing", "//"]),
+                ("", vec!["// last"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new("a\nb\n", &mut reporter);
+        scanner.scan_tokens();
+        assert_eq!(scanner.tokens.len(), 3);
+        scanner.reset("c @\n", true);
+        scanner.scan_tokens();
+        let positions: Vec<(&str, usize, usize)> = scanner
+            .tokens
+            .iter()
+            .map(|t| (t.lexeme.as_str(), t.line, t.column))
+            .collect();
+        assert_eq!(positions, [("c", 3, 1), ("", 3, 2)]);
+        scanner.reset("d", false);
+        scanner.scan_tokens();
+        assert_eq!(scanner.tokens[0].line, 1);
+        assert_eq!(scanner.into_tokens().len(), 2);
+        assert_eq!(
+            reporter.messages,
+            ["[line 3, col 3] Error : encountered unexpected character: @"]
+        );
+    }
+
+    #[test]
+    fn test_lazy_scanning() {
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::new("a @ b", &mut reporter);
+        assert_eq!(scanner.next().map(|t| t.lexeme), Some("a".to_string()));
+        assert_eq!(scanner.next().map(|t| t.lexeme), Some("b".to_string()));
+        assert_eq!(
+            scanner.next().map(|t| t.token_type),
+            Some(TokenType::EndOfFile)
+        );
+        assert_eq!(scanner.next(), None);
+        scanner.scan_tokens();
+        assert!(scanner.into_tokens().is_empty());
+        assert_eq!(
+            reporter.messages,
+            ["[line 1, col 3] Error : encountered unexpected character: @"]
+        );
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let source = "#!/usr/bin/env lux
+var s = \"two
+lines\" + \"\"\"raw
+text\"\"\"; // é
+print s @@ 1.5 ...;";
+        let mut reporter = BufferedReporter::default();
+        let streamed: Vec<Token> = Scanner::from_reader(source.as_bytes(), &mut reporter).collect();
+        let (tokens, errors) = scan(source);
+        assert_eq!(streamed, tokens);
+        assert_eq!(reporter.messages, errors);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Produces `lines` copies of `line` without holding them all, noting
+    /// how many bytes have been handed out so far.
+    struct Generated {
+        line: &'static [u8],
+        lines: usize,
+        position: usize,
+        read: Rc<Cell<usize>>,
+    }
+
+    impl io::Read for Generated {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let remaining = self.line.len() * self.lines - self.position;
+            let count = buf.len().min(remaining);
+            for (i, byte) in buf[..count].iter_mut().enumerate() {
+                *byte = self.line[(self.position + i) % self.line.len()];
+            }
+            self.position += count;
+            self.read.set(self.position);
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn test_streaming_large_source() {
+        let read = Rc::new(Cell::new(0));
+        let lines = 100_000;
+        let generated = Generated {
+            line: b"var total = total + 12.5; // running sum\n",
+            lines,
+            position: 0,
+            read: Rc::clone(&read),
+        };
+        let mut reporter = BufferedReporter::default();
+        let scanner = Scanner::from_reader(io::BufReader::new(generated), &mut reporter);
+        let mut count = 0;
+        let mut last = None;
+        for token in scanner {
+            // No further ahead than the reader's own buffer.
+            assert!(read.get() - token.span.end <= 16 * 1024);
+            count += 1;
+            last = Some(token);
+        }
+        assert!(read.get() > 4_000_000);
+        assert_eq!(count, 7 * lines + 1);
+        let last = last.unwrap();
+        assert_eq!(last.token_type, TokenType::EndOfFile);
+        // Just after the last semicolon, ahead of the trailing comment.
+        let comment = " // running sum\n".len();
+        assert_eq!((last.line, last.span.start), (lines, read.get() - comment));
+        assert!(reporter.messages.is_empty());
+    }
+
+    #[test]
+    fn test_streaming_long_line() {
+        // Copies of a line without a newline, which make one long line.
+        let generated = Generated {
+            line: b"var total = total + 12.5; ",
+            lines: 100_000,
+            position: 0,
+            read: Rc::new(Cell::new(0)),
+        };
+        let mut reporter = BufferedReporter::default();
+        let mut scanner = Scanner::from_reader(io::BufReader::new(generated), &mut reporter);
+        let mut count = 0;
+        while let Some(token) = scanner.next() {
+            // The scanner holds a window of the line, not all of it.
+            assert!(
+                scanner.source.len() <= 3 * CHUNK,
+                "{}",
+                scanner.source.len()
+            );
+            assert_eq!(token.line, 1);
+            count += 1;
+        }
+        drop(scanner);
+        assert_eq!(count, 7 * 100_000 + 1);
+        assert!(reporter.messages.is_empty());
+
+        // Characters cut in two by the end of a chunk come out whole.
+        let source = "var s = \"é€😀\" + 1; ".repeat(1000);
+        let mut reporter = BufferedReporter::default();
+        let streamed: Vec<Token> = Scanner::from_reader(source.as_bytes(), &mut reporter).collect();
+        let (tokens, errors) = scan(&source);
+        assert_eq!(streamed, tokens);
+        assert!(reporter.messages.is_empty() && errors.is_empty());
+
+        // One left unfinished at the end of the source is an error.
+        let mut reporter = BufferedReporter::default();
+        let streamed: Vec<Token> =
+            Scanner::from_reader(&b"var s;\xe2\x82"[..], &mut reporter).collect();
+        assert_eq!(streamed.len(), 4);
+        assert_eq!(
+            reporter.messages,
+            ["[line 1, col 1] Error : can't read source: stream did not contain valid UTF-8"]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let source = "( ) { } [ ] , . ... - + ? : ; / * ** // comment
+! != = == > >= < <= -= += ?? /= *=
+name _x9 \"str\" \"two
+lines\" \"\"\"raw\"\"\" 42 4.5 0xff \"é\" é
+and as break case catch class continue default do else false finally for fun
+if import in nil or xor not print eprint return super switch this throw true
+try var while";
+        let (tokens, errors) = scan(source);
+        assert_eq!(errors.len(), 1);
+        for token in &tokens {
+            assert_eq!(&source[token.span.clone()], token.lexeme, "{}", token);
+            assert_eq!(
+                line_and_column(source, &token.span),
+                (token.line, token.column),
+                "{}",
+                token
+            );
+        }
+        let string = tokens
+            .iter()
+            .find(|token| token.lexeme.starts_with("\"two"))
+            .unwrap();
+        assert_eq!(&source[string.span.clone()], "\"two\nlines\"");
+        let end = tokens.last().unwrap();
+        assert_eq!(end.span, source.len()..source.len());
+    }
+
+    #[test]
+    fn test_non_ascii() {
+        let (tokens, errors) = scan("print \"café – 🦀\"; // naïve ✓\nx");
+        assert!(errors.is_empty());
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Print,
+                TokenType::String("café – 🦀".to_string()),
+                TokenType::Semicolon,
+                TokenType::Identifier,
+                TokenType::EndOfFile,
+            ]
+        );
+        let (tokens, errors) = scan("a – b");
+        assert_eq!(
+            errors,
+            ["[line 1, col 3] Error : encountered unexpected character: –"]
+        );
+        assert_eq!(tokens.len(), 3);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let (tokens, errors) = scan("total Total totals total _total this \"total\" 1");
+        assert!(errors.is_empty());
+        let symbols: Vec<Option<Symbol>> = tokens.iter().map(|token| token.symbol).collect();
+        let total = Symbol::intern("total");
+        assert_eq!(symbols[0], Some(total));
+        assert_eq!(symbols[3], Some(total));
+        // Spellings differing in case, by a suffix or by a prefix don't share
+        // a symbol.
+        for other in [&symbols[1], &symbols[2], &symbols[4]] {
+            assert_ne!(*other, Some(total));
+        }
+        assert_ne!(symbols[1], symbols[2]);
+        assert_eq!(symbols[5], Some(Symbol::intern("this")));
+        // Only names are interned.
+        assert_eq!(symbols[6..], [None, None, None]);
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let (tokens, errors) = scan("x\n\"never\nclosed");
+        assert_eq!(errors, ["[line 2, col 1] Error : string not closed"]);
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(types, [TokenType::Identifier, TokenType::EndOfFile]);
+    }
+
+    #[test]
+    fn test_unterminated_raw_string() {
+        let (_, errors) = scan("\n\"\"\" never\nclosed");
+        assert_eq!(errors, ["[line 2, col 1] Error : raw string not closed"]);
+    }
 }
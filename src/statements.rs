@@ -1,4 +1,6 @@
-use crate::expressions::Expr;
+use std::rc::Rc;
+
+use crate::expressions::{Expr, VarExpr};
 use crate::token::Token;
 
 pub trait Accept<R> {
@@ -7,8 +9,24 @@ pub trait Accept<R> {
 
 pub trait StmtVisitor<R> {
     fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> R;
+    fn visit_eprint_stmt(&mut self, stmt: &EprintStmt) -> R;
     fn visit_expr_stmt(&mut self, stmt: &ExprStmt) -> R;
     fn visit_var_stmt(&mut self, stmt: &VarStmt) -> R;
+    fn visit_import_stmt(&mut self, stmt: &ImportStmt) -> R;
+    fn visit_destructure_stmt(&mut self, stmt: &DestructureStmt) -> R;
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> R;
+    fn visit_do_while_stmt(&mut self, stmt: &DoWhileStmt) -> R;
+    fn visit_for_in_stmt(&mut self, stmt: &ForInStmt) -> R;
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> R;
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> R;
+    fn visit_break_stmt(&mut self, stmt: &BreakStmt) -> R;
+    fn visit_continue_stmt(&mut self, stmt: &ContinueStmt) -> R;
+    fn visit_function_stmt(&mut self, stmt: &FunctionStmt) -> R;
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> R;
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> R;
+    fn visit_throw_stmt(&mut self, stmt: &ThrowStmt) -> R;
+    fn visit_switch_stmt(&mut self, stmt: &SwitchStmt) -> R;
+    fn visit_try_stmt(&mut self, stmt: &TryStmt) -> R;
 }
 
 #[macro_export]
@@ -31,25 +49,131 @@ macro_rules! stmt {
     };
 }
 
-stmt!(PrintStmt, (expr, Expr));
+// The values are written separated by spaces, then a newline.
+stmt!(PrintStmt, (expressions, Vec<Expr>));
+stmt!(EprintStmt, (expr, Expr));
 stmt!(ExprStmt, (expr, Expr));
-stmt!(VarStmt, (name, Token), (initializer, Option<Expr>));
+// `var a = 1, b;` declares each name in turn, so later initializers can
+// refer to earlier names.
+stmt!(VarStmt, (declarators, Vec<(Token, Option<Expr>)>));
+// `import "m.lux";` copies the module's exports into the importing scope,
+// while `import "m.lux" as m;` binds the module itself to `m`.
+stmt!(
+    ImportStmt,
+    (keyword, Token),
+    (path, String),
+    (alias, Option<Token>)
+);
+// `var (a, b) = pair;` binds the elements of a tuple or list of exactly that
+// length.
+stmt!(DestructureStmt, (names, Vec<Token>), (initializer, Expr));
+// `increment` is the third clause of a desugared for loop. It is kept apart
+// from the body so that `continue` still runs it.
+stmt!(
+    WhileStmt,
+    (condition, Expr),
+    (body, Stmt),
+    (increment, Option<Expr>)
+);
+stmt!(DoWhileStmt, (body, Stmt), (condition, Expr));
+stmt!(ForInStmt, (variable, Token), (iterable, Expr), (body, Stmt));
+stmt!(BreakStmt, (keyword, Token));
+stmt!(ContinueStmt, (keyword, Token));
+// The body is shared with every function value created from the declaration.
+// A getter is a method declared without a parameter list, called on access.
+// `rest` is a final `...name` parameter, which collects any arguments past
+// `params` into a list.
+stmt!(
+    FunctionStmt,
+    (name, Token),
+    (params, Vec<Token>),
+    (rest, Option<Token>),
+    (body, Rc<Vec<Stmt>>),
+    (is_getter, bool)
+);
+stmt!(ReturnStmt, (keyword, Token), (value, Option<Expr>));
+// Field declarations with defaults are gathered into `field_initializer`, a
+// method assigning each field on `this`, which runs on every new instance
+// before `init`.
+stmt!(
+    ClassStmt,
+    (name, Token),
+    (superclass, Option<VarExpr>),
+    (methods, Vec<FunctionStmt>),
+    (field_initializer, Option<FunctionStmt>)
+);
+stmt!(BlockStmt, (statements, Vec<Stmt>));
+stmt!(ThrowStmt, (keyword, Token), (value, Expr));
+// Only the first case whose value equals the subject runs; there is no
+// fallthrough into the next case.
+stmt!(
+    SwitchStmt,
+    (subject, Expr),
+    (cases, Vec<(Expr, Vec<Stmt>)>),
+    (default, Option<Vec<Stmt>>)
+);
+// At least one of `catch`, which names the variable bound to the caught
+// value, and `finally` is present.
+stmt!(
+    TryStmt,
+    (body, Vec<Stmt>),
+    (catch, Option<(Token, Vec<Stmt>)>),
+    (finally, Option<Vec<Stmt>>)
+);
+stmt!(
+    IfStmt,
+    (condition, Expr),
+    (then_branch, Stmt),
+    (else_branch, Option<Stmt>)
+);
 
 // Box is necessary because expression created inside a function
 // needs to be owned
 #[derive(Clone, Debug)]
 pub enum Stmt {
     Print(Box<PrintStmt>),
+    Eprint(Box<EprintStmt>),
     Expr(Box<ExprStmt>),
     Var(Box<VarStmt>),
+    Import(Box<ImportStmt>),
+    Destructure(Box<DestructureStmt>),
+    While(Box<WhileStmt>),
+    DoWhile(Box<DoWhileStmt>),
+    ForIn(Box<ForInStmt>),
+    Block(Box<BlockStmt>),
+    If(Box<IfStmt>),
+    Break(Box<BreakStmt>),
+    Continue(Box<ContinueStmt>),
+    Function(Box<FunctionStmt>),
+    Return(Box<ReturnStmt>),
+    Class(Box<ClassStmt>),
+    Throw(Box<ThrowStmt>),
+    Switch(Box<SwitchStmt>),
+    Try(Box<TryStmt>),
 }
 
 impl<R> Accept<R> for Stmt {
     fn accept(&self, visitor: &mut impl StmtVisitor<R>) -> R {
         match self {
             Self::Print(stmt) => stmt.accept(visitor),
+            Self::Eprint(stmt) => stmt.accept(visitor),
             Self::Expr(stmt) => stmt.accept(visitor),
             Self::Var(stmt) => stmt.accept(visitor),
+            Self::Import(stmt) => stmt.accept(visitor),
+            Self::Destructure(stmt) => stmt.accept(visitor),
+            Self::While(stmt) => stmt.accept(visitor),
+            Self::DoWhile(stmt) => stmt.accept(visitor),
+            Self::ForIn(stmt) => stmt.accept(visitor),
+            Self::Block(stmt) => stmt.accept(visitor),
+            Self::If(stmt) => stmt.accept(visitor),
+            Self::Break(stmt) => stmt.accept(visitor),
+            Self::Continue(stmt) => stmt.accept(visitor),
+            Self::Function(stmt) => stmt.accept(visitor),
+            Self::Return(stmt) => stmt.accept(visitor),
+            Self::Class(stmt) => stmt.accept(visitor),
+            Self::Throw(stmt) => stmt.accept(visitor),
+            Self::Switch(stmt) => stmt.accept(visitor),
+            Self::Try(stmt) => stmt.accept(visitor),
         }
     }
 }
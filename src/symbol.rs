@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::{LazyLock, Mutex};
+
+/// An interned name, as the scanner makes for every identifier and keyword.
+/// Two symbols are equal exactly when their names are, so comparing and
+/// hashing them costs no more than for an integer.
+///
+/// Names are interned for the life of the process and shared by every
+/// thread, so symbols from different scanners and interpreters agree.
+/// This is a deliberate trade-off: each distinct name is kept, leaked,
+/// until the process exits, which in exchange lets tokens, scopes and
+/// values carry a plain `Copy` symbol with no interner to thread through.
+/// A program has only so many distinct identifiers, so the memory held
+/// grows with the variety of names seen rather than with how often they
+/// are used. Interning takes a lock, so it happens where names come in,
+/// as the scanner makes tokens or the host defines or reads a global, and
+/// not on every variable access. Names the interpreter refers to itself,
+/// such as `this`, are constants.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// A map keyed by symbols, which hashes them far more cheaply than the
+/// default hasher would.
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+
+/// Hashes a symbol by spreading the bits of its number, which is already
+/// unique. It is not meant for hashing anything else.
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u32(byte as u32);
+        }
+    }
+
+    fn write_u32(&mut self, number: u32) {
+        self.0 = (self.0.rotate_left(5) ^ number as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+impl Interner {
+    fn insert(&mut self, name: &'static str) -> Symbol {
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name);
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+}
+
+/// The names of the constant symbols, in the order of their numbers.
+const PREDEFINED: [&str; 3] = ["this", "super", "fields"];
+
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(|| {
+    let mut interner = Interner::default();
+    for name in PREDEFINED {
+        interner.insert(name);
+    }
+    Mutex::new(interner)
+});
+
+impl Symbol {
+    pub const THIS: Symbol = Symbol(0);
+    pub const SUPER: Symbol = Symbol(1);
+    /// The name of the function that sets a class's field defaults.
+    pub const FIELDS: Symbol = Symbol(2);
+
+    pub fn intern(name: &str) -> Self {
+        let mut interner = INTERNER.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+        // Leaked so that `as_str` can hand out the name without holding the
+        // lock; there is one copy of each distinct name.
+        interner.insert(Box::leak(name.into()))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let interner = INTERNER.lock().unwrap_or_else(|error| error.into_inner());
+        interner.names[self.0 as usize]
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Symbol({:?})", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let count = Symbol::intern("count");
+        assert_eq!(Symbol::intern("count"), count);
+        assert_eq!(Symbol::intern(String::from("count").as_str()), count);
+        assert_eq!(count.as_str(), "count");
+
+        // Distinct spellings, including ones differing only in case or by a
+        // prefix, get distinct symbols.
+        let others = ["Count", "counter", "coun", "count_", "", "cöunt"];
+        for other in others {
+            assert_ne!(Symbol::intern(other), count, "{}", other);
+            assert_eq!(Symbol::intern(other).as_str(), other);
+        }
+
+        // Symbols agree between threads.
+        let elsewhere = std::thread::spawn(|| Symbol::intern("count"))
+            .join()
+            .unwrap();
+        assert_eq!(elsewhere, count);
+        assert_eq!(format!("{} {:?}", count, count), "count Symbol(\"count\")");
+
+        let mut map = SymbolMap::default();
+        for (index, name) in others.iter().enumerate() {
+            map.insert(Symbol::intern(name), index);
+        }
+        map.insert(count, others.len());
+        assert_eq!(map.len(), others.len() + 1);
+        assert_eq!(map[&Symbol::intern("coun")], 2);
+    }
+
+    #[test]
+    fn test_predefined() {
+        for (symbol, name) in [
+            (Symbol::THIS, "this"),
+            (Symbol::SUPER, "super"),
+            (Symbol::FIELDS, "fields"),
+        ] {
+            assert_eq!(Symbol::intern(name), symbol);
+            assert_eq!(symbol.as_str(), name);
+        }
+    }
+}
@@ -1,4 +1,7 @@
 use std::fmt;
+use std::ops::Range;
+
+use crate::symbol::Symbol;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
@@ -7,14 +10,20 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    DotDotDot,
     Minus,
     Plus,
+    Question,
+    Colon,
 
     Semicolon,
     Slash,
     Star,
+    StarStar,
 
     //OneOrTwoCharacterTokens.
     Bang,
@@ -25,33 +34,68 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    MinusEqual,
+    MinusMinus,
+    PlusEqual,
+    PlusPlus,
+    QuestionQuestion,
+    SlashEqual,
+    StarEqual,
 
     //Literals.
     Identifier,
     String(String),
+    Int(i64),
     Number(f64),
 
     //Keywords.
     And,
+    As,
+    Break,
+    Case,
+    Catch,
     Class,
+    Continue,
+    Default,
+    Do,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
+    In,
     Nil,
     Or,
+    Xor,
+    Not,
     Print,
+    Eprint,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
+    /// A `//` comment, holding the text after the slashes. Only a scanner
+    /// made `with_trivia` produces these.
+    Comment(String),
+
     EndOfFile,
 }
 
+impl TokenType {
+    /// Whether this is a comment, which means nothing to the parser.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Self::Comment(_))
+    }
+}
+
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -62,7 +106,57 @@ impl fmt::Display for TokenType {
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
+    /// The line, counting from 1, that the token starts on.
     pub line: usize,
+    /// The character, counting from 1, that the token starts at within its
+    /// line.
+    pub column: usize,
+    /// The byte offsets of the token in the source, covering all of it even
+    /// when it spans several lines.
+    pub span: Range<usize>,
+    /// The lexeme of an identifier or keyword, interned by the scanner so
+    /// that variables can be looked up without comparing strings.
+    pub symbol: Option<Symbol>,
+}
+
+impl Token {
+    /// The lexeme as a symbol, interning it now if the scanner did not.
+    pub fn symbol(&self) -> Symbol {
+        self.symbol.unwrap_or_else(|| Symbol::intern(&self.lexeme))
+    }
+}
+
+/// The line and column, both counting from 1, that `span`
+/// starts at in `source`, numbered as for `Token`. Lines may end in `\n`,
+/// `\r\n` or `\r`.
+pub fn line_and_column(source: &str, span: &Range<usize>) -> (usize, usize) {
+    let before = &source[..span.start];
+    let mut line = 1;
+    let mut line_offset = 0;
+    for (index, c) in before.char_indices() {
+        let next = before[index + c.len_utf8()..].chars().next();
+        if c == '\n' || (c == '\r' && next != Some('\n')) {
+            line += 1;
+            line_offset = index + 1;
+        }
+    }
+    (line, before[line_offset..].chars().count() + 1)
+}
+
+/// Pair each token in `tokens` other than a comment with the comments just
+/// before it, for a tool such as a formatter to keep them with the code
+/// they describe. Comments at the end go with the EndOfFile token.
+pub fn attach_comments(tokens: &[Token]) -> Vec<(&Token, Vec<&Token>)> {
+    let mut attached = Vec::new();
+    let mut comments = Vec::new();
+    for token in tokens {
+        if token.token_type.is_trivia() {
+            comments.push(token);
+        } else {
+            attached.push((token, std::mem::take(&mut comments)));
+        }
+    }
+    attached
 }
 
 impl fmt::Display for Token {
@@ -0,0 +1,849 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::class::{Class, Instance};
+use crate::expressions::LiteralValue;
+use crate::function::Callable;
+use crate::module::Module;
+
+/// Runtime values produced by the interpreter. Unlike `LiteralValue`, which
+/// only covers what can be written down in the source, this also holds the
+/// composite values that are shared by reference.
+#[derive(Clone, Debug)]
+pub enum Value {
+    /// Strings are immutable, so they are shared rather than copied when a
+    /// value is cloned, e.g. on every read of a variable.
+    String(Rc<str>),
+    Int(i64),
+    Number(f64),
+    Bool(bool),
+    Nil,
+    List(Rc<RefCell<Vec<Value>>>),
+    /// An immutable sequence such as `(1, "a")`. A cycle through tuples
+    /// always passes through a list or map, since a tuple can only hold
+    /// values that existed before it.
+    Tuple(Rc<Vec<Value>>),
+    Map(Rc<RefCell<HashMap<HashableValue, Value>>>),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    /// A module imported with `import "path" as name;`.
+    Module(Rc<Module>),
+}
+
+impl Value {
+    pub fn list(values: Vec<Value>) -> Self {
+        Self::List(Rc::new(RefCell::new(values)))
+    }
+
+    pub fn tuple(values: Vec<Value>) -> Self {
+        Self::Tuple(Rc::new(values))
+    }
+
+    pub fn map(entries: HashMap<HashableValue, Value>) -> Self {
+        Self::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /// The value of an int or float as a float.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(value) => Some(*value as f64),
+            Self::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The name of this value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "string",
+            Self::Int(_) => "int",
+            Self::Number(_) => "float",
+            Self::Bool(_) => "bool",
+            Self::Nil => "nil",
+            Self::List(_) => "list",
+            Self::Tuple(_) => "tuple",
+            Self::Map(_) => "map",
+            Self::Callable(_) => "function",
+            Self::Class(_) => "class",
+            Self::Instance(_) => "instance",
+            Self::Module(_) => "module",
+        }
+    }
+}
+
+/// A value usable as a map key. Only the immutable kinds (strings, numbers,
+/// bools and nil) qualify; lists and maps could change after insertion and
+/// are rejected by `new`, as are tuples, which may hold lists, and
+/// functions, classes, instances and modules.
+///
+/// Numbers are keyed by value, so `1` and `1.0` are the same key, whether
+/// written as an int or a float. `-0` is
+/// the same key as `0`, and every NaN is one and the same key so that an
+/// entry stored under NaN can be found again.
+#[derive(Clone, Debug)]
+pub struct HashableValue(Value);
+
+impl HashableValue {
+    pub fn new(value: Value) -> Result<Self, &'static str> {
+        match value {
+            Value::String(_) | Value::Int(_) | Value::Number(_) | Value::Bool(_) | Value::Nil => {
+                Ok(Self(value))
+            }
+            Value::List(_)
+            | Value::Tuple(_)
+            | Value::Map(_)
+            | Value::Callable(_)
+            | Value::Class(_)
+            | Value::Instance(_)
+            | Value::Module(_) => Err("map keys must be strings, numbers, bools or nil"),
+        }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+
+    fn normalized(number: f64) -> f64 {
+        if number.is_nan() {
+            f64::NAN
+        } else if number == 0.0 {
+            0.0
+        } else {
+            number
+        }
+    }
+
+    /// Position of each kind when sorting keys of different kinds.
+    fn rank(&self) -> u8 {
+        match self.0 {
+            Value::Nil => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::Number(_) => 2,
+            _ => 3,
+        }
+    }
+}
+
+impl From<&str> for HashableValue {
+    fn from(key: &str) -> Self {
+        Self(Value::String(key.into()))
+    }
+}
+
+impl Hash for HashableValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match &self.0 {
+            Value::String(value) => value.hash(state),
+            // Ints hash as the float they convert to, so that equal keys of
+            // either kind hash alike.
+            Value::Int(_) | Value::Number(_) => {
+                let number = self.0.as_f64().unwrap_or_default();
+                Self::normalized(number).to_bits().hash(state)
+            }
+            Value::Bool(value) => value.hash(state),
+            _ => {}
+        }
+    }
+}
+
+impl PartialEq for HashableValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HashableValue {}
+
+impl PartialOrd for HashableValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders nil, then bools, numbers and strings, which gives maps a
+/// deterministic printing order.
+impl Ord for HashableValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.0, &other.0) {
+            (Value::String(left), Value::String(right)) => left.cmp(right),
+            (Value::Int(left), Value::Int(right)) => left.cmp(right),
+            (Value::Bool(left), Value::Bool(right)) => left.cmp(right),
+            _ => match (self.0.as_f64(), other.0.as_f64()) {
+                (Some(left), Some(right)) => {
+                    Self::normalized(left).total_cmp(&Self::normalized(right))
+                }
+                _ => self.rank().cmp(&other.rank()),
+            },
+        }
+    }
+}
+
+impl From<LiteralValue> for Value {
+    fn from(literal: LiteralValue) -> Self {
+        match literal {
+            LiteralValue::String(value) => Self::String(value.into()),
+            LiteralValue::Int(value) => Self::Int(value),
+            LiteralValue::Number(value) => Self::Number(value),
+            LiteralValue::Bool(value) => Self::Bool(value),
+            LiteralValue::Nil => Self::Nil,
+        }
+    }
+}
+
+/// Render a value the way `print` shows it. Top-level strings are written
+/// as-is while strings nested in lists and maps are quoted and escaped, so
+/// `[1, [2, "a"]]` reads back as a literal. Map entries are sorted by key and
+/// a list or map that contains itself is shown as `[...]` or `{...}`.
+pub fn fmt_value(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, false, &mut Vec::new());
+    out
+}
+
+fn write_value(out: &mut String, value: &Value, quoted: bool, visiting: &mut Vec<*const ()>) {
+    match value {
+        Value::String(value) if quoted => out.push_str(&format!("{:?}", value)),
+        Value::String(value) => out.push_str(value),
+        Value::Int(value) => out.push_str(&value.to_string()),
+        Value::Number(value) => out.push_str(&fmt_number(*value)),
+        Value::Bool(value) => out.push_str(&value.to_string()),
+        Value::Nil => out.push_str("nil"),
+        Value::Callable(callable) => out.push_str(&callable.to_string()),
+        Value::Class(class) => out.push_str(&class.to_string()),
+        Value::Instance(instance) => out.push_str(&instance.to_string()),
+        Value::Module(module) => out.push_str(&module.to_string()),
+        Value::Tuple(values) => {
+            out.push('(');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, value, true, visiting);
+            }
+            // A one-element tuple keeps its comma, as in its literal.
+            if values.len() == 1 {
+                out.push(',');
+            }
+            out.push(')');
+        }
+        Value::List(values) => {
+            let id = Rc::as_ptr(values) as *const ();
+            if visiting.contains(&id) {
+                out.push_str("[...]");
+                return;
+            }
+            visiting.push(id);
+            out.push('[');
+            for (i, value) in values.borrow().iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, value, true, visiting);
+            }
+            out.push(']');
+            visiting.pop();
+        }
+        Value::Map(entries) => {
+            let id = Rc::as_ptr(entries) as *const ();
+            if visiting.contains(&id) {
+                out.push_str("{...}");
+                return;
+            }
+            visiting.push(id);
+            let entries = entries.borrow();
+            let mut keys: Vec<&HashableValue> = entries.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, key.value(), true, visiting);
+                out.push_str(": ");
+                write_value(out, &entries[key], true, visiting);
+            }
+            out.push('}');
+            visiting.pop();
+        }
+    }
+}
+
+/// Render a number for display. Every conversion of a number to text goes
+/// through here so that output stays consistent.
+///
+/// This is the shortest text that reads back as the same number, without a
+/// trailing `.0` when it is integral: `4`, `0.30000000000000004`. There is
+/// never an exponent, which number literals can't have, so `num` reads back
+/// whatever `str` gives for a finite number. The other values are `nan`,
+/// `inf` and `-inf`, and negative zero keeps its sign, `-0`.
+pub fn fmt_number(number: f64) -> String {
+    if number.is_nan() {
+        "nan".to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+/// Largest digit count accepted by `fmt_fixed` and `fmt_precision`.
+const MAX_DIGITS: f64 = 100.0;
+
+/// `digits` as a count, if it is a whole number from `min` to `MAX_DIGITS`.
+fn digit_count(digits: f64, min: f64) -> Option<usize> {
+    if digits.fract() != 0.0 || digits < min || digits > MAX_DIGITS {
+        None
+    } else {
+        Some(digits as usize)
+    }
+}
+
+/// Render `number` with exactly `digits` digits after the decimal point,
+/// e.g. `fmt_fixed(3.14159, 2)` is `"3.14"`.
+///
+/// Rounding works on the exact binary value of the number, with exact ties
+/// going to the even digit. So `2.675` becomes `"2.67"` because the double
+/// closest to 2.675 is slightly below it, while `0.125` becomes `"0.12"`.
+pub fn fmt_fixed(number: f64, digits: f64) -> Result<String, &'static str> {
+    let digits =
+        digit_count(digits, 0.0).ok_or("digit count must be an integer between 0 and 100")?;
+    if !number.is_finite() {
+        return Ok(fmt_number(number));
+    }
+    // Adding zero turns -0 into 0 so it does not print with a sign.
+    Ok(format!("{:.*}", digits, number + 0.0))
+}
+
+/// Render `number` with `digits` significant digits, switching to
+/// exponential notation (`"1.23e3"`) when the exponent is below -6 or not
+/// smaller than `digits`, and to fixed notation (`"1230"` for 4 digits)
+/// otherwise. Rounding follows `fmt_fixed`.
+pub fn fmt_precision(number: f64, digits: f64) -> Result<String, &'static str> {
+    let digits =
+        digit_count(digits, 1.0).ok_or("digit count must be an integer between 1 and 100")?;
+    if !number.is_finite() {
+        return Ok(fmt_number(number));
+    }
+    let exponential = format!("{:.*e}", digits - 1, number + 0.0);
+    let exponent: i32 = exponential
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse().ok())
+        .unwrap_or(0);
+    if exponent < -6 || exponent >= digits as i32 {
+        Ok(exponential)
+    } else {
+        let decimals = (digits as i32 - 1 - exponent) as usize;
+        Ok(format!("{:.*}", decimals, number + 0.0))
+    }
+}
+
+/// Structural equality: lists and maps compare by content rather than by
+/// reference. A pair of containers already being compared further up is
+/// assumed equal, which keeps self-referential structures from recursing
+/// forever. Functions, classes, instances and modules are only equal to
+/// themselves.
+/// An int equals a float of the same value, so `3 == 3.0`.
+pub fn deep_eq(left: &Value, right: &Value) -> bool {
+    deep_eq_at(left, right, &mut Vec::new())
+}
+
+fn deep_eq_at(left: &Value, right: &Value, visiting: &mut Vec<(*const (), *const ())>) -> bool {
+    match (left, right) {
+        (Value::String(left), Value::String(right)) => left == right,
+        (Value::Int(left), Value::Int(right)) => left == right,
+        (Value::Int(_) | Value::Number(_), Value::Int(_) | Value::Number(_)) => {
+            left.as_f64() == right.as_f64()
+        }
+        (Value::Bool(left), Value::Bool(right)) => left == right,
+        (Value::Nil, Value::Nil) => true,
+        (Value::Callable(left), Value::Callable(right)) => Rc::ptr_eq(left, right),
+        (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+        (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+        (Value::Module(left), Value::Module(right)) => Rc::ptr_eq(left, right),
+        (Value::Tuple(left), Value::Tuple(right)) => {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right.iter())
+                    .all(|(left, right)| deep_eq_at(left, right, visiting))
+        }
+        (Value::List(left), Value::List(right)) => {
+            let pair = (
+                Rc::as_ptr(left) as *const (),
+                Rc::as_ptr(right) as *const (),
+            );
+            if Rc::ptr_eq(left, right) || visiting.contains(&pair) {
+                return true;
+            }
+            visiting.push(pair);
+            let (left, right) = (left.borrow(), right.borrow());
+            let equal = left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right.iter())
+                    .all(|(left, right)| deep_eq_at(left, right, visiting));
+            visiting.pop();
+            equal
+        }
+        (Value::Map(left), Value::Map(right)) => {
+            let pair = (
+                Rc::as_ptr(left) as *const (),
+                Rc::as_ptr(right) as *const (),
+            );
+            if Rc::ptr_eq(left, right) || visiting.contains(&pair) {
+                return true;
+            }
+            visiting.push(pair);
+            let (left, right) = (left.borrow(), right.borrow());
+            let equal = left.len() == right.len()
+                && left.iter().all(|(key, left)| {
+                    right
+                        .get(key)
+                        .is_some_and(|right| deep_eq_at(left, right, visiting))
+                });
+            visiting.pop();
+            equal
+        }
+        _ => false,
+    }
+}
+
+/// Reference equality: lists, maps, tuples and objects are identical only
+/// to themselves, however alike their contents. Strings, numbers, bools and
+/// nil have no identity beyond their value, though an int is never
+/// identical to a float.
+pub fn identical(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::List(left), Value::List(right)) => Rc::ptr_eq(left, right),
+        (Value::Map(left), Value::Map(right)) => Rc::ptr_eq(left, right),
+        (Value::Tuple(left), Value::Tuple(right)) => Rc::ptr_eq(left, right),
+        (Value::Int(_), Value::Number(_)) | (Value::Number(_), Value::Int(_)) => false,
+        _ => deep_eq(left, right),
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        deep_eq(self, other)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", fmt_value(self))
+    }
+}
+
+/// Failure to express a runtime value as JSON. `path` locates the offending
+/// value from the root, e.g. `$.points[2]`.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq)]
+pub struct ConversionError {
+    pub path: String,
+    pub message: String,
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.path)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for ConversionError {}
+
+#[cfg(feature = "serde")]
+impl Value {
+    /// Convert host data into a runtime value. Objects become maps and
+    /// arrays become lists. JSON numbers that fit an `i64` become ints and
+    /// the rest become floats.
+    pub fn from_json(json: &serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Self::Nil,
+            serde_json::Value::Bool(value) => Self::Bool(*value),
+            serde_json::Value::Number(value) => match value.as_i64() {
+                Some(value) => Self::Int(value),
+                None => Self::Number(value.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(value) => Self::String(value.as_str().into()),
+            serde_json::Value::Array(values) => {
+                Self::list(values.iter().map(Self::from_json).collect())
+            }
+            serde_json::Value::Object(entries) => Self::map(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.as_str().into(), Self::from_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Convert a runtime value back into host data. Ints are emitted as JSON
+    /// integers and floats as JSON floats, even when they have no fractional
+    /// part, so both `1` and `1.0` survive a round trip as they were.
+    pub fn to_json(&self) -> Result<serde_json::Value, ConversionError> {
+        let mut path = String::from("$");
+        let mut visiting = Vec::new();
+        self.to_json_at(&mut path, &mut visiting)
+    }
+
+    fn to_json_at(
+        &self,
+        path: &mut String,
+        visiting: &mut Vec<*const ()>,
+    ) -> Result<serde_json::Value, ConversionError> {
+        let error = |path: &String, message: &str| ConversionError {
+            path: path.clone(),
+            message: message.to_string(),
+        };
+        match self {
+            Self::Nil => Ok(serde_json::Value::Null),
+            Self::Bool(value) => Ok(serde_json::Value::Bool(*value)),
+            Self::String(value) => Ok(serde_json::Value::String(value.to_string())),
+            Self::Callable(_) => Err(error(path, "function has no JSON form")),
+            Self::Class(_) => Err(error(path, "class has no JSON form")),
+            Self::Instance(_) => Err(error(path, "instance has no JSON form")),
+            Self::Module(_) => Err(error(path, "module has no JSON form")),
+            Self::Int(value) => Ok(serde_json::Value::from(*value)),
+            Self::Number(value) => serde_json::Number::from_f64(*value)
+                .map(serde_json::Value::Number)
+                .ok_or_else(|| error(path, "non-finite number has no JSON form")),
+            Self::Tuple(values) => {
+                let mut array = Vec::new();
+                for (i, value) in values.iter().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("[{}]", i));
+                    array.push(value.to_json_at(path, visiting)?);
+                    path.truncate(len);
+                }
+                Ok(serde_json::Value::Array(array))
+            }
+            Self::List(values) => {
+                let id = Rc::as_ptr(values) as *const ();
+                if visiting.contains(&id) {
+                    return Err(error(path, "cyclic list has no JSON form"));
+                }
+                visiting.push(id);
+                let mut array = Vec::new();
+                for (i, value) in values.borrow().iter().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("[{}]", i));
+                    array.push(value.to_json_at(path, visiting)?);
+                    path.truncate(len);
+                }
+                visiting.pop();
+                Ok(serde_json::Value::Array(array))
+            }
+            Self::Map(entries) => {
+                let id = Rc::as_ptr(entries) as *const ();
+                if visiting.contains(&id) {
+                    return Err(error(path, "cyclic map has no JSON form"));
+                }
+                visiting.push(id);
+                let mut object = serde_json::Map::new();
+                for (key, value) in entries.borrow().iter() {
+                    let Value::String(key) = key.value() else {
+                        return Err(error(
+                            path,
+                            &format!("map key {} is not a string", fmt_value(key.value())),
+                        ));
+                    };
+                    let len = path.len();
+                    path.push_str(&format!(".{}", key));
+                    object.insert(key.to_string(), value.to_json_at(path, visiting)?);
+                    path.truncate(len);
+                }
+                visiting.pop();
+                Ok(serde_json::Value::Object(object))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "serde")]
+    use serde_json::json;
+
+    fn cyclic_list() -> Value {
+        let value = Value::list(vec![Value::Number(1.0), Value::Nil]);
+        if let Value::List(values) = &value {
+            values.borrow_mut()[1] = value.clone();
+        }
+        value
+    }
+
+    #[test]
+    fn test_fmt_value() {
+        let nested = Value::list(vec![
+            Value::Number(1.0),
+            Value::list(vec![Value::Number(2.0), Value::String("a".into())]),
+        ]);
+        assert_eq!(fmt_value(&nested), r#"[1, [2, "a"]]"#);
+        assert_eq!(fmt_value(&Value::String("a".into())), "a");
+        assert_eq!(
+            fmt_value(&Value::list(vec![Value::String("say \"hi\"\n".into())])),
+            r#"["say \"hi\"\n"]"#
+        );
+        let map = Value::map(HashMap::from([
+            ("b".into(), Value::Bool(true)),
+            ("a".into(), Value::Nil),
+        ]));
+        assert_eq!(fmt_value(&map), r#"{"a": nil, "b": true}"#);
+    }
+
+    #[test]
+    fn test_fmt_value_empty() {
+        assert_eq!(fmt_value(&Value::list(vec![])), "[]");
+        assert_eq!(fmt_value(&Value::map(HashMap::new())), "{}");
+    }
+
+    #[test]
+    fn test_fmt_value_cyclic() {
+        assert_eq!(fmt_value(&cyclic_list()), "[1, [...]]");
+    }
+
+    #[test]
+    fn test_fmt_number() {
+        for (number, expected) in [
+            (4.0, "4"),
+            (-12.0, "-12"),
+            (0.0, "0"),
+            (-0.0, "-0"),
+            (0.5, "0.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (123456789012345680000.0, "123456789012345680000"),
+            (1e21, "1000000000000000000000"),
+            (-2.5e25, "-25000000000000000000000000"),
+            (0.000001, "0.000001"),
+            (1.5e-7, "0.00000015"),
+            (f64::NAN, "nan"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ] {
+            assert_eq!(fmt_number(number), expected);
+            if number.is_finite() {
+                assert_eq!(expected.parse::<f64>(), Ok(number));
+            }
+        }
+        assert_eq!(
+            Value::list(vec![Value::Number(2.0), Value::Number(f64::NAN)]).to_string(),
+            "[2, nan]"
+        );
+    }
+
+    #[test]
+    fn test_fmt_fixed() {
+        let cases = [
+            (3.14158, 2.0, "3.14"),
+            (3.14158, 0.0, "3"),
+            (0.005, 2.0, "0.01"),
+            (2.675, 2.0, "2.67"),
+            (1.005, 2.0, "1.00"),
+            (0.125, 2.0, "0.12"),
+            (0.375, 2.0, "0.38"),
+            (2.5, 0.0, "2"),
+            (-1.5, 0.0, "-2"),
+            (-0.0, 2.0, "0.00"),
+            (1e21, 1.0, "1000000000000000000000.0"),
+            (f64::INFINITY, 2.0, "inf"),
+        ];
+        for (number, digits, expected) in cases {
+            assert_eq!(
+                fmt_fixed(number, digits).as_deref(),
+                Ok(expected),
+                "{}",
+                number
+            );
+        }
+    }
+
+    #[test]
+    fn test_fmt_precision() {
+        let cases = [
+            (1234.5678, 3.0, "1.23e3"),
+            (1234.5678, 4.0, "1235"),
+            (1234.5678, 6.0, "1234.57"),
+            (9999.0, 2.0, "1.0e4"),
+            (0.000123, 2.0, "0.00012"),
+            (0.0000001234, 2.0, "1.2e-7"),
+            (2.675, 3.0, "2.67"),
+            (0.0, 3.0, "0.00"),
+            (-42.0, 1.0, "-4e1"),
+        ];
+        for (number, digits, expected) in cases {
+            assert_eq!(
+                fmt_precision(number, digits).as_deref(),
+                Ok(expected),
+                "{}",
+                number
+            );
+        }
+    }
+
+    #[test]
+    fn test_fmt_digits_validation() {
+        let error = Err("digit count must be an integer between 0 and 100");
+        assert_eq!(fmt_fixed(1.0, -1.0), error);
+        assert_eq!(fmt_fixed(1.0, 1.5), error);
+        assert_eq!(fmt_fixed(1.0, 101.0), error);
+        assert_eq!(
+            fmt_precision(1.0, 0.0),
+            Err("digit count must be an integer between 1 and 100")
+        );
+        assert_eq!(fmt_precision(1.0, 100.0).map(|s| s.len()), Ok(101));
+    }
+
+    #[test]
+    fn test_deep_eq() {
+        let make = || {
+            Value::list(vec![
+                Value::Number(1.0),
+                Value::list(vec![Value::String("a".into())]),
+            ])
+        };
+        assert!(deep_eq(&make(), &make()));
+        assert!(deep_eq(&Value::list(vec![]), &Value::list(vec![])));
+        assert!(!deep_eq(&make(), &Value::list(vec![Value::Number(1.0)])));
+        assert!(!deep_eq(
+            &Value::list(vec![Value::Number(1.0)]),
+            &Value::list(vec![Value::String("1".into())])
+        ));
+        let left = Value::map(HashMap::from([
+            ("x".into(), Value::Number(1.0)),
+            ("y".into(), make()),
+        ]));
+        let right = Value::map(HashMap::from([
+            ("y".into(), make()),
+            ("x".into(), Value::Number(1.0)),
+        ]));
+        assert!(deep_eq(&left, &right));
+    }
+
+    fn number_key(number: f64) -> HashableValue {
+        HashableValue::new(Value::Number(number)).unwrap()
+    }
+
+    #[test]
+    fn test_hashable_value_numbers() {
+        let mut map = HashMap::new();
+        map.insert(number_key(1.0), "one");
+        map.insert(number_key(-0.0), "zero");
+        map.insert(number_key(f64::NAN), "nan");
+        assert_eq!(map.get(&number_key(1.0)), Some(&"one"));
+        assert_eq!(map.get(&number_key(0.0)), Some(&"zero"));
+        assert_eq!(map.get(&number_key(-f64::NAN)), Some(&"nan"));
+        assert_eq!(map.get(&"1".into()), None);
+        assert_eq!(
+            map.get(&HashableValue::new(Value::Bool(true)).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hashable_value_rejects_mutable_kinds() {
+        assert!(HashableValue::new(Value::list(vec![])).is_err());
+        assert!(HashableValue::new(Value::map(HashMap::new())).is_err());
+        assert!(HashableValue::new(Value::Nil).is_ok());
+    }
+
+    #[test]
+    fn test_fmt_value_mixed_keys() {
+        let map = Value::map(HashMap::from([
+            ("a".into(), Value::Nil),
+            (number_key(2.0), Value::Nil),
+            (HashableValue::new(Value::Bool(false)).unwrap(), Value::Nil),
+            (number_key(-1.0), Value::Nil),
+        ]));
+        assert_eq!(
+            fmt_value(&map),
+            r#"{false: nil, -1: nil, 2: nil, "a": nil}"#
+        );
+    }
+
+    #[test]
+    fn test_identical() {
+        let list = Value::list(vec![Value::Int(1)]);
+        assert!(identical(&list, &list.clone()));
+        assert!(!identical(&list, &Value::list(vec![Value::Int(1)])));
+        let map = Value::map(HashMap::new());
+        assert!(identical(&map, &map.clone()));
+        assert!(!identical(&map, &Value::map(HashMap::new())));
+        assert!(!identical(&Value::tuple(vec![]), &Value::tuple(vec![])));
+        assert!(identical(&Value::Int(1), &Value::Int(1)));
+        assert!(!identical(&Value::Int(1), &Value::Number(1.0)));
+        assert!(identical(
+            &Value::String("a".into()),
+            &Value::String("a".into())
+        ));
+    }
+
+    #[test]
+    fn test_deep_eq_cyclic() {
+        assert!(deep_eq(&cyclic_list(), &cyclic_list()));
+        assert!(!deep_eq(
+            &cyclic_list(),
+            &Value::list(vec![Value::Number(1.0)])
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let json = json!({
+            "name": "lux",
+            "version": 1,
+            "ratio": 0.5,
+            "tags": ["a", null, true, [2, 2.5]],
+            "nested": {"empty": [], "inner": {}}
+        });
+        let value = Value::from_json(&json);
+        assert_eq!(value.to_json(), Ok(json));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_integral_floats_stay_floats() {
+        let value = Value::from_json(&json!([1.0, -3.0, 1.5, 2]));
+        assert_eq!(value.to_json(), Ok(json!([1.0, -3.0, 1.5, 2])));
+        let json = value.to_json().unwrap();
+        assert!(json[0].is_f64());
+        assert!(json[1].is_f64());
+        assert!(json[3].is_i64());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_conversion_error_names_path() {
+        let value = Value::from_json(&json!({"points": [0, 1, 2]}));
+        if let Value::Map(entries) = &value {
+            if let Some(Value::List(points)) = entries.borrow().get(&"points".into()) {
+                points.borrow_mut()[2] = Value::Number(f64::NAN);
+            }
+        }
+        let error = value.to_json().unwrap_err();
+        assert_eq!(error.path, "$.points[2]");
+        assert_eq!(
+            error.to_string(),
+            "non-finite number has no JSON form at $.points[2]"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_cyclic_list() {
+        let error = cyclic_list().to_json().unwrap_err();
+        assert_eq!(error.path, "$[1]");
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Run the interpreter on a script holding `source`.
+fn run_script(name: &str, source: &str) -> Output {
+    run_script_with_flags(name, &[], source)
+}
+
+fn run_script_with_flags(name: &str, flags: &[&str], source: &str) -> Output {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("lux-cli-{}-{}.lux", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox-rs"))
+        .args(flags)
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn test_exit_status() {
+    let output = run_script("ok", "print 1;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+
+    let output = run_script("runtime", "print 1;\nprint 1 / 0;\nprint 2;");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 2] runtime error: division by zero\n"
+    );
+
+    let output = run_script("thrown", "throw \"boom\";");
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run_script("recursion", "fun f() { f(); }\nf();");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] runtime error: maximum call depth (1000) exceeded\n  \
+         in f (line 1), 1000 times / in f (line 2)\n"
+    );
+
+    let output = run_script("syntax", "print 1;\nprint 1 +;");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 2, col 10] Error at ';': Expected expression, found ';'\n"
+    );
+
+    let output = run_script("resolve", "{ var a = a; }");
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1, col 11] Error at 'a': Can't read local variable in its own initializer.\n"
+    );
+
+    // Rejected while parsing, rather than overflowing the stack once run.
+    let chain = format!("print 1{};", " + 1".repeat(10_000));
+    let output = run_script("chain", &chain);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1, col 1029] Error at '+': Expression too deeply nested.\n"
+    );
+}
+
+#[test]
+fn test_no_warn() {
+    let source = "if (true); print 1;";
+    let output = run_script("warn", source);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1, col 10] Warning at ';': Empty body after 'if'; the ';' ends the statement.\n"
+    );
+
+    let output = run_script_with_flags("no-warn", &["--no-warn"], source);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(output.stderr, b"");
+}
+
+#[test]
+fn test_prompt_numbers_lines_across_entries() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox-rs"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"var a = 1;\nprint a;\n{}\nprint a + 1;\nprint a +;\nprint a / 0;\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "> > 1\n> > 2\n> > > "
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 5, col 10] Error at ';': Expected expression, found ';'\n\
+         [line 6] runtime error: division by zero\n"
+    );
+}